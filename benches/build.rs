@@ -0,0 +1,46 @@
+//! Measures `ServiceCollection::build` for large collections.
+//! Run with `cargo bench --bench build`
+use minfac::{Registered, ServiceCollection};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const SERVICE_COUNT: usize = 10_000;
+const ITERATIONS: u32 = 20;
+
+fn create_collection() -> ServiceCollection {
+    let mut collection = ServiceCollection::new();
+    for _ in 0..SERVICE_COUNT / 4 {
+        collection.register(|| 1i32);
+        collection.register_shared(|| Arc::new(2i64));
+        collection.with::<Registered<i32>>().register(|x| x as u32);
+        collection
+            .with::<Registered<Arc<i64>>>()
+            .register_shared(|x| Arc::new(*x as u64));
+        collection
+            .with::<(
+                Registered<i32>,
+                Registered<u32>,
+                Registered<Arc<i64>>,
+                Registered<Arc<u64>>,
+            )>()
+            .register(|(a, b, c, d)| a as i128 + b as i128 + *c as i128 + *d as i128);
+    }
+    collection
+}
+
+fn main() {
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let collection = create_collection();
+        let start = Instant::now();
+        let provider = collection.build().expect("All dependencies are registered");
+        total += start.elapsed();
+        drop(provider);
+    }
+    println!(
+        "build with {SERVICE_COUNT} services: {:?} per iteration",
+        total / ITERATIONS
+    );
+}
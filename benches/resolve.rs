@@ -0,0 +1,125 @@
+//! Measures the hot paths of resolving services with criterion.
+//! Run with `cargo bench --bench resolve`
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use minfac::{Registered, ServiceCollection};
+use std::sync::Arc;
+
+const INSTANCE_COUNT: usize = 1_000;
+const FILLER_COUNT: usize = 10_000;
+
+fn create_collection() -> ServiceCollection {
+    let mut collection = ServiceCollection::new();
+    for _ in 0..INSTANCE_COUNT {
+        collection.register(|| 1i32);
+    }
+    collection.register(|| 2i64);
+    collection.register_shared(|| Arc::new(3u64));
+    collection
+}
+
+fn get(c: &mut Criterion) {
+    let provider = create_collection().build().unwrap();
+    c.bench_function("get single instance", |b| {
+        b.iter(|| black_box(provider.get::<i64>()))
+    });
+
+    let mut collection = ServiceCollection::new();
+    for _ in 0..FILLER_COUNT {
+        collection.register(|| 1i32);
+    }
+    collection.register(|| 2i64);
+    let provider = collection.build().unwrap();
+    c.bench_function("get single instance among 10000 services", |b| {
+        b.iter(|| black_box(provider.get::<i64>()))
+    });
+}
+
+fn get_all(c: &mut Criterion) {
+    let provider = create_collection().build().unwrap();
+    c.bench_function("get_all over 1000 instances", |b| {
+        b.iter(|| black_box(provider.get_all::<i32>().sum::<i32>()))
+    });
+}
+
+fn shared(c: &mut Criterion) {
+    let provider = create_collection().build().unwrap();
+    provider.get::<Arc<u64>>();
+    c.bench_function("shared cached", |b| {
+        b.iter(|| black_box(provider.get::<Arc<u64>>()))
+    });
+    c.bench_function("shared first initialization", |b| {
+        b.iter_batched(
+            || create_collection().build().unwrap(),
+            |provider| {
+                black_box(provider.get::<Arc<u64>>().map(|x| *x));
+                provider
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+trait Handler: Send + Sync {
+    fn id(&self) -> usize;
+}
+
+impl Handler for u32 {
+    fn id(&self) -> usize {
+        *self as usize
+    }
+}
+
+fn get_all_aliased_shared(c: &mut Criterion) {
+    let mut collection = ServiceCollection::new();
+    for _ in 0..INSTANCE_COUNT {
+        collection
+            .register_shared(|| Arc::new(1u32))
+            .alias(|x| x as Arc<dyn Handler>);
+    }
+    let provider = collection.build().unwrap();
+    provider.warm_up();
+    c.bench_function("get_all over 1000 shared services", |b| {
+        b.iter(|| {
+            black_box(
+                provider
+                    .get_all::<Arc<u32>>()
+                    .map(|x| x.id())
+                    .sum::<usize>(),
+            )
+        })
+    });
+    c.bench_function("get_all over 1000 aliased shared services", |b| {
+        b.iter(|| {
+            black_box(
+                provider
+                    .get_all::<Arc<dyn Handler>>()
+                    .map(|x| x.id())
+                    .sum::<usize>(),
+            )
+        })
+    });
+}
+
+fn factory_build(c: &mut Criterion) {
+    let mut collection = create_collection();
+    collection
+        .with::<Registered<u8>>()
+        .register_shared(|x| Arc::new(x as u16));
+    let factory = collection.build_factory::<u8>().unwrap();
+    c.bench_function("factory build per request", |b| {
+        b.iter(|| {
+            let provider = factory.build(1);
+            black_box(provider.get::<Arc<u16>>().map(|x| *x))
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    get,
+    get_all,
+    get_all_aliased_shared,
+    shared,
+    factory_build
+);
+criterion_main!(benches);
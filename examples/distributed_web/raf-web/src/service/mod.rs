@@ -47,12 +47,10 @@ impl HostedService for HostedServer {
             route.handler.register_dummy_dependency(&mut web_collection);
         }
 
-        let factory = Arc::new(
-            web_collection
-                .with_parent(p)
-                .build_factory::<WebProviderRemainer>()
-                .unwrap(),
-        );
+        let factory = web_collection
+            .with_parent(p)
+            .build_factory::<WebProviderRemainer>()
+            .unwrap();
         let cloned = self.routes.clone();
 
         async move {
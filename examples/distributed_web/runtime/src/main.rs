@@ -37,10 +37,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let provider = collection.build().expect("all dependencies to resolve");
 
-    let services: Vec<_> = provider
-        .get_all::<Box<dyn HostedService>>()
-        .map(|i| i.start())
-        .collect();
+    let services = provider.get_all_mapped(|i: Box<dyn HostedService>| i.start());
 
     println!("Start {} servers", services.len());
     join_all(services).await;
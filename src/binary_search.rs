@@ -78,7 +78,7 @@ mod tests {
         assert_eq!(Some(1), binary_search_last_by_key(&[0, 0, 1], &0, |a| a));
     }
 
-    fn test_first_or_last<'a>(i: impl Fn(&[usize], &usize) -> Option<usize>) {
+    fn test_first_or_last(i: impl Fn(&[usize], &usize) -> Option<usize>) {
         assert_eq!(Some(0), (i)(&[0, 1, 2], &0));
         assert_eq!(Some(1), (i)(&[0, 1, 2], &1));
         assert_eq!(Some(2), (i)(&[0, 1, 2], &2));
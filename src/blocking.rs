@@ -0,0 +1,156 @@
+//! Minimal executor for shared services, which are created by a future.
+//! It doesn't depend on a runtime, so the future must not rely on one to make progress.
+//! Shared services created on a background thread are completed here as well.
+
+use core::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+use std::{
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+    task::Wake,
+    thread::{self, JoinHandle, Thread},
+};
+
+/// Turns the output of a shared creator into the service
+pub(crate) trait Completion<R> {
+    type Output;
+    fn complete(output: R) -> Self::Output;
+}
+
+/// The creator returns the service directly
+pub(crate) struct Immediate;
+
+impl<T> Completion<T> for Immediate {
+    type Output = T;
+    fn complete(output: T) -> T {
+        output
+    }
+}
+
+/// The creator returns a future, which is polled on the current thread until it's ready
+pub(crate) struct BlockOn;
+
+impl<F: Future> Completion<F> for BlockOn {
+    type Output = F::Output;
+    fn complete(output: F) -> F::Output {
+        block_on(output)
+    }
+}
+
+/// The creator returns a closure, which constructs the service on a new thread
+pub(crate) struct Spawn;
+
+impl<T: Send + Sync + 'static, F: FnOnce() -> Arc<T> + Send + 'static> Completion<F> for Spawn {
+    type Output = Arc<Background<T>>;
+    fn complete(output: F) -> Self::Output {
+        Arc::new(Background {
+            service: OnceLock::new(),
+            thread: Mutex::new(Some(thread::spawn(output))),
+        })
+    }
+}
+
+/// Shared service, which is constructed on a background thread, see
+/// `ServiceBuilder::register_shared_background`. Dropping it waits for the thread to finish.
+pub struct Background<T> {
+    service: OnceLock<Arc<T>>,
+    thread: Mutex<Option<JoinHandle<Arc<T>>>>,
+}
+
+impl<T> Background<T> {
+    /// Returns true, if `wait` doesn't block
+    pub fn is_ready(&self) -> bool {
+        self.service.get().is_some()
+            || self
+                .thread
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_ref()
+                .map_or(true, |thread| thread.is_finished())
+    }
+
+    /// Blocks the current thread until the service is constructed
+    ///
+    /// # Panics
+    /// Panics if the creator panicked, with its payload on the first call
+    pub fn wait(&self) -> Arc<T> {
+        if let Some(service) = self.service.get() {
+            return service.clone();
+        }
+        let mut thread = self.thread.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(handle) = thread.take() {
+            match handle.join() {
+                Ok(service) => {
+                    let _ = self.service.set(service);
+                }
+                Err(payload) => std::panic::resume_unwind(payload),
+            }
+        }
+        drop(thread);
+        self.service
+            .get()
+            .expect("Background construction panicked")
+            .clone()
+    }
+}
+
+impl<T> Drop for Background<T> {
+    // Services captured by the creator must not outlive the ServiceProvider
+    fn drop(&mut self) {
+        let thread = self
+            .thread
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(handle) = thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(x) => return x,
+            // Spurious wakeups just lead to another poll
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_on_waits_for_wake_from_other_thread() {
+        let (sender, receiver) = std::sync::mpsc::channel::<Waker>();
+        let handle = thread::spawn(move || receiver.recv().unwrap().wake());
+        let mut sender = Some(sender);
+        let result = block_on(core::future::poll_fn(|cx| match sender.take() {
+            Some(s) => {
+                s.send(cx.waker().clone()).unwrap();
+                Poll::Pending
+            }
+            None => Poll::Ready(42),
+        }));
+        handle.join().unwrap();
+        assert_eq!(42, result);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{strategy::Identifyable, ServiceProvider, Strategy};
+use alloc::boxed::Box;
+use core::{future::Future, pin::Pin};
+use tokio::task::{JoinError, JoinSet};
+pub use tokio_util::sync::CancellationToken;
+
+/// Future of a running HostedService
+pub type HostedFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Long-running service like a webserver or a background job, which is started by `run_hosted_services`.
+/// HostedServices are registered as `Box<dyn HostedService>`, so each library can contribute its own.
+pub trait HostedService: Send {
+    /// Runs the service until it's done or `shutdown` is cancelled, after which it should stop gracefully
+    fn start(self: Box<Self>, shutdown: CancellationToken) -> HostedFuture;
+}
+
+/// Resolves all `Box<dyn HostedService>`, runs them on the current tokio runtime and waits until all of them
+/// stopped. Cancelling `shutdown` requests all services to stop. If a service panics, the remaining services
+/// are requested to stop as well and the first error is returned once all of them stopped.
+///
+/// Services might keep shared services alive until they stop, so `provider` must not be dropped before the
+/// returned future completes. Dropping the future early aborts all services.
+/// ```
+/// use minfac::{CancellationToken, HostedFuture, HostedService, ServiceCollection};
+///
+/// struct Server;
+/// impl HostedService for Server {
+///     fn start(self: Box<Self>, shutdown: CancellationToken) -> HostedFuture {
+///         Box::pin(async move { shutdown.cancelled().await })
+///     }
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| Box::new(Server) as Box<dyn HostedService>);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let shutdown = CancellationToken::new();
+/// shutdown.cancel();
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(minfac::run_hosted_services(&provider, shutdown)).unwrap();
+/// ```
+pub async fn run_hosted_services<TS: Strategy + 'static>(
+    provider: &ServiceProvider<TS>,
+    shutdown: CancellationToken,
+) -> Result<(), JoinError>
+where
+    Box<dyn HostedService>: Identifyable<TS::Id>,
+{
+    let mut tasks = JoinSet::new();
+    for service in provider.get_all::<Box<dyn HostedService>>() {
+        tasks.spawn(service.start(shutdown.child_token()));
+    }
+
+    let mut result = Ok(());
+    while let Some(next) = tasks.join_next().await {
+        if let Err(e) = next {
+            shutdown.cancel();
+            result = result.and(Err(e));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceCollection;
+    use core::sync::atomic::{AtomicI32, Ordering};
+
+    static STOPPED: AtomicI32 = AtomicI32::new(0);
+
+    struct Waiting;
+    impl HostedService for Waiting {
+        fn start(self: Box<Self>, shutdown: CancellationToken) -> HostedFuture {
+            Box::pin(async move {
+                shutdown.cancelled().await;
+                STOPPED.fetch_add(1, Ordering::Relaxed);
+            })
+        }
+    }
+
+    struct Panicking;
+    impl HostedService for Panicking {
+        fn start(self: Box<Self>, _: CancellationToken) -> HostedFuture {
+            Box::pin(async { panic!("Failing service") })
+        }
+    }
+
+    #[test]
+    fn panicking_service_stops_remaining_services() {
+        let mut collection = ServiceCollection::new();
+        collection.register(|| Box::new(Waiting) as Box<dyn HostedService>);
+        collection.register(|| Box::new(Panicking) as Box<dyn HostedService>);
+        collection.register(|| Box::new(Waiting) as Box<dyn HostedService>);
+        let provider = collection.build().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(run_hosted_services(&provider, CancellationToken::new()));
+
+        assert!(result.unwrap_err().is_panic());
+        assert_eq!(2, STOPPED.load(Ordering::Relaxed));
+    }
+}
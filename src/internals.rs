@@ -0,0 +1,37 @@
+//! Access to data, which is computed internally while building a ServiceProvider, e.g. for external graph
+//! analysis or custom validators. This module is unstable and might change in any release, even in patches.
+
+use crate::{
+    resolvable::{build_position_index, PrecheckContext},
+    strategy::Strategy,
+    BuildError, Resolvable, TypeManifest,
+};
+use alloc::vec::Vec;
+
+/// Returns the positions within `manifest`, which the dependency `R` is resolved from. These are the edges
+/// used for cycle detection, when a service depending on `R` is built.
+/// ```
+/// use minfac::{internals, AllRegistered, Registered, ServiceCollection};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1u8);
+/// collection.register(|| 2u8);
+/// let manifest = collection.build().expect("Configuration is valid").export_manifest();
+///
+/// assert_eq!(Ok(vec![1]), internals::positions::<Registered<u8>, _>(&manifest));
+/// assert_eq!(Ok(vec![0, 1]), internals::positions::<AllRegistered<u8>, _>(&manifest));
+/// assert!(internals::positions::<Registered<u16>, _>(&manifest).is_err());
+/// ```
+pub fn positions<R: Resolvable<TS>, TS: Strategy + 'static>(
+    manifest: &TypeManifest<TS>,
+) -> Result<Vec<usize>, BuildError<TS>> {
+    let (types, kinds): (Vec<_>, Vec<_>) = manifest
+        .services
+        .iter()
+        .map(|(id, _, kind)| (*id, *kind))
+        .unzip();
+    let index = build_position_index::<TS>(&types);
+    let ctx = PrecheckContext::new(&types, &kinds).with_index(&index);
+    R::precheck(&ctx)?;
+    Ok(R::iter_positions(&ctx).collect())
+}
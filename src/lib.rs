@@ -17,34 +17,98 @@ use alloc::{
     sync::Arc,
     vec::Vec,
 };
-use core::{any::type_name, cell::RefCell, fmt::Debug, marker::PhantomData};
+use core::{
+    any::{type_name, Any},
+    cell::RefCell,
+    fmt::{self, Debug, Display, Formatter},
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
 use lifetime::default_error_handler;
+use service_provider::{SharedServiceFinalizer, SharedServiceTeardown};
 use service_provider_factory::ServiceProviderFactoryBuilder;
-use std::sync::OnceLock;
-use strategy::{Identifyable, Strategy};
-use untyped::{AutoFreePointer, UntypedFn};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use untyped::{AutoFreePointer, FactoryContext, UntypedFn};
 
-mod binary_search;
+/// Search primitives on sorted slices, which minfac uses to find the positions of services by their id.
+/// They are public for authors of custom Strategies, but exempt from semver and might change in any release.
+/// ```
+/// use minfac::binary_search::{binary_search_first_by_key, binary_search_last_by_key};
+///
+/// let ids = [1, 2, 2, 2, 3];
+/// assert_eq!(Some(1), binary_search_first_by_key(&ids, &2, |x| x));
+/// assert_eq!(Some(3), binary_search_last_by_key(&ids, &2, |x| x));
+/// assert_eq!(None, binary_search_first_by_key(&ids, &4, |x| x));
+/// ```
+pub mod binary_search;
+mod blocking;
+#[cfg(feature = "hosted")]
+mod hosted;
+#[cfg(feature = "internals")]
+pub mod internals;
 mod lifetime;
+#[cfg(feature = "plugin")]
+mod plugin;
 mod resolvable;
+mod resolver;
 mod service_provider;
 mod service_provider_factory;
+#[cfg(feature = "serde")]
+mod snapshot;
 #[cfg(feature = "stable_abi")]
 pub mod stable_abi;
 mod strategy;
+/// Helpers for tests of applications, which use minfac
+#[cfg(all(feature = "std", any(debug_assertions, feature = "runtime_checks")))]
+pub mod test;
 mod untyped;
 
+pub use blocking::Background;
+#[cfg(feature = "hosted")]
+pub use hosted::{run_hosted_services, CancellationToken, HostedFuture, HostedService};
 pub use lifetime::LifetimeError;
+#[cfg(feature = "plugin")]
+pub use plugin::{PluginError, PluginHost};
 pub use resolvable::Resolvable;
+pub use resolver::Resolver;
+#[cfg(feature = "resolution_depth_limit")]
+pub use service_provider::set_max_resolution_depth;
+pub use service_provider::Factory;
+pub use service_provider::LazyService;
+pub use service_provider::ResolutionPlan;
+pub use service_provider::ScopeLocalSlot;
+pub use service_provider::Scoped;
+pub use service_provider::ServiceDescriptor;
 pub use service_provider::ServiceIterator;
 pub use service_provider::ServiceProvider;
+pub use service_provider::TypeManifest;
 pub use service_provider::WeakServiceProvider;
-pub use service_provider_factory::ServiceProviderFactory;
-pub use strategy::AnyStrategy;
+pub use service_provider_factory::{MultiBaseFactory, ServiceProviderFactory};
+#[cfg(feature = "serde")]
+pub use snapshot::{SerializedInstances, SnapshotError};
+pub use strategy::{AnyStrategy, Identifyable, Strategy};
+pub use untyped::ArcAutoFreePointer;
 
-use crate::resolvable::SealedResolvable;
+use crate::resolvable::{PrecheckContext, SealedResolvable};
 pub type ServiceCollection = GenericServiceCollection<AnyStrategy>;
 
+// Logs a registration with the location of the caller, which requires `#[track_caller]` up to the public API
+macro_rules! log_registration {
+    ($message:literal, $($t:ty),+) => {
+        #[cfg(feature = "log")]
+        log::debug!(
+            concat!($message, " at {}"),
+            $(core::any::type_name::<$t>(),)+
+            core::panic::Location::caller()
+        );
+    };
+}
+
 type InternalBuildResult<TS> = RResult<UntypedFn<TS>, InternalBuildError<TS>>;
 
 type AnyPtr = *const ();
@@ -58,15 +122,261 @@ type AnyPtr = *const ();
 /// However, leaking context specific services often lead to memory leaks in user code which are difficult to find:
 /// All shared references of a ServiceProvider are kept alive if the result of a single provider::get::<AllRegistered<i32>>() call
 /// is leaking it's provider. This can easily happen, if you forget to collect the results into a vector.
-/// To prevent these sneaky errors, ServiceProvider::drop() ensures that none of it's internals are kept alive when debug_assertions are enabled.
+/// To prevent these sneaky errors, ServiceProvider::drop() ensures that none of it's internals are kept alive when debug_assertions or the `runtime_checks` feature are enabled.
 ///
 /// The default implementation panics, if the std-feature is enabled (on by default). Otherwise this is a no_op
 /// For custom implementations, be aware that this function could be called while panicking already.
 /// In std, panic!(), when the thread is panicking already, terminates the entire program immediately.
 ///
-/// This variable only exists, if debug_assertions are enabled
-#[cfg(debug_assertions)]
-pub static mut MINFAC_ERROR_HANDLER: extern "C-unwind" fn(&LifetimeError) = default_error_handler;
+/// The handler is only called and these functions only exist, if debug_assertions or the `runtime_checks` feature are enabled
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+pub type ErrorHandler = extern "C-unwind" fn(&LifetimeError);
+
+// Null until a handler is installed, because function pointers cannot be cast to pointers in statics
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+static MINFAC_ERROR_HANDLER: core::sync::atomic::AtomicPtr<()> =
+    core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+fn error_handler_from_ptr(ptr: *mut ()) -> ErrorHandler {
+    if ptr.is_null() {
+        default_error_handler
+    } else {
+        unsafe { core::mem::transmute::<*mut (), ErrorHandler>(ptr) }
+    }
+}
+
+/// Returns the handler, which is called for lifetime errors
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+pub fn error_handler() -> ErrorHandler {
+    error_handler_from_ptr(MINFAC_ERROR_HANDLER.load(core::sync::atomic::Ordering::Acquire))
+}
+
+/// Installs `handler` for lifetime errors and returns the previous one, so it can be restored afterwards.
+/// ```
+/// use minfac::{swap_error_handler, LifetimeError, ServiceCollection, WeakServiceProvider};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static ERRORS: AtomicUsize = AtomicUsize::new(0);
+/// extern "C-unwind" fn count(_: &LifetimeError) {
+///     ERRORS.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// let previous = swap_error_handler(count);
+/// let mut collection = ServiceCollection::new();
+/// collection.with::<WeakServiceProvider>().register(|p| p);
+/// let provider = collection.build().expect("Configuration is valid");
+/// let outliving = provider.get::<WeakServiceProvider>();
+/// drop(provider);
+/// swap_error_handler(previous);
+///
+/// assert_eq!(1, ERRORS.load(Ordering::Relaxed));
+/// drop(outliving);
+/// ```
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+pub fn swap_error_handler(handler: ErrorHandler) -> ErrorHandler {
+    error_handler_from_ptr(
+        MINFAC_ERROR_HANDLER.swap(handler as *mut (), core::sync::atomic::Ordering::AcqRel),
+    )
+}
+
+/// Installs `handler` until the returned guard is dropped, which restores the previous handler.
+/// Guards should be dropped in reverse order of their creation, like any other scope.
+/// ```
+/// use minfac::{scoped_error_handler, LifetimeError, ServiceCollection, WeakServiceProvider};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static ERRORS: AtomicUsize = AtomicUsize::new(0);
+/// extern "C-unwind" fn count(_: &LifetimeError) {
+///     ERRORS.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.with::<WeakServiceProvider>().register(|p| p);
+/// let provider = collection.build().expect("Configuration is valid");
+/// let outliving = provider.get::<WeakServiceProvider>();
+/// {
+///     let _guard = scoped_error_handler(count);
+///     drop(provider);
+/// }
+/// assert_eq!(1, ERRORS.load(Ordering::Relaxed));
+/// drop(outliving);
+/// ```
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+pub fn scoped_error_handler(handler: ErrorHandler) -> ErrorHandlerGuard {
+    ErrorHandlerGuard {
+        previous: swap_error_handler(handler),
+    }
+}
+
+/// Restores the error handler, which was active before [scoped_error_handler] was called, when dropped
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+#[must_use = "the previous error handler is restored immediately, if the guard is not kept alive"]
+pub struct ErrorHandlerGuard {
+    previous: ErrorHandler,
+}
+
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+impl Drop for ErrorHandlerGuard {
+    fn drop(&mut self) {
+        swap_error_handler(self.previous);
+    }
+}
+
+/// Builds the collection populated by `$register` and panics with a listing of all registered services,
+/// if dependencies are missing or cyclic. Library authors who ship a fixed collection can use it in a test,
+/// so an invalid configuration fails in CI instead of at the first run of the application.
+/// ```
+/// use minfac::{GenericServiceCollection, Registered, ServiceCollection};
+///
+/// fn register_services(collection: &mut ServiceCollection) {
+///     collection.with::<Registered<u8>>().register(|byte| byte as i16);
+///     collection.register(|| 1u8);
+/// }
+///
+/// minfac::assert_collection_valid!(register_services);
+/// // Collections with a custom strategy
+/// minfac::assert_collection_valid!(minfac::AnyStrategy, |col: &mut GenericServiceCollection<_>| {
+///     col.register(|| 1u8);
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_collection_valid {
+    ($register:expr) => {
+        $crate::assert_collection_valid!($crate::AnyStrategy, $register)
+    };
+    ($strategy:ty, $register:expr) => {{
+        let mut collection = $crate::GenericServiceCollection::<$strategy>::new();
+        ($register)(&mut collection);
+        drop(collection.build_or_report());
+    }};
+}
+
+/// Registers a struct, whose fields are initialized with the resolved dependencies. Every field has to be
+/// listed together with the Resolvable used to initialize it. Services are transient by default. With the
+/// `shared` prefix, the struct is registered as shared service `Arc<Struct>`.
+/// The result of `register` or `register_shared` is returned, so aliases can be added.
+/// ```
+/// use {minfac::{register_struct, AllRegistered, Registered, ServiceCollection, ServiceIterator}, std::sync::Arc};
+///
+/// struct Config { level: u8 }
+/// struct Service { config: Arc<Config>, handlers: ServiceIterator<i32> }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1u8);
+/// collection.register(|| 42i32);
+/// register_struct!(collection, shared Config { level: Registered<u8> });
+/// register_struct!(collection, Service {
+///     config: Registered<Arc<Config>>,
+///     handlers: AllRegistered<i32>,
+/// });
+///
+/// let provider = collection.build().expect("Configuration is valid");
+/// let service = provider.get::<Service>().unwrap();
+/// assert_eq!(1, service.config.level);
+/// assert_eq!(vec![42], service.handlers.collect::<Vec<_>>());
+/// ```
+#[macro_export]
+macro_rules! register_struct {
+    ($col:expr, shared $name:ident { $($field:ident : $dep:ty),* $(,)? }) => {
+        $col.with::<$crate::__register_struct_deps!($($dep),*)>()
+            .register_shared(|$crate::__register_struct_pattern!($($field),*)| {
+                ::std::sync::Arc::new($name { $($field),* })
+            })
+    };
+    ($col:expr, $name:ident { $($field:ident : $dep:ty),* $(,)? }) => {
+        $col.with::<$crate::__register_struct_deps!($($dep),*)>()
+            .register(|$crate::__register_struct_pattern!($($field),*)| $name { $($field),* })
+    };
+}
+
+/// Resolves a struct, whose fields are initialized with the resolved services, as the counterpart of
+/// `register_struct!` for code which needs many services at once. Fields are listed with their
+/// Resolvable like in `register_struct!`, so a bundle reads better than a large tuple passed to
+/// `resolve_refs`. Like `resolve_refs`, None is returned if any of the services is missing.
+/// ```
+/// use {minfac::{resolve_bundle, AllRegistered, Registered, ServiceCollection, ServiceIterator, Shared}, std::sync::Arc};
+///
+/// struct Handlers<'a> { level: u8, name: &'a String, handlers: ServiceIterator<i32> }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1u8);
+/// collection.register(|| 42i32);
+/// collection.register_shared(|| Arc::new(String::from("main")));
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let bundle = resolve_bundle!(provider, Handlers {
+///     level: Registered<u8>,
+///     name: Shared<String>,
+///     handlers: AllRegistered<i32>,
+/// })
+/// .unwrap();
+/// assert_eq!((1, "main"), (bundle.level, bundle.name.as_str()));
+/// assert_eq!(vec![42], bundle.handlers.collect::<Vec<_>>());
+/// ```
+#[macro_export]
+macro_rules! resolve_bundle {
+    ($provider:expr, $name:ident { $($field:ident : $dep:ty),* $(,)? }) => {
+        $provider
+            .resolve_refs::<$crate::__register_struct_deps!($($dep),*)>()
+            .map(|$crate::__register_struct_pattern!($($field),*)| $name { $($field),* })
+    };
+}
+
+// Fields are nested into pairs, so structs aren't limited by the supported tuple size
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_struct_deps {
+    () => { () };
+    ($dep:ty) => { $dep };
+    ($dep:ty, $($rest:ty),+) => { ($dep, $crate::__register_struct_deps!($($rest),+)) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_struct_pattern {
+    () => { () };
+    ($field:ident) => { $field };
+    ($field:ident, $($rest:ident),+) => { ($field, $crate::__register_struct_pattern!($($rest),+)) };
+}
+
+/// Registers the service created by `creator` and aliases it as each of the listed traits. With the `shared`
+/// prefix, `creator` returns an `Arc` and all aliases are `Arc<dyn Trait>` of the same instance. Otherwise the
+/// aliases are `Box<dyn Trait>` and, like any transient service, each resolution creates its own instance.
+/// The builder of the concrete service is returned, so further aliases can be added.
+/// ```
+/// use {minfac::{register_all_traits, ServiceCollection}, std::sync::Arc};
+///
+/// trait Named { fn name(&self) -> &'static str; }
+/// trait Plugin: Send + Sync { fn init(&self) -> bool; }
+/// struct Auth;
+/// impl Named for Auth { fn name(&self) -> &'static str { "auth" } }
+/// impl Plugin for Auth { fn init(&self) -> bool { true } }
+///
+/// let mut collection = ServiceCollection::new();
+/// register_all_traits!(collection, shared || Arc::new(Auth), dyn Named + Send + Sync, dyn Plugin);
+/// register_all_traits!(collection, || Auth, dyn Named);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let named = provider.get::<Arc<dyn Named + Send + Sync>>().unwrap();
+/// let plugin = provider.get::<Arc<dyn Plugin>>().unwrap();
+/// assert!(plugin.init());
+/// assert_eq!(Arc::as_ptr(&named) as *const (), Arc::as_ptr(&plugin) as *const ());
+/// assert_eq!("auth", provider.get::<Box<dyn Named>>().unwrap().name());
+/// ```
+#[macro_export]
+macro_rules! register_all_traits {
+    ($col:expr, shared $creator:expr $(, $trait:ty)* $(,)?) => {{
+        let mut builder = $col.register_shared($creator);
+        $(builder.alias(|x| x as ::std::sync::Arc<$trait>);)*
+        builder
+    }};
+    ($col:expr, $creator:expr $(, $trait:ty)* $(,)?) => {{
+        let mut builder = $col.register($creator);
+        $(builder.alias(|x| ::std::boxed::Box::new(x) as ::std::boxed::Box<$trait>);)*
+        builder
+    }};
+}
 
 /// Represents a query for the last registered instance of `T`
 pub struct Registered<T>(PhantomData<T>);
@@ -74,23 +384,429 @@ pub struct Registered<T>(PhantomData<T>);
 /// Represents a query for all registered instances of Type `T`.
 pub struct AllRegistered<T>(PhantomData<T>);
 
+/// Represents a query for the number of registered instances of `T`, without constructing any of them.
+/// Because no instance is resolved, a service may even count instances of its own type.
+/// ```
+/// use minfac::{CountOf, ServiceCollection};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1i32);
+/// collection.register(|| 2i32);
+/// collection.with::<CountOf<i32>>().register(|count| count as i32);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!(vec![1, 2, 3], provider.get_all::<i32>().collect::<Vec<_>>());
+/// ```
+pub struct CountOf<T>(PhantomData<T>);
+
+/// Represents a query for the last instance of `T` registered in the nearest parent ServiceProvider, which
+/// provides one. Services of the current provider are skipped, so a service can decorate the parent's `T`,
+/// even if the child registers its own `T`. Resolves to `None`, if no parent provides `T`.
+/// ```
+/// use minfac::{FromParent, ServiceCollection};
+///
+/// let mut parent = ServiceCollection::new();
+/// parent.register(|| 1i32);
+/// let parent = parent.build().expect("Configuration is valid");
+///
+/// let mut child = ServiceCollection::new();
+/// child.override_parent(|| 2i32);
+/// child.with::<FromParent<i32>>().register(|x| x.unwrap_or_default() as i64);
+/// let child = child.with_parent(&parent).build_factory::<()>().unwrap().build(());
+///
+/// assert_eq!(Some(2), child.get::<i32>());
+/// assert_eq!(Some(1), child.get::<i64>());
+/// ```
+pub struct FromParent<T>(PhantomData<T>);
+
+/// Represents a query for the `T` registered right before the service depending on it, which has to be a `T` itself.
+/// Unlike `Registered<T>`, which would be the service itself, it allows decorating the previous registration.
+/// Building fails with `BuildError::MissingDependency`, if the service isn't a `T` or is the first one.
+/// Services inherited from parents are registered before the ones of the child. Resolving it directly with
+/// `ServiceProvider::resolve_refs` returns `None`, as there is no service depending on it.
+/// ```
+/// use minfac::{PreviousRegistered, ServiceCollection};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1i32);
+/// collection.with::<PreviousRegistered<i32>>().register(|previous| previous * 10);
+/// collection.with::<PreviousRegistered<i32>>().register(|previous| previous + 1);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!(vec![1, 10, 11], provider.get_all::<i32>().collect::<Vec<_>>());
+/// ```
+pub struct PreviousRegistered<T>(PhantomData<T>);
+
+/// Represents a query for all registered instances of `T` like `AllRegistered<T>`, but `ServiceCollection::build()`
+/// fails with `BuildError::MissingDependency`, if there isn't at least one instance. Resolving it directly
+/// with `ServiceProvider::resolve_refs` returns None instead of an empty iterator.
+/// ```
+/// use {minfac::{AtLeastOne, BuildError, ServiceCollection, ServiceIterator}};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection
+///     .with::<AtLeastOne<i32>>()
+///     .register(|handlers: ServiceIterator<i32>| handlers.count());
+/// assert!(matches!(collection.build(), Err(BuildError::MissingDependency { .. })));
+/// ```
+pub struct AtLeastOne<T>(PhantomData<T>);
+
+/// Represents a query for exactly `N` registered instances of `T`, which are resolved into `[T; N]`
+/// in registration order. `ServiceCollection::build()` fails with `BuildError::CountMismatch`, if a
+/// dependency doesn't have exactly `N` registrations. `ServiceProvider::resolve_refs` returns None instead.
+/// ```
+/// use minfac::{RegisteredArray, ServiceCollection};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1);
+/// collection.register(|| 2);
+/// collection.with::<RegisteredArray<i32, 2>>().register(|[a, b]| (a * 10 + b) as i64);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!(Some(12), provider.get::<i64>());
+/// assert_eq!(Some([1, 2]), provider.resolve_refs::<RegisteredArray<i32, 2>>());
+/// assert_eq!(None, provider.resolve_refs::<RegisteredArray<i32, 3>>());
+/// ```
+pub struct RegisteredArray<T, const N: usize>(PhantomData<T>);
+
+/// Represents a query for all registered instances of `C::Service`, collected into the container `C`.
+/// ```
+/// use {minfac::{CollectInto, ServiceCollection}, std::collections::BTreeSet};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 2);
+/// collection.register(|| 1);
+/// collection.register(|| 2);
+/// collection
+///     .with::<CollectInto<BTreeSet<i32>>>()
+///     .register(|set| set.into_iter().collect::<Vec<_>>());
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!(Some(vec![1, 2]), provider.get::<Vec<i32>>());
+/// ```
+/// Shared services are collected as clones of their `Arc`, e.g. into a `Vec<Arc<dyn Plugin>>`. In contrast
+/// to the iterator of `AllRegistered`, the snapshot holds no WeakServiceProvider. The Arcs are still shared
+/// services though, so they have to be dropped before the ServiceProvider.
+/// ```
+/// use {minfac::{CollectInto, ServiceCollection}, std::sync::Arc};
+///
+/// trait Plugin: Send + Sync {
+///     fn name(&self) -> &'static str;
+/// }
+/// struct Auth;
+/// impl Plugin for Auth {
+///     fn name(&self) -> &'static str { "auth" }
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection
+///     .register_shared(|| Arc::new(Auth))
+///     .alias(|x| x as Arc<dyn Plugin>);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let plugins = provider.resolve_refs::<CollectInto<Vec<Arc<dyn Plugin>>>>().unwrap();
+/// assert_eq!(vec!["auth"], plugins.iter().map(|p| p.name()).collect::<Vec<_>>());
+/// ```
+pub struct CollectInto<C>(PhantomData<C>);
+
+/// Container, which can be built from all registered instances of `Service` by `CollectInto`.
+/// Implement it for custom containers to resolve them with `CollectInto<Container>`.
+pub trait FromServices: FromIterator<Self::Service> {
+    type Service;
+}
+
+impl<T> FromServices for Vec<T> {
+    type Service = T;
+}
+
+impl<T> FromServices for alloc::collections::VecDeque<T> {
+    type Service = T;
+}
+
+impl<T> FromServices for alloc::collections::LinkedList<T> {
+    type Service = T;
+}
+
+impl<T: Ord> FromServices for alloc::collections::BTreeSet<T> {
+    type Service = T;
+}
+
+impl<T: Ord> FromServices for alloc::collections::BinaryHeap<T> {
+    type Service = T;
+}
+
+#[cfg(feature = "std")]
+impl<T: Eq + core::hash::Hash> FromServices for std::collections::HashSet<T> {
+    type Service = T;
+}
+
+/// Represents a query for a `Weak<T>` to the last shared service registered as `Arc<T>`.
+/// Resolving it initializes the shared service, if this didn't happen yet. The returned
+/// `Weak<T>` doesn't keep the service alive and cannot be upgraded after its ServiceProvider is dropped.
+pub struct WeakArc<T>(PhantomData<T>);
+
+/// Represents a query for the last registered instance of `T`, wrapped into a new `Arc<T>`.
+/// In contrast to shared services, each resolution creates a separate instance, so nothing is shared
+/// between resolutions. This is useful for dependencies which require an `Arc` of a transient service.
+pub struct Arced<T>(PhantomData<T>);
+
+/// Represents a query for the last registered `T` or, if there is none, the last shared service registered
+/// as `Arc<T>`. Consumers working with the resulting `OwnedOrShared<T>` don't depend on the registration kind.
+/// ```
+/// use {minfac::{Either, OwnedOrShared, ServiceCollection}, std::sync::Arc};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1i32);
+/// collection.register_shared(|| Arc::new(2i64));
+/// collection.with::<(Either<i32>, Either<i64>)>().register(|(a, b)| *a as u8 + *b as u8);
+/// let provider = collection.build().expect("Either is registered");
+///
+/// assert_eq!(Some(3u8), provider.get());
+/// assert!(matches!(provider.resolve_unchecked::<Either<i64>>(), OwnedOrShared::Shared(_)));
+/// ```
+pub struct Either<T>(PhantomData<T>);
+
+/// Service resolved by `Either<T>`, which either owns a transient `T` or shares the `Arc<T>` of a shared service
+#[derive(Debug)]
+pub enum OwnedOrShared<T> {
+    Owned(T),
+    Shared(Arc<T>),
+}
+
+impl<T> Deref for OwnedOrShared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            OwnedOrShared::Owned(x) => x,
+            OwnedOrShared::Shared(x) => x,
+        }
+    }
+}
+
+/// Represents a query for the first type of the tuple `T`, for which a service is registered, converted into `TOut`.
+/// This allows to depend on interchangeable services, e.g. a redis or an in-memory cache, without a common trait.
+/// Like `Registered`, the last registered instance of the chosen type is resolved. If none of the types is
+/// registered, `BuildError::MissingDependency` reports the first one.
+/// ```
+/// use minfac::{OneOf, ServiceCollection};
+///
+/// struct RedisCache;
+/// struct InMemoryCache;
+/// enum Cache {
+///     Redis(RedisCache),
+///     InMemory(InMemoryCache),
+/// }
+/// impl From<RedisCache> for Cache {
+///     fn from(x: RedisCache) -> Self { Cache::Redis(x) }
+/// }
+/// impl From<InMemoryCache> for Cache {
+///     fn from(x: InMemoryCache) -> Self { Cache::InMemory(x) }
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| InMemoryCache);
+/// collection.with::<OneOf<(RedisCache, InMemoryCache), Cache>>().register(|cache| match cache {
+///     Cache::Redis(_) => "redis",
+///     Cache::InMemory(_) => "in-memory",
+/// });
+/// let provider = collection.build().expect("One of the caches is registered");
+/// assert_eq!(Some("in-memory"), provider.get());
+/// ```
+pub struct OneOf<T, TOut>(PhantomData<(T, TOut)>);
+
+/// Represents a query for a `LazyService<T>`, which resolves the last registered instance of `T`
+/// only when `LazyService::get()` is called. Missing dependencies are still reported by
+/// `ServiceCollection::build()`, so `get()` never fails.
+/// ```
+/// use minfac::{Lazy, LazyService, Registered, ServiceCollection};
+///
+/// struct Expensive(i32);
+/// struct Consumer(LazyService<Expensive>);
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| Expensive(42));
+/// collection.with::<Lazy<Expensive>>().register(Consumer);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let consumer = provider.get::<Consumer>().unwrap();
+/// assert_eq!(42, consumer.0.get().0);
+/// ```
+pub struct Lazy<T>(PhantomData<T>);
+
+/// Represents a query for the `ScopeLocalSlot<T>` of a ServiceProvider, e.g. to stash request-scoped values
+/// which are computed within a handler and read later in the same scope. In contrast to shared services,
+/// each ServiceProvider built by a ServiceProviderFactory has its own slots, which start empty. Clones and
+/// WeakServiceProviders share the slots of their provider. Values are dropped along with the provider's state.
+/// ```
+/// use minfac::{ScopeLocal, ServiceCollection};
+///
+/// let factory = ServiceCollection::new()
+///     .build_factory::<()>()
+///     .expect("Configuration is valid");
+/// let request = factory.build(());
+///
+/// request.resolve_unchecked::<ScopeLocal<u32>>().set(42);
+/// assert_eq!(Some(42), request.resolve_unchecked::<ScopeLocal<u32>>().get());
+/// assert_eq!(None, factory.build(()).resolve_unchecked::<ScopeLocal<u32>>().get());
+/// ```
+pub struct ScopeLocal<T>(PhantomData<T>);
+
+/// Represents a query for the innermost context of type `T`, which is set by `ServiceProvider::with_context`
+/// during a resolution. It resolves to `Option<T>`, which is None outside of `with_context`. Contexts don't
+/// have to be registered, so any service can depend on it without affecting the build.
+pub struct ResolutionContext<T>(PhantomData<T>);
+
+/// Service of type `T`, which was registered with a key by `register_keyed`.
+/// Keyed services are only resolvable with `ServiceProvider::get_keyed`, so they don't mix with
+/// services of type `T` registered without a key.
+pub struct KeyedService<K, T>(pub(crate) T, PhantomData<K>);
+
+/// Represents a query for the keyed service `T`, which was registered with the key of `TKey`.
+/// In contrast to `ServiceProvider::get_keyed`, it can be used as a dependency. `ServiceCollection::build()`
+/// fails with `BuildError::MissingKey`, if the key isn't registered in the same collection.
+/// Keys are only known while building, so `ServiceProvider::resolve_refs` cannot resolve it.
+/// Use `ServiceProvider::get_keyed` instead.
+/// ```
+/// use minfac::{Keyed, Registered, ServiceCollection, TypedKey};
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// enum Db { Primary, Replica }
+/// struct PrimaryDb;
+/// impl TypedKey for PrimaryDb {
+///     type Key = Db;
+///     fn key() -> Db { Db::Primary }
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register_keyed(Db::Primary, || "primary");
+/// collection.register_keyed(Db::Replica, || "replica");
+/// collection.register(|| 1u8);
+/// collection
+///     .with::<(Keyed<PrimaryDb, &str>, Registered<u8>)>()
+///     .register(|(db, n)| format!("{db}{n}"));
+/// let provider = collection.build().expect("Primary key is registered");
+///
+/// assert_eq!(Some("primary1".to_string()), provider.get::<String>());
+/// assert!(provider.resolve_refs::<Keyed<PrimaryDb, &str>>().is_none());
+/// assert_eq!(Some("primary"), provider.get_keyed::<Db, &str>(&Db::Primary));
+/// ```
+pub struct Keyed<TKey, T>(PhantomData<(TKey, T)>);
+
+/// Key of a keyed service, which is known at compile time, so it can be used with `Keyed<Self, T>`
+pub trait TypedKey: 'static {
+    type Key: PartialEq + Debug + 'static;
+    fn key() -> Self::Key;
+}
+
+/// Key of the `KeyedService<K, T>` at the same position
+#[doc(hidden)]
+pub struct ServiceKey<K, T>(pub(crate) K, PhantomData<fn() -> T>);
+
+impl<K: Clone, T> Clone for ServiceKey<K, T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
+/// Represents a query for a reference to the last shared service registered as `Arc<T>`.
+/// In contrast to `Registered<Arc<T>>`, the reference count of the service isn't touched.
+/// References are borrowed from the ServiceProvider, so they are only available via
+/// `ServiceProvider::resolve_refs` or as dependency within `ServiceBuilder`.
+///
+/// Tuples can mix `Shared<T>` with owned dependencies, e.g. `(Registered<A>, Shared<B>)` resolves to `(A, &B)`.
+/// ```
+/// use {minfac::{Registered, ServiceCollection, Shared}, std::sync::Arc};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 2usize);
+/// collection.register_shared(|| Arc::new(String::from("Hello")));
+/// collection
+///     .with::<(Registered<usize>, Shared<String>)>()
+///     .register(|(count, text): (usize, &String)| text.repeat(count));
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!(Some("HelloHello".to_string()), provider.get::<String>());
+/// ```
+pub struct Shared<T>(PhantomData<T>);
+
 /// Collection of constructors for different types of services. Registered constructors are never called in this state.
 /// Instances can only be received by a ServiceProvider, which can be created by calling `build`
 pub struct GenericServiceCollection<TS: Strategy + 'static> {
     strategy: PhantomData<TS>,
     producer_factories: Vec<ServiceProducer<TS>>,
+    alias_all_producers: Vec<AliasAllProducer<TS>>,
+    // Types, for which services inherited from parent providers are ignored
+    parent_overrides: Vec<TS::Id>,
+    // Types, which must not be registered more than once
+    unique_types: Vec<TS::Id>,
+    // Keys of register_keyed and checks of expect_keys, which are validated when building
+    registered_keys: Vec<(TS::Id, Arc<dyn Any + Send + Sync>)>,
+    key_checks: Vec<KeyCheck<TS>>,
+    libraries: Vec<LibraryHandle>,
+    // Set by set_profile. Services registered with register_for are only built, if it matches their profile
+    profile: Option<Arc<dyn Any + Send + Sync>>,
+    // Types, which AliasBuilder::boxable made resolvable by ServiceProvider::get_boxed
+    boxers: Vec<service_provider::Boxer<TS>>,
+    // Instances registered with register_serializable_instance, which are part of snapshot_instances
+    #[cfg(feature = "serde")]
+    serializers: Vec<snapshot::Serializer>,
 }
 
+// Collections are built on other threads than they were populated, e.g. by plugin loaders
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ServiceCollection>();
+};
+
+type KeyCheck<TS> = Arc<
+    dyn Fn(&[(<TS as Strategy>::Id, Arc<dyn Any + Send + Sync>)]) -> Result<(), BuildError<TS>>
+        + Send
+        + Sync,
+>;
+
+// Keeps a dynamic library loaded as long as any ServiceProvider might call into it
+type LibraryHandle = Arc<dyn core::any::Any + Send + Sync>;
+
+// Returns true, if the active profile of a collection is the profile of a service registered with register_for
+type ProfileMatcher = Arc<dyn Fn(&(dyn Any + Send + Sync)) -> bool + Send + Sync>;
+
 /// Alias builder is used to register services, which depend on the previous service.
 /// This is especially useful, if the previous service contains an anonymous type like a lambda
 pub struct AliasBuilder<'a, T: ?Sized, TS: Strategy + 'static>(
     Rc<RefCell<&'a mut GenericServiceCollection<TS>>>,
     PhantomData<T>,
+    // Profile of the service registered with register_for, which is applied to all its aliases
+    Option<ProfileMatcher>,
 );
 
 impl<'a, T: Identifyable<TS::Id>, TS: Strategy + 'static> AliasBuilder<'a, T, TS> {
     fn new(col: &'a mut GenericServiceCollection<TS>) -> Self {
-        Self(Rc::new(RefCell::new(col)), PhantomData)
+        Self(Rc::new(RefCell::new(col)), PhantomData, None)
+    }
+
+    fn with_profile(mut self, profile: ProfileMatcher) -> Self {
+        self.2 = Some(profile);
+        self
+    }
+
+    // Applies the profile of this builder to the services registered since the collection had `start` producers
+    fn apply_profile(&self, start: usize) {
+        if let Some(profile) = &self.2 {
+            for producer in self.0.borrow_mut().producer_factories[start..].iter_mut() {
+                producer.profile = Some(profile.clone());
+            }
+        }
+    }
+
+    fn producer_count(&self) -> usize {
+        self.0.borrow().producer_factories.len()
+    }
+
+    /// Returns a handle to the registered type, which can be passed to `alias_registered` after this builder is gone.
+    /// This allows registering services in one place and aliasing some of them later.
+    pub fn handle(&self) -> RegistrationHandle<T> {
+        RegistrationHandle(PhantomData)
     }
 
     /// Registers an aliased service. The returned AliasBuilder refers to the new type
@@ -103,43 +819,503 @@ impl<'a, T: Identifyable<TS::Id>, TS: Strategy + 'static> AliasBuilder<'a, T, TS
     /// assert_eq!(Some(2i16), prov.get());
     /// assert_eq!(Some(4i32), prov.get());
     /// ```
+    /// To register multiple aliases of the same service, e.g. a shared service as several trait objects, keep the
+    /// original builder and call `alias` on it for each of them. Aliases of shared services resolve the same instance.
+    /// Unsizing `Arc<T>` isn't possible for generic traits on stable Rust, so `creator` has to do it.
+    /// ``` rust
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    /// trait Logger: Send + Sync {}
+    /// trait Flushable: Send + Sync {}
+    /// struct LoggerImpl;
+    /// impl Logger for LoggerImpl {}
+    /// impl Flushable for LoggerImpl {}
+    ///
+    /// let mut col = ServiceCollection::new();
+    /// let mut shared = col.register_shared(|| Arc::new(LoggerImpl));
+    /// shared.alias(|x| x as Arc<dyn Logger>);
+    /// shared.alias(|x| x as Arc<dyn Flushable>);
+    /// let provider = col.build().unwrap();
+    /// let logger = provider.get::<Arc<dyn Logger>>().unwrap();
+    /// let flushable = provider.get::<Arc<dyn Flushable>>().unwrap();
+    /// assert!(core::ptr::addr_eq(Arc::as_ptr(&logger), Arc::as_ptr(&flushable)));
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
     pub fn alias<TNew: Identifyable<TS::Id>>(
         &mut self,
         creator: fn(T) -> TNew,
     ) -> AliasBuilder<'a, TNew, TS> {
+        let start = self.producer_count();
         self.0
             .borrow_mut()
             .with::<Registered<T>>()
             .register(creator);
-        AliasBuilder::<_, TS>(self.0.clone(), PhantomData)
+        self.apply_profile(start);
+        AliasBuilder::<_, TS>(self.0.clone(), PhantomData, self.2.clone())
+    }
+
+    /// Registers the service as member of group `G`, so it's resolvable by `get_all::<GroupMember<G>>()` along with
+    /// all other members, independent of their concrete types. Like `alias`, the member is created from the last
+    /// registered instance of `T`. `convert` is usually `|x| Box::new(x)`, as the trait object cannot be created generically.
+    /// ``` rust
+    /// use minfac::{Group, GroupMember, ServiceCollection};
+    ///
+    /// trait Shutdown {
+    ///     fn shutdown(&self) -> &'static str;
+    /// }
+    /// struct Db;
+    /// impl Shutdown for Db {
+    ///     fn shutdown(&self) -> &'static str { "db" }
+    /// }
+    /// struct Cache;
+    /// impl Shutdown for Cache {
+    ///     fn shutdown(&self) -> &'static str { "cache" }
+    /// }
+    /// struct ShutdownGroup;
+    /// impl Group for ShutdownGroup {
+    ///     type Member = dyn Shutdown;
+    /// }
+    ///
+    /// let mut col = ServiceCollection::new();
+    /// col.register(|| Db).in_group::<ShutdownGroup>(|x| Box::new(x));
+    /// col.register(|| Cache).in_group::<ShutdownGroup>(|x| Box::new(x));
+    /// let provider = col.build().unwrap();
+    /// let names: Vec<_> = provider
+    ///     .get_all::<GroupMember<ShutdownGroup>>()
+    ///     .map(|x| x.shutdown())
+    ///     .collect();
+    /// assert_eq!(2, names.len());
+    /// assert!(names.contains(&"db") && names.contains(&"cache"));
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn in_group<G: Group>(&mut self, convert: fn(T) -> Box<G::Member>) -> &mut Self
+    where
+        GroupMember<G>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register {} in group {}", T, G);
+        fn create<T: Identifyable<TS::Id>, G: Group, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> GroupMember<G> {
+            let ctx =
+                unsafe { TransientContext::<GroupMember<G>, Registered<T>, TS>::from_erased(ctx) };
+            let convert: fn(T) -> Box<G::Member> = unsafe { core::mem::transmute(ctx.creator) };
+            GroupMember(convert(ctx.resolve(provider)), PhantomData)
+        }
+        let start = self.producer_count();
+        self.0
+            .borrow_mut()
+            .with::<Registered<T>>()
+            .register_transient_with(convert as AnyPtr, create::<T, G, TS>);
+        self.apply_profile(start);
+        self
+    }
+
+    /// Allows `ServiceProvider::get_boxed` to resolve services of type `T` without knowing the type at compile
+    /// time, e.g. for bridges to scripting languages. This applies to all services of type `T` including the
+    /// ones inherited by child providers, because the boxing function is recorded per type.
+    /// ```
+    /// use {minfac::ServiceCollection, std::any::TypeId};
+    ///
+    /// let mut col = ServiceCollection::new();
+    /// col.register(|| 42i32).boxable();
+    /// col.register(|| 1u8);
+    /// let provider = col.build().unwrap();
+    ///
+    /// let boxed = provider.get_boxed(TypeId::of::<i32>()).unwrap();
+    /// assert_eq!(Some(&42), boxed.downcast_ref::<i32>());
+    /// assert!(provider.get_boxed(TypeId::of::<u8>()).is_none());
+    /// ```
+    pub fn boxable(&mut self) -> &mut Self
+    where
+        T: Any + Send,
+    {
+        let mut col = self.0.borrow_mut();
+        if !col.boxers.iter().any(|(id, _)| *id == T::get_id()) {
+            col.boxers
+                .push((T::get_id(), service_provider::box_service::<T, TS>));
+        }
+        drop(col);
+        self
+    }
+
+    /// Registers a section of the service, which is cloned from the last registered instance of `T` via `project`,
+    /// e.g. to inject parts of a central configuration without depending on all of it. Like `in_group`, the builder
+    /// keeps referring to `T`, so several sections can be registered in a row.
+    /// ``` rust
+    /// #[derive(Clone)]
+    /// struct Database { url: &'static str }
+    /// #[derive(Clone)]
+    /// struct AppConfig { database: Database, port: u16 }
+    ///
+    /// let mut col = minfac::ServiceCollection::new();
+    /// col.register_instance(AppConfig { database: Database { url: "db://" }, port: 80 })
+    ///     .section(|c| &c.database)
+    ///     .section(|c| &c.port);
+    /// let prov = col.build().unwrap();
+    /// assert_eq!(Some("db://"), prov.get::<Database>().map(|x| x.url));
+    /// assert_eq!(Some(80u16), prov.get());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn section<TNew: Identifyable<TS::Id> + Clone>(
+        &mut self,
+        project: fn(&T) -> &TNew,
+    ) -> &mut Self {
+        log_registration!("Register section {} of {}", TNew, T);
+        fn create<T: Identifyable<TS::Id>, TNew: Clone, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> TNew {
+            let ctx = unsafe { TransientContext::<TNew, Registered<T>, TS>::from_erased(ctx) };
+            let project: fn(&T) -> &TNew = unsafe { core::mem::transmute(ctx.creator) };
+            project(&ctx.resolve(provider)).clone()
+        }
+        let start = self.producer_count();
+        self.0
+            .borrow_mut()
+            .with::<Registered<T>>()
+            .register_transient_with(project as AnyPtr, create::<T, TNew, TS>);
+        self.apply_profile(start);
+        self
+    }
+
+    /// Registers an aliased service for each registered instance of `T`, including instances which
+    /// are registered later or inherited from a parent ServiceProvider. In contrast to `alias`, which
+    /// only maps the last registered instance, `get_all::<TNew>()` returns one service per instance of `T`.
+    /// ``` rust
+    /// let mut col = minfac::ServiceCollection::new();
+    /// col.register(|| 1i8).alias_all(|a| a as i16 * 2);
+    /// col.register(|| 2i8);
+    /// let prov = col.build().unwrap();
+    /// assert_eq!(vec![2i16, 4i16], prov.get_all::<i16>().collect::<Vec<_>>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn alias_all<TNew: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn(T) -> TNew,
+    ) -> AliasBuilder<'a, TNew, TS> {
+        log_registration!("Alias all {} as {}", T, TNew);
+        self.0
+            .borrow_mut()
+            .alias_all_producers
+            .push(AliasAllProducer::new::<T, TNew>(creator));
+        AliasBuilder::<_, TS>(self.0.clone(), PhantomData, self.2.clone())
+    }
+}
+
+/// Refers to a registered type `T`, so aliases can be registered independent of the AliasBuilder.
+/// It is just a type marker, so aliasing it behaves exactly like `AliasBuilder::alias`.
+pub struct RegistrationHandle<T: ?Sized>(PhantomData<fn() -> T>);
+
+impl<T: ?Sized> Clone for RegistrationHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for RegistrationHandle<T> {}
+
+/// Groups services of different types, so they can be resolved together by `get_all::<GroupMember<G>>()`.
+/// Services join a group using `AliasBuilder::in_group`.
+pub trait Group: 'static {
+    /// Common interface of all members, usually a trait object
+    type Member: ?Sized + 'static;
+}
+
+/// Service registered by `AliasBuilder::in_group`, which dereferences to the common interface of group `G`
+#[repr(transparent)]
+pub struct GroupMember<G: Group>(Box<G::Member>, PhantomData<G>);
+
+impl<G: Group> GroupMember<G> {
+    pub fn into_inner(self) -> Box<G::Member> {
+        self.0
+    }
+}
+
+impl<G: Group> Deref for GroupMember<G> {
+    type Target = G::Member;
+
+    fn deref(&self) -> &G::Member {
+        &self.0
+    }
+}
+
+/// Returned when registering a shared service `Arc<T>`. It dereferences to an AliasBuilder for
+/// registering aliases and allows configuring the shared service itself.
+pub struct SharedBuilder<'a, T, TS: Strategy + 'static> {
+    alias: AliasBuilder<'a, Arc<T>, TS>,
+    position: usize,
+}
+
+impl<'a, T: Send + Sync, TS: Strategy + 'static> SharedBuilder<'a, T, TS>
+where
+    Arc<T>: Identifyable<TS::Id>,
+{
+    fn new(col: &'a mut GenericServiceCollection<TS>) -> Self {
+        let position = col.producer_factories.len() - 1;
+        Self {
+            alias: AliasBuilder::new(col),
+            position,
+        }
+    }
+
+    /// Registers a finalizer, which is called when the ServiceProvider is dropped or `reset_shared()`, before its `Arc<T>` is released.
+    /// The finalizer is only called if the shared service was initialized. Finalizers run before
+    /// the ServiceProvider checks for shared services outliving it.
+    /// ``` rust
+    /// use {minfac::ServiceCollection, std::sync::{Arc, atomic::{AtomicI32, Ordering}}};
+    /// static FLUSHED: AtomicI32 = AtomicI32::new(0);
+    ///
+    /// let mut col = ServiceCollection::new();
+    /// col.register_shared(|| Arc::new(42))
+    ///     .on_drop(|i| FLUSHED.store(*i, Ordering::Relaxed));
+    /// let provider = col.build().unwrap();
+    /// assert_eq!(Some(42), provider.get::<Arc<i32>>().map(|i| *i));
+    /// drop(provider);
+    /// assert_eq!(42, FLUSHED.load(Ordering::Relaxed));
+    /// ```
+    pub fn on_drop(&mut self, finalizer: fn(&T)) -> &mut Self {
+        self.alias.0.borrow_mut().producer_factories[self.position]
+            .teardown
+            .finalizer = Some(SharedServiceFinalizer::new(finalizer));
+        self
+    }
+
+    /// Shared services are released from the highest to the lowest drop priority when the ServiceProvider is dropped
+    /// or `reset_shared()`, so teardown constraints which aren't expressed by dependencies can be declared explicitly.
+    /// Their finalizers run in the same order. Services with equal priority, which is 0 by default, keep their
+    /// registration order.
+    /// ``` rust
+    /// use {minfac::ServiceCollection, std::sync::{Arc, Mutex}};
+    /// static ORDER: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+    /// struct Database;
+    /// struct Cache;
+    ///
+    /// let mut col = ServiceCollection::new();
+    /// col.register_shared(|| Arc::new(Database))
+    ///     .on_drop(|_| ORDER.lock().unwrap().push("database"));
+    /// col.register_shared(|| Arc::new(Cache))
+    ///     .on_drop(|_| ORDER.lock().unwrap().push("cache"))
+    ///     .drop_priority(1);
+    /// let provider = col.build().unwrap();
+    /// provider.get::<Arc<Database>>();
+    /// provider.get::<Arc<Cache>>();
+    /// drop(provider);
+    /// assert_eq!(vec!["cache", "database"], *ORDER.lock().unwrap());
+    /// ```
+    pub fn drop_priority(&mut self, priority: i32) -> &mut Self {
+        self.alias.0.borrow_mut().producer_factories[self.position]
+            .teardown
+            .drop_priority = priority;
+        self
+    }
+}
+
+impl<'a, T, TS: Strategy + 'static> Deref for SharedBuilder<'a, T, TS> {
+    type Target = AliasBuilder<'a, Arc<T>, TS>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.alias
+    }
+}
+
+impl<T, TS: Strategy + 'static> DerefMut for SharedBuilder<'_, T, TS> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.alias
     }
 }
 
 struct ServiceProducer<TS: Strategy + 'static> {
     identifier: TS::Id,
+    type_name: &'static str,
+    // Only used for diagnostics, e.g. in build_or_report()
+    dependencies: Option<&'static str>,
+    kind: ServiceKind,
+    // Only used for shared services
+    teardown: SharedServiceTeardown,
+    // Fallbacks are sorted before other services of the same type, so they are never the last registered one
+    is_fallback: bool,
+    profile: Option<ProfileMatcher>,
+    origin: ServiceOrigin,
     factory: UntypedFnFactory<TS>,
 }
 
+/// Describes how a registered service is produced. Resolvables like `Shared<T>` rely on it to
+/// find compatible producers during prechecks
+#[repr(C)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceKind {
+    /// A new instance is created for each resolution
+    Transient,
+    /// One instance is created per ServiceProvider and resolved as `Arc<T>`
+    Shared,
+}
+
+/// Describes where the service resolved from a ServiceProvider is registered, see `ServiceProvider::origin_of`
+#[repr(C)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceOrigin {
+    /// Registered in the collection this ServiceProvider was built from
+    Local,
+    /// Inherited from the parent at this position, in the order the parents were passed to the factory
+    Parent(usize),
+    /// The base instance passed to `ServiceProviderFactory::build`
+    Base,
+}
+
 impl<TS: Strategy + 'static> ServiceProducer<TS> {
     fn new<T: Identifyable<TS::Id>>(factory: UntypedFnFactory<TS>) -> Self {
-        Self::new_with_type(factory, T::get_id())
+        Self::new_with_type(factory, T::get_id(), type_name::<T>())
     }
-    fn new_with_type(factory: UntypedFnFactory<TS>, type_id: TS::Id) -> Self {
+    fn new_with_type(
+        factory: UntypedFnFactory<TS>,
+        type_id: TS::Id,
+        type_name: &'static str,
+    ) -> Self {
         Self {
             identifier: type_id,
+            type_name,
+            dependencies: None,
+            kind: ServiceKind::Transient,
+            teardown: SharedServiceTeardown::default(),
+            is_fallback: false,
+            profile: None,
+            origin: ServiceOrigin::Local,
             factory,
         }
     }
+    fn with_kind(mut self, kind: ServiceKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    fn with_dependencies<TDep>(mut self) -> Self {
+        self.dependencies = Some(type_name::<TDep>());
+        self
+    }
+    fn with_origin(mut self, origin: ServiceOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+    // Producers are ordered by this key in built ServiceProviders, so fallbacks are found last
+    fn order_key(&self) -> (TS::Id, bool) {
+        (self.identifier, !self.is_fallback)
+    }
+    // Panics would otherwise unwind out of build without telling, which registration caused them
+    fn call_factory(&self, ctx: &mut UntypedFnFactoryContext<TS>) -> InternalBuildResult<TS> {
+        #[cfg(feature = "std")]
+        {
+            std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| self.factory.call(ctx)))
+                .unwrap_or_else(|_| {
+                    RErr(InternalBuildError::FactoryPanicked {
+                        name: self.type_name.into(),
+                    })
+                })
+        }
+        #[cfg(not(feature = "std"))]
+        self.factory.call(ctx)
+    }
+}
+
+impl<TS: Strategy + 'static> Clone for ServiceProducer<TS> {
+    fn clone(&self) -> Self {
+        Self {
+            factory: self.factory.clone(),
+            profile: self.profile.clone(),
+            ..*self
+        }
+    }
 }
 
-type UntypedFnFactoryCreator<TS> = extern "C" fn(
-    outer_context: AutoFreePointer,
+/// Registered by `AliasBuilder::alias_all`. It's expanded to one ServiceProducer per registered
+/// instance of `source` during build, because the number of instances is unknown before.
+struct AliasAllProducer<TS: Strategy + 'static> {
+    source: TS::Id,
+    target: TS::Id,
+    creator: AnyPtr,
+    expand: fn(nth: usize, creator: AnyPtr) -> ServiceProducer<TS>,
+}
+
+// creator is always a function pointer, which can be called from any thread
+unsafe impl<TS: Strategy + 'static> Send for AliasAllProducer<TS> {}
+unsafe impl<TS: Strategy + 'static> Sync for AliasAllProducer<TS> {}
+
+impl<TS: Strategy + 'static> AliasAllProducer<TS> {
+    fn new<T: Identifyable<TS::Id>, TNew: Identifyable<TS::Id>>(creator: fn(T) -> TNew) -> Self {
+        fn expand<T: Identifyable<TS::Id>, TNew: Identifyable<TS::Id>, TS: Strategy + 'static>(
+            nth: usize,
+            creator: AnyPtr,
+        ) -> ServiceProducer<TS> {
+            type InnerContext = (usize, AnyPtr);
+            extern "C-unwind" fn factory<
+                T: Identifyable<TS::Id>,
+                TNew: Identifyable<TS::Id>,
+                TS: Strategy + 'static,
+            >(
+                outer_ctx: &AutoFreePointer,
+                ctx: &mut UntypedFnFactoryContext<TS>,
+            ) -> InternalBuildResult<TS> {
+                let (nth, creator) =
+                    *unsafe { FactoryContext::<InnerContext>::from_erased(outer_ctx) }.get();
+                let mut positions = AllRegistered::<T>::iter_positions(&ctx.precheck_context);
+                let pos = match positions.nth(nth) {
+                    Some(x) => x,
+                    None => return RErr(BuildError::<TS>::new_missing_dependency::<T>().into()),
+                };
+                ctx.register_cyclic_reference_candidate(
+                    type_name::<T>(),
+                    DynTrait::from_value(core::iter::once(pos)),
+                );
+                extern "C-unwind" fn func<
+                    T: Identifyable<TS::Id>,
+                    TNew: Identifyable<TS::Id>,
+                    TS: Strategy + 'static,
+                >(
+                    provider: *const ServiceProvider<TS>,
+                    outer_ctx: *const AutoFreePointer,
+                ) -> TNew {
+                    let provider = unsafe { &*provider as &ServiceProvider<TS> };
+                    let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
+                    let (pos, creator) =
+                        *unsafe { FactoryContext::<InnerContext>::from_erased(outer_ctx) }.get();
+                    let creator: fn(T) -> TNew = unsafe { std::mem::transmute(creator) };
+                    creator(unsafe { resolvable::resolve_unchecked::<TS, T>(provider, pos) })
+                }
+                let inner: InnerContext = (pos, creator);
+                ROk(UntypedFn::create(
+                    func::<T, TNew, TS>,
+                    FactoryContext::new(inner).into_erased(),
+                ))
+            }
+            let inner: InnerContext = (nth, creator);
+            let factory = UntypedFnFactory::boxed(inner, factory::<T, TNew, TS>);
+            ServiceProducer::<TS>::new::<TNew>(factory).with_dependencies::<T>()
+        }
+        Self {
+            source: T::get_id(),
+            target: TNew::get_id(),
+            creator: creator as AnyPtr,
+            expand: expand::<T, TNew, TS>,
+        }
+    }
+}
+
+impl<TS: Strategy + 'static> Clone for AliasAllProducer<TS> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+type UntypedFnFactoryCreator<TS> = extern "C-unwind" fn(
+    outer_context: &AutoFreePointer,
     inner_context: &mut UntypedFnFactoryContext<TS>,
 ) -> InternalBuildResult<TS>;
 
 struct UntypedFnFactory<TS: Strategy + 'static> {
     creator: UntypedFnFactoryCreator<TS>,
     context: AutoFreePointer,
+    // Contexts are type-erased, so they are cloned by a function knowing their type, e.g. to clone collections
+    clone_context: fn(&AutoFreePointer) -> AutoFreePointer,
 }
 
 impl<TS: Strategy + 'static> UntypedFnFactory<TS> {
@@ -147,31 +1323,50 @@ impl<TS: Strategy + 'static> UntypedFnFactory<TS> {
         Self {
             creator,
             context: AutoFreePointer::no_alloc(context),
+            clone_context: |x| AutoFreePointer::no_alloc(x.get_pointer()),
         }
     }
-    fn boxed<T>(input: T, creator: UntypedFnFactoryCreator<TS>) -> Self {
+    fn boxed<T: Clone>(input: T, creator: UntypedFnFactoryCreator<TS>) -> Self {
+        fn clone_context<T: Clone>(context: &AutoFreePointer) -> AutoFreePointer {
+            let value = unsafe { FactoryContext::<T>::from_erased(context) }.get();
+            FactoryContext::new(value.clone()).into_erased()
+        }
         Self {
             creator,
-            context: AutoFreePointer::boxed(input),
+            context: FactoryContext::new(input).into_erased(),
+            clone_context: clone_context::<T>,
         }
     }
-    fn call(self, ctx: &mut UntypedFnFactoryContext<TS>) -> InternalBuildResult<TS> {
-        (self.creator)(self.context, ctx)
+    // Factories can be called multiple times, e.g. by GenericServiceCollection::build_ref()
+    fn call(&self, ctx: &mut UntypedFnFactoryContext<TS>) -> InternalBuildResult<TS> {
+        (self.creator)(&self.context, ctx)
+    }
+}
+
+impl<TS: Strategy + 'static> Clone for UntypedFnFactory<TS> {
+    fn clone(&self) -> Self {
+        Self {
+            creator: self.creator,
+            context: (self.clone_context)(&self.context),
+            clone_context: self.clone_context,
+        }
     }
 }
 
 struct UntypedFnFactoryContext<'a, TS: Strategy + 'static> {
     service_descriptor_pos: usize,
-    state_counter: &'a mut usize,
-    final_ordered_types: &'a RVec<TS::Id>,
+    teardown: SharedServiceTeardown,
+    // Type names of the shared services, one for each reserved state
+    state_names: &'a mut RVec<&'static str>,
+    type_name: &'static str,
+    precheck_context: PrecheckContext<'a, TS>,
     cyclic_reference_candidates: &'a mut RHashMap<usize, CycleCheckerValue>,
 }
 
 impl<'a, TS: Strategy + 'static> UntypedFnFactoryContext<'a, TS> {
     fn reserve_state_space(&mut self) -> usize {
-        let result: usize = *self.state_counter;
-        *self.state_counter += 1;
-        result
+        self.state_names.push(self.type_name);
+        self.state_names.len() - 1
     }
     fn register_cyclic_reference_candidate(
         &mut self,
@@ -183,7 +1378,8 @@ impl<'a, TS: Strategy + 'static> UntypedFnFactoryContext<'a, TS> {
             CycleCheckerValue {
                 is_visited: false,
                 type_description: type_name,
-                iter: dependencies,
+                dependencies: dependencies.collect(),
+                next: 0,
             },
         );
     }
@@ -195,15 +1391,81 @@ impl<TS: Strategy + 'static> Default for GenericServiceCollection<TS> {
     }
 }
 
+/// Clones all registrations, e.g. to build variants of the same base registrations.
+/// Instances of `register_instance` are cloned, whereas the services of both collections are created independently.
+/// ```
+/// let mut base = minfac::ServiceCollection::new();
+/// base.register_instance(String::from("base"));
+/// base.register(|| 1u8);
+///
+/// let mut variant = base.clone();
+/// variant.register(|| 2u8);
+///
+/// let base = base.build().expect("Configuration is valid");
+/// let variant = variant.build().expect("Configuration is valid");
+/// assert_eq!((Some(1u8), Some(2u8)), (base.get(), variant.get()));
+/// assert_eq!(base.get::<String>(), variant.get::<String>());
+/// ```
+impl<TS: Strategy + 'static> Clone for GenericServiceCollection<TS> {
+    fn clone(&self) -> Self {
+        Self {
+            strategy: PhantomData,
+            producer_factories: self.producer_factories.clone(),
+            alias_all_producers: self.alias_all_producers.clone(),
+            parent_overrides: self.parent_overrides.clone(),
+            unique_types: self.unique_types.clone(),
+            registered_keys: self.registered_keys.clone(),
+            key_checks: self.key_checks.clone(),
+            libraries: self.libraries.clone(),
+            profile: self.profile.clone(),
+            boxers: self.boxers.clone(),
+            #[cfg(feature = "serde")]
+            serializers: self.serializers.clone(),
+        }
+    }
+}
+
 impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
     /// Creates an empty ServiceCollection
     pub fn new() -> Self {
         Self {
             strategy: PhantomData,
             producer_factories: Vec::new(),
+            alias_all_producers: Vec::new(),
+            parent_overrides: Vec::new(),
+            unique_types: Vec::new(),
+            registered_keys: Vec::new(),
+            key_checks: Vec::new(),
+            libraries: Vec::new(),
+            profile: None,
+            boxers: Vec::new(),
+            #[cfg(feature = "serde")]
+            serializers: Vec::new(),
         }
     }
 
+    /// Creates an empty ServiceCollection with space for at least `capacity` registrations,
+    /// e.g. if the number of services is known from a manifest.
+    /// ```
+    /// let mut collection = minfac::ServiceCollection::with_capacity(1000);
+    /// for _ in 0..1000 {
+    ///     collection.register(|| 42u32);
+    /// }
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// assert_eq!(1000, provider.get_all::<u32>().count());
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut result = Self::new();
+        result.reserve(capacity);
+        result
+    }
+
+    /// Reserves space for at least `additional` registrations, analogous to `Vec::reserve`.
+    /// Aliases are registrations of their own, so they need space too.
+    pub fn reserve(&mut self, additional: usize) {
+        self.producer_factories.reserve(additional);
+    }
+
     /// Generate a ServiceBuilder with `T` as a dependency.
     /// An instance of T is provided as an argument to the factory fn:
     /// ``` rust
@@ -231,20 +1493,86 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
         ServiceBuilder(self, PhantomData)
     }
 
+    /// Registers an alias for the type of `handle`, like `AliasBuilder::alias` would.
+    /// The alias depends on the last registered instance of `T` when building, not on the registration the handle came from.
+    /// ```
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// let handles: Vec<_> = (0..3i8)
+    ///     .map(|_| collection.register(|| 2i8).handle())
+    ///     .collect();
+    /// collection.alias_registered(handles[0], |a| a as i16 * 21);
+    /// let provider = collection.build().expect("i8 is registered");
+    /// assert_eq!(Some(42i16), provider.get());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn alias_registered<T: Identifyable<TS::Id>, TNew: Identifyable<TS::Id>>(
+        &mut self,
+        _handle: RegistrationHandle<T>,
+        creator: fn(T) -> TNew,
+    ) -> AliasBuilder<'_, TNew, TS> {
+        self.with::<Registered<T>>().register(creator);
+        AliasBuilder::new(self)
+    }
+
+    /// Registers a coercion, which makes each instance of `T` resolvable as `TNew` as well, e.g. to provide shared
+    /// services `Arc<Concrete>` as `Arc<dyn Trait>` without aliasing each registration. Like `AliasBuilder::alias_all`,
+    /// all instances of `T` are coerced, including instances registered later or inherited from parents.
+    ///
+    /// If several types are coerced into the same `TNew`, `get_all` returns the instances of all of them. Coerced
+    /// services are listed after the services registered as `TNew` directly, ordered by the registration of their
+    /// coercion. `get` therefore returns the last instance of the last registered coercion, for which an instance exists.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// trait ConfigView: Send + Sync {
+    ///     fn name(&self) -> &'static str;
+    /// }
+    /// struct Config;
+    /// impl ConfigView for Config {
+    ///     fn name(&self) -> &'static str { "config" }
+    /// }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_coercion(|x: Arc<Config>| x as Arc<dyn ConfigView>);
+    /// collection.register_shared(|| Arc::new(Config));
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let view = provider.get::<Arc<dyn ConfigView>>().expect("Config is coerced");
+    /// assert_eq!("config", view.name());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_coercion<T: Identifyable<TS::Id>, TNew: Identifyable<TS::Id>>(
+        &mut self,
+        convert: fn(T) -> TNew,
+    ) -> AliasBuilder<'_, TNew, TS> {
+        log_registration!("Coerce {} into {}", T, TNew);
+        self.alias_all_producers
+            .push(AliasAllProducer::new::<T, TNew>(convert));
+        AliasBuilder::new(self)
+    }
+
     /// Register an instance to be resolvable
     /// If a ServiceProviderFactory is used, all ServicesProviders will clone from the same origin
+    /// ```
+    /// let mut col = minfac::ServiceCollection::new();
+    /// col.register_instance(21u8).alias(|x| x as u16 * 2);
+    /// let provider = col.build().expect("Configuration is valid");
+    /// assert_eq!(Some(42u16), provider.get());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
     pub fn register_instance<T: Identifyable<TS::Id> + Clone + 'static + Send + Sync>(
         &mut self,
         instance: T,
-    ) {
-        extern "C" fn factory<
+    ) -> AliasBuilder<'_, T, TS> {
+        log_registration!("Register instance {}", T);
+        extern "C-unwind" fn factory<
             T: Identifyable<TS::Id> + Clone + 'static + Send + Sync,
             TS: Strategy + 'static,
         >(
-            outer_ctx: AutoFreePointer,
+            outer_ctx: &AutoFreePointer,
             _ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS> {
-            extern "C" fn func<
+            extern "C-unwind" fn func<
                 T: Identifyable<TS::Id> + Clone + 'static + Send + Sync,
                 TS: Strategy + 'static,
             >(
@@ -252,24 +1580,126 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
                 outer_ctx: *const AutoFreePointer,
             ) -> T {
                 let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
-                unsafe { &*(outer_ctx.get_pointer() as *mut T) }.clone()
+                unsafe { FactoryContext::<T>::from_erased(outer_ctx) }
+                    .get()
+                    .clone()
             }
-            ROk(UntypedFn::create(func::<T, TS>, outer_ctx))
+            let instance = unsafe { FactoryContext::<T>::from_erased(outer_ctx) }
+                .get()
+                .clone();
+            ROk(UntypedFn::create(
+                func::<T, TS>,
+                FactoryContext::new(instance).into_erased(),
+            ))
         }
 
         let factory = UntypedFnFactory::boxed(instance, factory::<T, TS>);
         self.producer_factories
             .push(ServiceProducer::<TS>::new::<T>(factory));
+        AliasBuilder::new(self)
+    }
+
+    /// Registers the value of the environment variable `name` parsed as `T`. The variable is read whenever
+    /// the collection is built, which fails with `BuildError::EnvironmentVariable`, if it's missing or
+    /// cannot be parsed. Use `register_instance` for optional variables, e.g. with a default value.
+    /// ```
+    /// use minfac::{BuildError, ServiceCollection};
+    ///
+    /// std::env::set_var("MINFAC_DOC_PORT", "8080");
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_from_env::<u16>("MINFAC_DOC_PORT");
+    /// let provider = collection.build().expect("Variable is set");
+    /// assert_eq!(Some(8080), provider.get::<u16>());
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_from_env::<u16>("MINFAC_DOC_MISSING");
+    /// assert!(matches!(collection.build(), Err(BuildError::EnvironmentVariable { .. })));
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_from_env<T>(&mut self, name: &'static str) -> AliasBuilder<'_, T, TS>
+    where
+        T: Identifyable<TS::Id> + core::str::FromStr + Clone + Send + Sync,
+        T::Err: core::fmt::Display,
+    {
+        log_registration!("Register {} from environment variable", T);
+        extern "C-unwind" fn factory<T, TS: Strategy + 'static>(
+            outer_ctx: &AutoFreePointer,
+            _ctx: &mut UntypedFnFactoryContext<TS>,
+        ) -> InternalBuildResult<TS>
+        where
+            T: Identifyable<TS::Id> + core::str::FromStr + Clone + Send + Sync,
+            T::Err: core::fmt::Display,
+        {
+            extern "C-unwind" fn func<T: Identifyable<TS::Id> + Clone, TS: Strategy + 'static>(
+                _: *const ServiceProvider<TS>,
+                outer_ctx: *const AutoFreePointer,
+            ) -> T {
+                let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
+                unsafe { FactoryContext::<T>::from_erased(outer_ctx) }
+                    .get()
+                    .clone()
+            }
+            let name = *unsafe { FactoryContext::<&'static str>::from_erased(outer_ctx) }.get();
+            let value = std::env::var(name)
+                .map_err(|e| e.to_string())
+                .and_then(|x| x.parse::<T>().map_err(|e| e.to_string()));
+            match value {
+                Ok(value) => ROk(UntypedFn::create(
+                    func::<T, TS>,
+                    FactoryContext::new(value).into_erased(),
+                )),
+                Err(message) => RErr(InternalBuildError::EnvironmentVariable {
+                    name: name.into(),
+                    message: message.into(),
+                }),
+            }
+        }
+
+        let factory = UntypedFnFactory::boxed(name, factory::<T, TS>);
+        self.producer_factories
+            .push(ServiceProducer::<TS>::new::<T>(factory));
+        AliasBuilder::new(self)
+    }
+
+    /// Registers an instance like `register_instance`, but removes all services of type `T`, which were
+    /// registered in this collection before, so only one exists, e.g. for a singular configuration.
+    /// Services inherited from parents are unaffected, see `override_parent` to hide them. With the `serde` feature,
+    /// removed instances of `T` aren't part of `snapshot_instances` anymore.
+    /// ```
+    /// let mut col = minfac::ServiceCollection::new();
+    /// col.register_instance(1u8);
+    /// col.register(|| 2u8);
+    /// col.set_instance(3u8);
+    /// let provider = col.build().expect("Configuration is valid");
+    /// assert_eq!(vec![3u8], provider.get_all::<u8>().collect::<Vec<_>>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn set_instance<T: Identifyable<TS::Id> + Clone + 'static + Send + Sync>(
+        &mut self,
+        instance: T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let id = T::get_id();
+        self.producer_factories.retain(|x| x.identifier != id);
+        #[cfg(feature = "serde")]
+        self.serializers
+            .retain(|(name, _)| *name != core::any::type_name::<T>());
+        self.register_instance(instance)
     }
 
     /// Registers a transient service without dependencies.
     /// To add dependencies, use `with` to generate a ServiceBuilder.
-    pub fn register<T: Identifyable<TS::Id>>(&mut self, creator: fn() -> T) -> AliasBuilder<T, TS> {
-        extern "C" fn factory<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
-            stage_1_data: AutoFreePointer,
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn() -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        log_registration!("Register {}", T);
+        extern "C-unwind" fn factory<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+            stage_1_data: &AutoFreePointer, // No-Alloc
             _ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS> {
-            extern "C" fn func<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+            extern "C-unwind" fn func<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
                 _: *const ServiceProvider<TS>,
                 stage_2_data: *const AutoFreePointer,
             ) -> T {
@@ -278,7 +1708,10 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
                 let creator: fn() -> T = unsafe { core::mem::transmute(ptr) };
                 creator()
             }
-            ROk(UntypedFn::create(func::<T, TS>, stage_1_data))
+            ROk(UntypedFn::create(
+                func::<T, TS>,
+                AutoFreePointer::no_alloc(stage_1_data.get_pointer()),
+            ))
         }
 
         let factory = UntypedFnFactory::no_alloc(creator as AnyPtr, factory::<T, TS>);
@@ -287,68 +1720,524 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
         AliasBuilder::new(self)
     }
 
+    /// Registers a transient service like `register`, but resolvable under `id` instead of the id of `T`,
+    /// e.g. to implement custom keying schemes on top of a Strategy with ids chosen at runtime.
+    /// ```
+    /// use {minfac::ServiceCollection, core::any::TypeId};
+    ///
+    /// #[repr(transparent)]
+    /// struct Port(u16);
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// // Sound, because Port is a transparent wrapper of u16
+    /// unsafe { collection.register_with_id(TypeId::of::<Port>(), || 8080u16) };
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert_eq!(Some(8080), provider.get::<Port>().map(|p| p.0));
+    /// assert_eq!(None, provider.get::<u16>());
+    /// ```
+    ///
+    /// # Safety
+    /// Services are resolved by id, so every type, which is resolved by `id`, must have the same layout and ABI as `T`.
+    /// Otherwise, resolving it is undefined behavior.
+    #[cfg_attr(feature = "log", track_caller)]
+    pub unsafe fn register_with_id<T: Identifyable<TS::Id>>(
+        &mut self,
+        id: TS::Id,
+        creator: fn() -> T,
+    ) {
+        log_registration!("Register {} with a custom id", T);
+        extern "C-unwind" fn factory<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+            outer_ctx: &AutoFreePointer,
+            _ctx: &mut UntypedFnFactoryContext<TS>,
+        ) -> InternalBuildResult<TS> {
+            extern "C-unwind" fn func<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+                _: *const ServiceProvider<TS>,
+                inner_ctx: *const AutoFreePointer,
+            ) -> T {
+                let ptr = unsafe { &*inner_ctx as &AutoFreePointer }.get_pointer();
+                let creator: fn() -> T = unsafe { core::mem::transmute(ptr) };
+                creator()
+            }
+            let (creator, id): (AnyPtr, TS::Id) =
+                *unsafe { FactoryContext::<(AnyPtr, TS::Id)>::from_erased(outer_ctx) }.get();
+            let producer = UntypedFn::create(func::<T, TS>, AutoFreePointer::no_alloc(creator));
+            ROk(unsafe { producer.with_result_type_id(id) })
+        }
+
+        let factory = UntypedFnFactory::boxed((creator as AnyPtr, id), factory::<T, TS>);
+        self.producer_factories
+            .push(ServiceProducer::<TS>::new_with_type(
+                factory,
+                id,
+                type_name::<T>(),
+            ));
+    }
+
+    /// Registers a transient service like `register`, but `build` fails with `BuildError::DuplicateUnique`,
+    /// if any other service of type `T` is registered, including services inherited from parents.
+    /// This catches composition mistakes, e.g. if two plugins provide the application config.
+    /// ```
+    /// use minfac::{BuildError, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_unique(|| 42i32);
+    /// collection.register(|| 1i32);
+    ///
+    /// assert!(matches!(collection.build(), Err(BuildError::DuplicateUnique { .. })));
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_unique<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn() -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let id = T::get_id();
+        if !self.unique_types.contains(&id) {
+            self.unique_types.push(id);
+        }
+        self.register(creator)
+    }
+
+    /// Registers a transient service like `register`, which is only resolved by `get`, if no other service of type `T`
+    /// is registered, no matter whether it's registered before, afterwards or in a parent ServiceProvider.
+    /// Fallbacks are listed before all other services of `T` by `get_all`.
+    /// ```
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// collection.register_fallback(|| "default");
+    /// collection.register(|| "configured");
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// assert_eq!(Some("configured"), provider.get::<&str>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_fallback<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn() -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let position = self.producer_factories.len();
+        self.register(creator);
+        self.producer_factories[position].is_fallback = true;
+        AliasBuilder::new(self)
+    }
+
+    /// Registers a transient service like `register`, which is only built if `profile` is the active profile of
+    /// this collection, e.g. to choose implementations per environment. See `set_profile`.
+    /// Aliases, groups and sections registered with the returned AliasBuilder have the same profile.
+    /// ```
+    /// #[derive(PartialEq)]
+    /// enum Profile { Dev, Prod }
+    ///
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// collection.register(|| "default");
+    /// collection.register_for(Profile::Dev, || "dev");
+    /// collection.register_for(Profile::Prod, || "prod");
+    /// collection.set_profile(Profile::Dev);
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// assert_eq!(vec!["default", "dev"], provider.get_all::<&str>().collect::<Vec<_>>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_for<P: PartialEq + Send + Sync + 'static, T: Identifyable<TS::Id>>(
+        &mut self,
+        profile: P,
+        creator: fn() -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let position = self.producer_factories.len();
+        self.register(creator);
+        let matcher: ProfileMatcher =
+            Arc::new(move |active| active.downcast_ref::<P>() == Some(&profile));
+        self.producer_factories[position].profile = Some(matcher.clone());
+        AliasBuilder::new(self).with_profile(matcher)
+    }
+
+    /// Sets the active profile, which decides which services of `register_for` are built. Without an active
+    /// profile, none of them are built. Services of `register_for` with profiles of another type are never built.
+    pub fn set_profile<P: PartialEq + Send + Sync + 'static>(&mut self, profile: P) {
+        self.profile = Some(Arc::new(profile));
+    }
+
+    /// Registers a transient service, which is resolved by `key` with `ServiceProvider::get_keyed`, e.g. one
+    /// handler per variant of a `MessageKind` enum. Use `expect_keys` to ensure that all variants are covered.
+    /// If a key is registered multiple times, the last registration wins.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// enum MessageKind { Text, Binary }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_keyed(MessageKind::Text, || "text handler");
+    /// collection.register_keyed(MessageKind::Binary, || "binary handler");
+    /// collection.expect_keys::<MessageKind, &str>(&[MessageKind::Text, MessageKind::Binary]);
+    /// let provider = collection.build().expect("All keys are registered");
+    ///
+    /// assert_eq!(Some("binary handler"), provider.get_keyed::<_, &str>(&MessageKind::Binary));
+    /// assert_eq!(None, provider.get::<&str>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_keyed<K, T>(&mut self, key: K, creator: fn() -> T)
+    where
+        K: Clone + PartialEq + Debug + Send + Sync + 'static,
+        T: 'static,
+        KeyedService<K, T>: Identifyable<TS::Id>,
+        ServiceKey<K, T>: Identifyable<TS::Id>,
+    {
+        extern "C-unwind" fn factory<K: 'static, T: 'static, TS: Strategy + 'static>(
+            stage_1_data: &AutoFreePointer, // No-Alloc
+            _ctx: &mut UntypedFnFactoryContext<TS>,
+        ) -> InternalBuildResult<TS>
+        where
+            KeyedService<K, T>: Identifyable<TS::Id>,
+        {
+            #[allow(improper_ctypes_definitions)]
+            extern "C-unwind" fn func<K: 'static, T: 'static, TS: Strategy + 'static>(
+                _: *const ServiceProvider<TS>,
+                stage_2_data: *const AutoFreePointer,
+            ) -> KeyedService<K, T>
+            where
+                KeyedService<K, T>: Identifyable<TS::Id>,
+            {
+                let stage_2_data = unsafe { &*stage_2_data as &AutoFreePointer };
+                let creator: fn() -> T =
+                    unsafe { core::mem::transmute(stage_2_data.get_pointer()) };
+                KeyedService(creator(), PhantomData)
+            }
+            ROk(UntypedFn::create(
+                func::<K, T, TS>,
+                AutoFreePointer::no_alloc(stage_1_data.get_pointer()),
+            ))
+        }
+
+        self.registered_keys
+            .push((ServiceKey::<K, T>::get_id(), Arc::new(key.clone())));
+        self.register_instance(ServiceKey::<K, T>(key, PhantomData));
+        log_registration!("Register keyed {}", T);
+        let factory = UntypedFnFactory::no_alloc(creator as AnyPtr, factory::<K, T, TS>);
+        self.producer_factories
+            .push(ServiceProducer::<TS>::new::<KeyedService<K, T>>(factory));
+    }
+
+    /// Lets `build` fail with `BuildError::MissingKey`, if any of `keys` isn't registered with `register_keyed`
+    /// for services of type `T`. Keys registered in parent providers are not considered.
+    pub fn expect_keys<K, T>(&mut self, keys: &[K])
+    where
+        K: Clone + PartialEq + Debug + Send + Sync + 'static,
+        T: 'static,
+        ServiceKey<K, T>: Identifyable<TS::Id>,
+    {
+        let keys = keys.to_vec();
+        self.key_checks.push(Arc::new(move |registered| {
+            let id = ServiceKey::<K, T>::get_id();
+            let registered = registered
+                .iter()
+                .filter(|(key_id, _)| *key_id == id)
+                .filter_map(|(_, key)| key.downcast_ref::<K>());
+            match keys.iter().find(|k| !registered.clone().any(|r| r == *k)) {
+                Some(missing) => Err(BuildError::MissingKey {
+                    name: type_name::<T>(),
+                    key: format!("{missing:?}"),
+                }),
+                None => Ok(()),
+            }
+        }));
+    }
+
+    /// Registers a transient service like `register`, but services of the same type inherited from
+    /// parent providers are ignored. Within a child, `get::<T>()` and `get_all::<T>()` therefore only
+    /// return services registered in the child collection, while the parent itself is unaffected.
+    /// Without parents, it behaves exactly like `register`.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut parent = ServiceCollection::new();
+    /// parent.register(|| 1i32);
+    /// let parent = parent.build().expect("Configuration is valid");
+    ///
+    /// let mut child = ServiceCollection::new();
+    /// child.override_parent(|| 2i32);
+    /// let factory = child.with_parent(&parent).build_factory::<()>().unwrap();
+    /// let child = factory.build(());
+    ///
+    /// assert_eq!(vec![2], child.get_all::<i32>().collect::<Vec<_>>());
+    /// assert_eq!(Some(1), parent.get::<i32>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn override_parent<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn() -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let id = T::get_id();
+        if !self.parent_overrides.contains(&id) {
+            self.parent_overrides.push(id);
+        }
+        self.register(creator)
+    }
+
     /// Registers a shared service without dependencies.
     /// To add dependencies, use `with` to generate a ServiceBuilder.
+    /// The creator has to wrap the service into `Arc::new(...)`, which is also the type used to resolve it.
     ///
     /// Shared services must have a reference count == 0 after dropping the ServiceProvider. If an Arc is
     /// cloned and thus kept alive, ServiceProvider::drop will panic to prevent service leaking in std.
+    ///
+    /// Creators returning the plain service are rejected with a type mismatch on the closure:
+    /// ```compile_fail
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// collection.register_shared(|| 42); // expected `Arc<_>`, found integer
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
     pub fn register_shared<T: Send + Sync>(
         &mut self,
         creator: fn() -> Arc<T>,
-    ) -> AliasBuilder<Arc<T>, TS>
+    ) -> SharedBuilder<'_, T, TS>
+    where
+        Arc<T>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register shared {}", Arc<T>);
+        fn create<T>(creator: AnyPtr) -> Arc<T> {
+            let creator: fn() -> Arc<T> = unsafe { std::mem::transmute(creator) };
+            creator()
+        }
+        self.register_shared_with(creator as AnyPtr, create::<T>)
+    }
+
+    /// Registers a shared service, which is wrapped into a `Mutex` to be `Sync`, so `T` only has to be `Send`.
+    /// The service is resolvable as `Arc<Mutex<T>>`.
+    /// ```
+    /// use {minfac::ServiceCollection, std::{cell::Cell, sync::{Arc, Mutex}}};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared_mutex(|| Cell::new(1i32));
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let counter = provider.get::<Arc<Mutex<Cell<i32>>>>().unwrap();
+    /// counter.lock().unwrap().set(2);
+    /// assert_eq!(2, provider.get::<Arc<Mutex<Cell<i32>>>>().unwrap().lock().unwrap().get());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_shared_mutex<T: Send>(
+        &mut self,
+        creator: fn() -> T,
+    ) -> SharedBuilder<'_, Mutex<T>, TS>
+    where
+        Arc<Mutex<T>>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register shared {}", Arc<Mutex<T>>);
+        fn create<T>(creator: AnyPtr) -> Arc<Mutex<T>> {
+            let creator: fn() -> T = unsafe { std::mem::transmute(creator) };
+            Arc::new(Mutex::new(creator()))
+        }
+        self.register_shared_with(creator as AnyPtr, create::<T>)
+    }
+
+    // `create` is called with `creator` to initialize the shared service
+    fn register_shared_with<T: Send + Sync>(
+        &mut self,
+        creator: AnyPtr,
+        create: fn(AnyPtr) -> Arc<T>,
+    ) -> SharedBuilder<'_, T, TS>
     where
         Arc<T>: Identifyable<TS::Id>,
     {
-        type InnerContext = (usize, AnyPtr);
-        extern "C" fn factory<T: Send + Sync, TS: Strategy + 'static>(
-            outer_ctx: AutoFreePointer, // No-Alloc
+        type OuterContext<T> = (AnyPtr, fn(AnyPtr) -> Arc<T>);
+        type InnerContext<T> = (usize, OuterContext<T>, SharedServiceTeardown);
+        extern "C-unwind" fn factory<T: Send + Sync, TS: Strategy + 'static>(
+            outer_ctx: &AutoFreePointer,
             ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS>
         where
             Arc<T>: Identifyable<TS::Id>,
         {
-            extern "C" fn func<T: Send + Sync + 'static, TS: Strategy + 'static>(
+            #[allow(improper_ctypes_definitions)]
+            extern "C-unwind" fn func<T: Send + Sync + 'static, TS: Strategy + 'static>(
                 provider: *const ServiceProvider<TS>,
                 outer_ctx: *const AutoFreePointer,
             ) -> Arc<T> {
+                unsafe { get_shared::<T, TS>(provider, outer_ctx).clone_inner::<T>() }
+            }
+            extern "C-unwind" fn shared_pointer<
+                T: Send + Sync + 'static,
+                TS: Strategy + 'static,
+            >(
+                provider: *const ServiceProvider<TS>,
+                outer_ctx: *const AutoFreePointer,
+            ) -> *const ArcAutoFreePointer {
+                get_shared::<T, TS>(provider, outer_ctx)
+            }
+            fn get_shared<'a, T: Send + Sync + 'static, TS: Strategy + 'static>(
+                provider: *const ServiceProvider<TS>,
+                outer_ctx: *const AutoFreePointer,
+            ) -> &'a ArcAutoFreePointer {
                 let provider = unsafe { &*provider as &ServiceProvider<TS> };
                 let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
-                let (service_state_idx, fnptr) =
-                    unsafe { &*(outer_ctx.get_pointer() as *mut InnerContext) };
-                let creator: fn() -> Arc<T> = unsafe { std::mem::transmute(*fnptr) };
-                provider.get_or_initialize_pos(*service_state_idx, creator)
+                let (service_state_idx, (creator, create), teardown) =
+                    unsafe { FactoryContext::<InnerContext<T>>::from_erased(outer_ctx) }.get();
+                provider.get_or_initialize_pos(*service_state_idx, *teardown, || create(*creator))
             }
             let service_state_idx = ctx.reserve_state_space();
-            let inner: InnerContext = (service_state_idx, outer_ctx.get_pointer());
-            ROk(UntypedFn::create(func, AutoFreePointer::boxed(inner)))
+            let outer: OuterContext<T> =
+                *unsafe { FactoryContext::<OuterContext<T>>::from_erased(outer_ctx) }.get();
+            let inner: InnerContext<T> = (service_state_idx, outer, ctx.teardown);
+            ROk(
+                UntypedFn::create(func::<T, TS>, FactoryContext::new(inner).into_erased())
+                    .with_shared_pointer(shared_pointer::<T, TS>),
+            )
         }
 
-        let factory = UntypedFnFactory::no_alloc(creator as AnyPtr, factory::<T, TS>);
+        let outer: OuterContext<T> = (creator, create);
+        let factory = UntypedFnFactory::boxed(outer, factory::<T, TS>);
         self.producer_factories
-            .push(ServiceProducer::<TS>::new::<Arc<T>>(factory));
+            .push(ServiceProducer::<TS>::new::<Arc<T>>(factory).with_kind(ServiceKind::Shared));
 
+        SharedBuilder::new(self)
+    }
+
+    /// Registers a shared service for each creator, so all of them are resolvable via `get_all::<Arc<T>>()`.
+    /// Each shared service is initialized at most once, independent of the others.
+    /// The returned AliasBuilder refers to the last registered service. Use `alias_all` to alias each of them.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_all_shared(&[|| Arc::new(1i32), || Arc::new(2i32)]);
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// assert_eq!(vec![1, 2], provider.get_all::<Arc<i32>>().map(|x| *x).collect::<Vec<_>>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_all_shared<T: Send + Sync>(
+        &mut self,
+        creators: &[fn() -> Arc<T>],
+    ) -> AliasBuilder<'_, Arc<T>, TS>
+    where
+        Arc<T>: Identifyable<TS::Id>,
+    {
+        for creator in creators {
+            self.register_shared(*creator);
+        }
         AliasBuilder::new(self)
     }
 
     /// Checks, if all dependencies of registered services are available.
     /// If no errors occured, Ok(ServiceProvider) is returned.
+    /// Use `build_ref` to keep the collection, e.g. to rebuild it after further registrations.
     pub fn build(self) -> Result<ServiceProvider<TS>, BuildError<TS>> {
-        let validation = self.validate_producers(Vec::new())?;
-        let shared_services = (0..validation.service_states_count)
-            .map(|_| OnceLock::default())
-            .collect();
-        let immutable_state = RArc::new(service_provider::ServiceProviderImmutableState::new(
-            validation.types,
-            validation.producers,
-            RVec::new(),
-        ));
+        self.build_with(None)
+    }
+
+    /// Same as `build`, but `instrument` is called with the type name and duration of each validated producer.
+    /// This helps to find slow registrations in large collections, e.g. when loading many plugins.
+    /// Only the build is measured. Resolving services later doesn't call `instrument`.
+    /// ```
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// let mut names = Vec::new();
+    /// collection
+    ///     .build_instrumented(|name, _duration| names.push(name))
+    ///     .expect("Configuration is valid");
+    /// assert_eq!(vec!["u8"], names);
+    /// ```
+    pub fn build_instrumented(
+        self,
+        mut instrument: impl FnMut(&'static str, Duration),
+    ) -> Result<ServiceProvider<TS>, BuildError<TS>> {
+        self.build_with(Some(&mut instrument))
+    }
+
+    /// Same as `build`, but the collection isn't consumed and can be used to build further independent
+    /// ServiceProviders. In contrast to ServiceProviderFactory, the providers don't share any state, so
+    /// each has its own instances of shared services.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42i32));
+    /// let first = collection.build_ref().expect("Configuration is valid");
+    /// let second = collection.build_ref().expect("Configuration is valid");
+    ///
+    /// let (a, b) = (first.get::<Arc<i32>>().unwrap(), second.get::<Arc<i32>>().unwrap());
+    /// assert!(!Arc::ptr_eq(&a, &b));
+    /// ```
+    /// Registrations aren't copied: Creators are function pointers and services only keep the
+    /// small contexts created for them while validating, so building repeatedly is cheap.
+    pub fn build_ref(&self) -> Result<ServiceProvider<TS>, BuildError<TS>> {
+        self.build_with(None)
+    }
+
+    /// Performs all checks of `build` without creating a ServiceProvider, e.g. in extension traits which
+    /// check the registrations made so far. Like all methods taking `&self`, it leaves the collection
+    /// unchanged, so services can still be registered and the collection can be validated or built again.
+    /// ```
+    /// use minfac::{BuildError, Registered, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<Registered<u8>>().register(|x| x as u16);
+    /// assert!(matches!(collection.validate(), Err(BuildError::MissingDependency { .. })));
+    ///
+    /// collection.register(|| 1u8);
+    /// assert!(collection.validate().is_ok());
+    /// ```
+    pub fn validate(&self) -> Result<(), BuildError<TS>> {
+        self.validate_producers(Vec::new(), None).map(|_| ())
+    }
+
+    fn build_with(
+        &self,
+        instrument: Option<&mut dyn FnMut(&'static str, Duration)>,
+    ) -> Result<ServiceProvider<TS>, BuildError<TS>> {
+        let validation = self.validate_producers(Vec::new(), instrument)?;
+        let service_states_count = validation.shared_type_names.len();
+        let immutable_state = RArc::new(
+            service_provider::ServiceProviderImmutableState::new(
+                validation.types,
+                validation.type_names,
+                validation.kinds,
+                validation.index,
+                validation.producers,
+                RVec::new(),
+                validation.libraries,
+            )
+            .with_shared_type_names(validation.shared_type_names)
+            .with_dependencies(validation.dependencies)
+            .with_origins(validation.origins)
+            .with_boxers(validation.boxers),
+        );
         Ok(ServiceProvider::<TS>::new(
             immutable_state,
-            shared_services,
+            service_states_count,
             None,
         ))
     }
 
+    /// Same as `build`, but panics with the error and a listing of all registered services and
+    /// their declared dependencies. This is intended to be used in tests, where `build().unwrap()`
+    /// would only print the error itself.
+    /// ``` rust,should_panic
+    /// use minfac::{Registered, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<Registered<u8>>().register(|byte| byte as i16 * 2);
+    /// collection.build_or_report(); // Panics, because u8 is not registered
+    /// ```
+    #[track_caller]
+    pub fn build_or_report(self) -> ServiceProvider<TS> {
+        let report = self.report();
+        match self.build() {
+            Ok(provider) => provider,
+            Err(e) => panic!("Failed to build ServiceProvider: {:?}\n{}", e, report),
+        }
+    }
+
+    fn report(&self) -> String {
+        let mut producers: Vec<_> = self.active_producers().collect();
+        producers.sort_by_key(|a| a.identifier);
+        producers
+            .into_iter()
+            .fold(String::from("Registered services:"), |acc, p| {
+                match p.dependencies {
+                    Some(dependencies) => acc + "\n  " + p.type_name + " <- " + dependencies,
+                    None => acc + "\n  " + p.type_name,
+                }
+            })
+    }
+
     ///
     /// Returns a factory which can efficiently create ServiceProviders from
     /// ServiceCollections which are missing one dependent service T (e.g. HttpRequest, StartupConfiguration)
@@ -362,6 +2251,12 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
         ServiceProviderFactory::<_, TS>::create(self, RVec::new())
     }
 
+    /// Returns a factory like `build_factory`, whose base type is chosen for each ServiceProvider it builds.
+    /// This avoids registering the same services again for each base type.
+    pub fn build_multi_base_factory(self) -> MultiBaseFactory<TS> {
+        MultiBaseFactory::new(self, RVec::new())
+    }
+
     pub fn with_parent(
         self,
         provider: impl Into<WeakServiceProvider<TS>>,
@@ -369,65 +2264,362 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
         ServiceProviderFactoryBuilder::create(self, provider.into())
     }
 
+    /// Lists the dependencies, which neither a registered service nor one of the `provided` types satisfies.
+    /// This allows a host to check, whether a plugin's collection is compatible before wiring everything.
+    /// Unlike `build`, all producers are checked, but only `BuildError::MissingDependency` is reported.
+    /// Each missing type is listed once.
+    /// ```
+    /// use {
+    ///     core::any::TypeId,
+    ///     minfac::{BuildError, Registered, ServiceCollection},
+    /// };
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<Registered<i32>>().register(|x| x as i64);
+    /// collection.with::<Registered<u8>>().register(|x| x as u64);
+    ///
+    /// let missing = collection.missing_dependencies(&[TypeId::of::<i32>()]);
+    /// assert!(matches!(
+    ///     missing.as_slice(),
+    ///     [BuildError::MissingDependency { id, .. }] if *id == TypeId::of::<u8>()
+    /// ));
+    /// ```
+    pub fn missing_dependencies(&self, provided: &[TS::Id]) -> Vec<BuildError<TS>> {
+        // Provided types don't have a producer. They are assumed to be shared, so every resolvable accepts them
+        self.missing_dependencies_with(provided.iter().map(|id| (*id, ServiceKind::Shared)))
+    }
+
+    /// Lists the dependencies like `missing_dependencies`, which neither a registered service nor a service of the
+    /// host's `manifest` satisfies. In contrast to `missing_dependencies`, transient services of the host don't
+    /// satisfy dependencies which require shared services, like `Shared<T>`.
+    /// ```
+    /// use minfac::{BuildError, Registered, ServiceCollection, Shared};
+    /// use std::sync::Arc;
+    ///
+    /// let mut host = ServiceCollection::new();
+    /// host.register(|| 1i32);
+    /// host.register(|| Arc::new(2u8));
+    /// let manifest = host.build().expect("Configuration is valid").export_manifest();
+    ///
+    /// let mut plugin = ServiceCollection::new();
+    /// plugin.with::<Registered<i32>>().register(|x| x as i64);
+    /// plugin.with::<Shared<u8>>().register(|x| *x as u64);
+    ///
+    /// let missing = plugin.check_against(&manifest);
+    /// assert!(matches!(
+    ///     missing.as_slice(),
+    ///     [BuildError::MissingDependency { name, .. }] if name.contains("u8")
+    /// ));
+    /// ```
+    pub fn check_against(&self, manifest: &TypeManifest<TS>) -> Vec<BuildError<TS>> {
+        self.missing_dependencies_with(manifest.services.iter().map(|(id, _, kind)| (*id, *kind)))
+    }
+
+    fn missing_dependencies_with(
+        &self,
+        provided: impl Iterator<Item = (TS::Id, ServiceKind)>,
+    ) -> Vec<BuildError<TS>> {
+        let expanded = self.expand_alias_all_producers(&[]);
+        let mut entries: Vec<_> = self
+            .active_producers()
+            .chain(expanded.iter())
+            .map(|f| (f.identifier, f.kind, Some(f)))
+            .chain(provided.map(|(id, kind)| (id, kind, None)))
+            .collect();
+        entries.sort_by_key(|(id, _, f)| (*id, !f.is_some_and(|f| f.is_fallback)));
+
+        let types: RVec<_> = entries.iter().map(|(id, ..)| *id).collect();
+        let kinds: RVec<_> = entries.iter().map(|(_, kind, _)| *kind).collect();
+        let position_index = resolvable::build_position_index::<TS>(&types);
+        let mut state_names = RVec::new();
+        let mut cyclic_reference_candidates = RHashMap::new();
+        let mut missing = Vec::new();
+
+        for (i, (_, _, producer)) in entries.iter().enumerate() {
+            let Some(producer) = producer else {
+                continue;
+            };
+            let mut ctx = UntypedFnFactoryContext {
+                state_names: &mut state_names,
+                type_name: producer.type_name,
+                precheck_context: PrecheckContext::new(&types, &kinds)
+                    .with_index(&position_index)
+                    .with_keys(&self.registered_keys)
+                    .with_current(i),
+                cyclic_reference_candidates: &mut cyclic_reference_candidates,
+                service_descriptor_pos: i,
+                teardown: producer.teardown,
+            };
+            if let RErr(InternalBuildError::MissingDependency { id, name }) =
+                producer.call_factory(&mut ctx)
+            {
+                let listed = missing
+                    .iter()
+                    .any(|e| matches!(e, BuildError::MissingDependency { id: x, .. } if *x == id));
+                if !listed {
+                    missing.push(BuildError::MissingDependency {
+                        id,
+                        name: name.into(),
+                    });
+                }
+            }
+        }
+        missing
+    }
+
+    /// Lists the type names of all services, which a service of type `T` depends on directly or indirectly.
+    /// All registrations of `T` are considered. Each name is listed once, in the order it was discovered.
+    /// The collection is validated like in `build`, so configuration errors are returned instead.
+    /// ```
+    /// use minfac::{Registered, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// collection.with::<Registered<u8>>().register(|x| x as u16);
+    /// collection.with::<Registered<u16>>().register(|x| x as u32);
+    /// collection.register(|| 1i8);
+    ///
+    /// let dependencies = collection.transitive_dependencies::<u32>().unwrap();
+    /// assert_eq!(vec!["u16", "u8"], dependencies);
+    /// ```
+    pub fn transitive_dependencies<T: Identifyable<TS::Id>>(
+        &self,
+    ) -> Result<Vec<&'static str>, BuildError<TS>> {
+        let validation = self.validate_producers(Vec::new(), None)?;
+        let roots = PrecheckContext::<TS>::new(&validation.types, &validation.kinds)
+            .with_index(&validation.index)
+            .positions(&T::get_id());
+        let mut visited = alloc::vec![false; validation.types.len()];
+        let mut pending: Vec<_> = roots
+            .rev()
+            .flat_map(|x| validation.dependencies[x].iter().rev().copied())
+            .collect();
+        let mut names = Vec::new();
+        while let Some(current) = pending.pop() {
+            if core::mem::replace(&mut visited[current], true) {
+                continue;
+            }
+            let name = validation.type_names[current];
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            pending.extend(validation.dependencies[current].iter().rev().copied());
+        }
+        Ok(names)
+    }
+
+    /// Lists the type names of all services, which neither are one of the `roots` nor a direct or indirect
+    /// dependency of them, e.g. to find registrations a plugin set doesn't need. Each name is listed once.
+    /// This is advisory only: Services resolved at runtime, e.g. via `WeakServiceProvider`, aren't
+    /// visible to this analysis. The collection is validated like in `build`, so configuration errors
+    /// are returned instead.
+    /// ```
+    /// use {core::any::TypeId, minfac::{Registered, ServiceCollection}};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// collection.with::<Registered<u8>>().register(|x| x as u16);
+    /// collection.register(|| 1i8);
+    ///
+    /// let unreachable = collection.unreachable_services(&[TypeId::of::<u16>()]).unwrap();
+    /// assert_eq!(vec!["i8"], unreachable);
+    /// ```
+    pub fn unreachable_services(
+        &self,
+        roots: &[TS::Id],
+    ) -> Result<Vec<&'static str>, BuildError<TS>> {
+        let validation = self.validate_producers(Vec::new(), None)?;
+        let ctx = PrecheckContext::<TS>::new(&validation.types, &validation.kinds)
+            .with_index(&validation.index);
+        let mut visited = alloc::vec![false; validation.types.len()];
+        let mut pending: Vec<_> = roots.iter().flat_map(|id| ctx.positions(id)).collect();
+        while let Some(current) = pending.pop() {
+            if !core::mem::replace(&mut visited[current], true) {
+                pending.extend(validation.dependencies[current].iter().copied());
+            }
+        }
+        let mut names = Vec::new();
+        for (name, _) in validation
+            .type_names
+            .iter()
+            .zip(visited)
+            .filter(|(_, visited)| !visited)
+        {
+            if !names.contains(name) {
+                names.push(*name);
+            }
+        }
+        Ok(names)
+    }
+
+    // Producers registered with register_for are skipped, unless their profile is the active one
+    fn active_producers(&self) -> impl Iterator<Item = &ServiceProducer<TS>> {
+        self.producer_factories
+            .iter()
+            .filter(|x| match (&x.profile, &self.profile) {
+                (None, _) => true,
+                (Some(matches), Some(active)) => matches(&**active),
+                (Some(_), None) => false,
+            })
+    }
+
+    // Returns the ServiceProducers of all alias_all registrations, based on the number of their sources
+    fn expand_alias_all_producers(
+        &self,
+        parent_factories: &[ServiceProducer<TS>],
+    ) -> Vec<ServiceProducer<TS>> {
+        let mut expanded = Vec::new();
+        let mut pending: Vec<_> = self.alias_all_producers.iter().collect();
+        while !pending.is_empty() {
+            // Sources, which are targets of other pending alias_all, have to be expanded afterwards
+            let targets: Vec<_> = pending.iter().map(|x| x.target).collect();
+            let (mut ready, mut blocked): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|x| !targets.contains(&x.source));
+            if ready.is_empty() {
+                // Cyclic aliases only map the instances registered so far
+                ready = core::mem::take(&mut blocked);
+            }
+            for alias in ready {
+                let count = parent_factories
+                    .iter()
+                    .chain(self.active_producers())
+                    .chain(expanded.iter())
+                    .filter(|f| f.identifier == alias.source)
+                    .count();
+                expanded.extend((0..count).map(|nth| (alias.expand)(nth, alias.creator)));
+            }
+            pending = blocked;
+        }
+        expanded
+    }
+
     fn validate_producers(
-        self,
-        mut factories: Vec<ServiceProducer<TS>>,
+        &self,
+        parent_factories: Vec<ServiceProducer<TS>>,
+        instrument: Option<&mut dyn FnMut(&'static str, Duration)>,
     ) -> Result<ProducerValidationResult<TS>, BuildError<TS>> {
-        let mut service_states_count: usize = 0;
-        factories.extend(self.producer_factories.into_iter());
+        let expanded = self.expand_alias_all_producers(&parent_factories);
+        let mut factories: Vec<_> = parent_factories
+            .iter()
+            .chain(self.active_producers())
+            .chain(expanded.iter())
+            .collect();
 
-        factories.sort_by_key(|a| a.identifier);
+        factories.sort_by_key(|a| a.order_key());
+        self.validate_ordered_producers(factories, instrument)
+    }
 
-        let mut final_ordered_types = factories.iter().map(|f| f.identifier).collect();
+    // Returns the producers validate_producers would validate in their order, so they can be prepared
+    // once for validations, which only differ in additional producers
+    pub(crate) fn ordered_producers(
+        &self,
+        parent_factories: Vec<ServiceProducer<TS>>,
+    ) -> Vec<ServiceProducer<TS>> {
+        let expanded = self.expand_alias_all_producers(&parent_factories);
+        let mut factories: Vec<_> = parent_factories
+            .into_iter()
+            .chain(self.active_producers().cloned())
+            .chain(expanded)
+            .collect();
+        factories.sort_by_key(ServiceProducer::order_key);
+        factories
+    }
 
-        let mut cyclic_reference_candidates = RHashMap::new();
+    // `factories` have to be sorted by `ServiceProducer::order_key`
+    pub(crate) fn validate_ordered_producers(
+        &self,
+        factories: Vec<&ServiceProducer<TS>>,
+        mut instrument: Option<&mut dyn FnMut(&'static str, Duration)>,
+    ) -> Result<ProducerValidationResult<TS>, BuildError<TS>> {
+        let mut state_names = RVec::new();
+        let final_ordered_types: RVec<_> = factories.iter().map(|f| f.identifier).collect();
+        let final_ordered_kinds: RVec<_> = factories.iter().map(|f| f.kind).collect();
+        let origins: RVec<_> = factories.iter().map(|f| f.origin).collect();
+        let position_index = resolvable::build_position_index::<TS>(&final_ordered_types);
+
+        let unique_ctx = PrecheckContext::<TS>::new(&final_ordered_types, &final_ordered_kinds)
+            .with_index(&position_index);
+        for check in self.key_checks.iter() {
+            check(&self.registered_keys)?;
+        }
+        for id in self.unique_types.iter() {
+            let positions = unique_ctx.positions(id);
+            if positions.len() > 1 {
+                return Err(BuildError::DuplicateUnique {
+                    id: *id,
+                    name: factories[positions.start].type_name,
+                });
+            }
+        }
+
+        let mut cyclic_reference_candidates = RHashMap::with_capacity(factories.len());
         let mut producers = RVec::with_capacity(factories.len());
         let mut types = RVec::with_capacity(factories.len());
+        let mut type_names = RVec::with_capacity(factories.len());
 
         for (i, x) in factories.into_iter().enumerate() {
             let mut ctx = UntypedFnFactoryContext {
-                state_counter: &mut service_states_count,
-                final_ordered_types: &mut final_ordered_types,
+                state_names: &mut state_names,
+                type_name: x.type_name,
+                precheck_context: PrecheckContext::new(&final_ordered_types, &final_ordered_kinds)
+                    .with_index(&position_index)
+                    .with_keys(&self.registered_keys)
+                    .with_current(i),
                 cyclic_reference_candidates: &mut cyclic_reference_candidates,
                 service_descriptor_pos: i,
+                teardown: x.teardown,
             };
 
-            let producer = match x.factory.call(&mut ctx) {
+            let start = instrument.is_some().then(Instant::now);
+            let producer = match x.call_factory(&mut ctx) {
                 ROk(x) => x,
                 RErr(e) => return Err(e.into()),
             };
+            if let (Some(instrument), Some(start)) = (instrument.as_mut(), start) {
+                instrument(x.type_name, start.elapsed());
+            }
             debug_assert_eq!(&x.identifier, producer.get_result_type_id());
             producers.push(producer);
             types.push(x.identifier);
+            type_names.push(x.type_name);
         }
 
+        // The checker consumes the candidates, so the dependencies are kept for resolve_subgraph beforehand
+        let dependencies: RVec<RVec<usize>> = (0..producers.len())
+            .map(|i| {
+                cyclic_reference_candidates
+                    .get(&i)
+                    .map(|x| x.dependencies.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
         CycleChecker(&mut cyclic_reference_candidates)
             .ok()
-            .map_err(|indices| BuildError::CyclicDependency {
-                description: indices
+            .map_err(|indices| {
+                let path: Vec<_> = indices
                     .into_iter()
-                    .skip(1)
                     .map(|i| {
                         cyclic_reference_candidates
                             .get(&i)
                             .unwrap()
                             .type_description
                     })
-                    .fold(
-                        cyclic_reference_candidates
-                            .values()
-                            .next()
-                            .unwrap()
-                            .type_description
-                            .to_string(),
-                        |acc, n| acc + " -> " + n,
-                    ),
+                    .collect();
+                BuildError::CyclicDependency { path }
             })?;
 
         Ok(ProducerValidationResult {
             producers,
             types,
-            service_states_count,
+            type_names,
+            kinds: final_ordered_kinds,
+            index: position_index,
+            shared_type_names: state_names,
+            dependencies,
+            origins,
+            boxers: self.boxers.clone(),
+            libraries: self.libraries.clone(),
         })
     }
 }
@@ -435,13 +2627,22 @@ impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
 pub(crate) struct ProducerValidationResult<TS: Strategy + 'static> {
     producers: RVec<UntypedFn<TS>>,
     types: RVec<TS::Id>,
-    service_states_count: usize,
+    type_names: RVec<&'static str>,
+    kinds: RVec<ServiceKind>,
+    index: resolvable::PositionIndex<TS>,
+    shared_type_names: RVec<&'static str>,
+    dependencies: RVec<RVec<usize>>,
+    origins: RVec<ServiceOrigin>,
+    boxers: Vec<service_provider::Boxer<TS>>,
+    libraries: Vec<LibraryHandle>,
 }
 
 struct CycleCheckerValue {
     is_visited: bool,
     type_description: &'static str,
-    iter: DynTrait<'static, RBox<()>, IteratorInterface<usize>>, // Use RVec
+    dependencies: RVec<usize>,
+    // Position in dependencies, which is visited next
+    next: usize,
 }
 
 struct CycleChecker<'a>(&'a mut RHashMap<usize, CycleCheckerValue>);
@@ -450,12 +2651,14 @@ impl<'a> CycleChecker<'a> {
     fn ok(self) -> Result<(), Vec<usize>> {
         let mut stack = Vec::new();
         let map = self.0;
+        // The iteration order of the map is random, so searching by position reports the same cycle on each build
+        let mut starts: Vec<_> = map.keys().copied().collect();
+        starts.sort_unstable();
 
-        loop {
-            let pos = match map.keys().next() {
-                Some(pos) => *pos,
-                _ => break,
-            };
+        for pos in starts {
+            if !map.contains_key(&pos) {
+                continue;
+            }
 
             stack.push(pos);
             while let Some(current) = stack.last() {
@@ -464,7 +2667,9 @@ impl<'a> CycleChecker<'a> {
                         return Err(stack);
                     }
                     value.is_visited = true;
-                    match value.iter.next() {
+                    let next = value.dependencies.get(value.next).copied();
+                    value.next += 1;
+                    match next {
                         Some(x) => {
                             stack.push(x);
                             continue;
@@ -492,28 +2697,107 @@ pub enum BuildError<TS: Strategy + Debug> {
     /// `name`-format is subject of change and should only be used for debugging purpose
     #[non_exhaustive]
     MissingDependency { id: TS::Id, name: &'static str },
-    /// `description`-format is subject of change and should only be used for debugging purpose
+    /// `path` contains the type names along the cycle, which starts and ends with the same type.
+    /// `Display` formats them on demand, e.g. `a -> b -> a`, which is subject of change
+    #[non_exhaustive]
+    CyclicDependency { path: Vec<&'static str> },
+    /// A service registered with `register_unique` was registered more than once
+    #[non_exhaustive]
+    DuplicateUnique { id: TS::Id, name: &'static str },
+    /// `key` was expected by `expect_keys`, but no keyed service `name` was registered for it.
+    /// `key` is formatted with `Debug`
     #[non_exhaustive]
-    CyclicDependency { description: String },
+    MissingKey { name: &'static str, key: String },
+    /// A `RegisteredArray` of `name` expected `expected` registrations, but `found` were registered
+    #[non_exhaustive]
+    CountMismatch {
+        id: TS::Id,
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// Validating the registration of `name` panicked, e.g. in `TypedKey::key` of a dependency.
+    /// Registrations are treated as `UnwindSafe` like `ServiceProvider::try_get` treats factories,
+    /// so state they modify before panicking might be left inconsistent
+    #[non_exhaustive]
+    FactoryPanicked { name: &'static str },
+    /// The environment variable `name` of `register_from_env` is missing or cannot be parsed.
+    /// `message` describes the reason
+    #[non_exhaustive]
+    EnvironmentVariable { name: &'static str, message: String },
 }
 
 // Internal, ABI-Safe representation
 #[repr(C)]
 enum InternalBuildError<TS: Strategy + Debug> {
-    MissingDependency { id: TS::Id, name: RStr<'static> },
-    CyclicDependency { description: RString },
+    MissingDependency {
+        id: TS::Id,
+        name: RStr<'static>,
+    },
+    CyclicDependency {
+        path: RVec<RStr<'static>>,
+    },
+    DuplicateUnique {
+        id: TS::Id,
+        name: RStr<'static>,
+    },
+    MissingKey {
+        name: RStr<'static>,
+        key: RString,
+    },
+    CountMismatch {
+        id: TS::Id,
+        name: RStr<'static>,
+        expected: usize,
+        found: usize,
+    },
+    FactoryPanicked {
+        name: RStr<'static>,
+    },
+    EnvironmentVariable {
+        name: RStr<'static>,
+        message: RString,
+    },
 }
 
 impl<TS: Strategy + Debug> From<InternalBuildError<TS>> for BuildError<TS> {
     fn from(i: InternalBuildError<TS>) -> Self {
         match i {
-            InternalBuildError::CyclicDependency { description } => BuildError::CyclicDependency {
-                description: description.into(),
+            InternalBuildError::CyclicDependency { path } => BuildError::CyclicDependency {
+                path: path.into_iter().map(Into::into).collect(),
             },
             InternalBuildError::MissingDependency { id, name } => BuildError::MissingDependency {
                 id,
                 name: name.into(),
             },
+            InternalBuildError::DuplicateUnique { id, name } => BuildError::DuplicateUnique {
+                id,
+                name: name.into(),
+            },
+            InternalBuildError::MissingKey { name, key } => BuildError::MissingKey {
+                name: name.into(),
+                key: key.into(),
+            },
+            InternalBuildError::CountMismatch {
+                id,
+                name,
+                expected,
+                found,
+            } => BuildError::CountMismatch {
+                id,
+                name: name.into(),
+                expected,
+                found,
+            },
+            InternalBuildError::FactoryPanicked { name } => {
+                BuildError::FactoryPanicked { name: name.into() }
+            }
+            InternalBuildError::EnvironmentVariable { name, message } => {
+                BuildError::EnvironmentVariable {
+                    name: name.into(),
+                    message: message.into(),
+                }
+            }
         }
     }
 }
@@ -521,13 +2805,113 @@ impl<TS: Strategy + Debug> From<InternalBuildError<TS>> for BuildError<TS> {
 impl<TS: Strategy + Debug> From<BuildError<TS>> for InternalBuildError<TS> {
     fn from(i: BuildError<TS>) -> Self {
         match i {
-            BuildError::CyclicDependency { description } => InternalBuildError::CyclicDependency {
-                description: description.into(),
+            BuildError::CyclicDependency { path } => InternalBuildError::CyclicDependency {
+                path: path.into_iter().map(Into::into).collect(),
             },
             BuildError::MissingDependency { id, name } => InternalBuildError::MissingDependency {
                 id,
                 name: name.into(),
             },
+            BuildError::DuplicateUnique { id, name } => InternalBuildError::DuplicateUnique {
+                id,
+                name: name.into(),
+            },
+            BuildError::MissingKey { name, key } => InternalBuildError::MissingKey {
+                name: name.into(),
+                key: key.into(),
+            },
+            BuildError::CountMismatch {
+                id,
+                name,
+                expected,
+                found,
+            } => InternalBuildError::CountMismatch {
+                id,
+                name: name.into(),
+                expected,
+                found,
+            },
+            BuildError::FactoryPanicked { name } => {
+                InternalBuildError::FactoryPanicked { name: name.into() }
+            }
+            BuildError::EnvironmentVariable { name, message } => {
+                InternalBuildError::EnvironmentVariable {
+                    name: name.into(),
+                    message: message.into(),
+                }
+            }
+        }
+    }
+}
+
+impl<TS: Strategy + Debug> BuildError<TS> {
+    /// Converts the error into the error of another strategy by mapping the service ids with `f`,
+    /// e.g. to report errors of a plugin's collection in terms of the host's strategy.
+    /// Errors without a service id are kept as they are.
+    pub fn map_id<TS2: Strategy + Debug>(self, f: impl Fn(TS::Id) -> TS2::Id) -> BuildError<TS2> {
+        match self {
+            BuildError::MissingDependency { id, name } => {
+                BuildError::MissingDependency { id: f(id), name }
+            }
+            BuildError::CyclicDependency { path } => BuildError::CyclicDependency { path },
+            BuildError::DuplicateUnique { id, name } => {
+                BuildError::DuplicateUnique { id: f(id), name }
+            }
+            BuildError::MissingKey { name, key } => BuildError::MissingKey { name, key },
+            BuildError::CountMismatch {
+                id,
+                name,
+                expected,
+                found,
+            } => BuildError::CountMismatch {
+                id: f(id),
+                name,
+                expected,
+                found,
+            },
+            BuildError::FactoryPanicked { name } => BuildError::FactoryPanicked { name },
+            BuildError::EnvironmentVariable { name, message } => {
+                BuildError::EnvironmentVariable { name, message }
+            }
+        }
+    }
+}
+
+impl<TS: Strategy + Debug> Display for BuildError<TS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingDependency { name, .. } => write!(f, "Missing dependency {name}"),
+            BuildError::CyclicDependency { path } => {
+                f.write_str("Cyclic dependency ")?;
+                for (i, name) in path.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" -> ")?;
+                    }
+                    f.write_str(name)?;
+                }
+                Ok(())
+            }
+            BuildError::DuplicateUnique { name, .. } => {
+                write!(f, "Unique service {name} is registered more than once")
+            }
+            BuildError::MissingKey { name, key } => {
+                write!(f, "Missing key {key} of keyed service {name}")
+            }
+            BuildError::CountMismatch {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "Expected {expected} services of {name}, but found {found}"
+            ),
+            BuildError::FactoryPanicked { name } => {
+                write!(f, "Validating the registration of {name} panicked")
+            }
+            BuildError::EnvironmentVariable { name, message } => {
+                write!(f, "Environment variable {name}: {message}")
+            }
         }
     }
 }
@@ -541,6 +2925,52 @@ impl<TS: Strategy + 'static> BuildError<TS> {
     }
 }
 
+/// Possible errors when calling ServiceProvider::try_get()
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResolutionError {
+    /// No service is registered for `name`
+    #[non_exhaustive]
+    MissingService { name: &'static str },
+    /// The factory of `name` or of one of its dependencies panicked.
+    /// `message` contains the panic payload, if it was a string
+    #[non_exhaustive]
+    Panicked { name: &'static str, message: String },
+}
+
+impl ResolutionError {
+    fn panicked<T>(payload: Box<dyn Any + Send>) -> Self {
+        ResolutionError::Panicked {
+            name: type_name::<T>(),
+            message: payload
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// Stage 2 data of services registered by `ServiceBuilder::register_transient_with`
+struct TransientContext<T, TDep: Resolvable<TS>, TS: Strategy + 'static> {
+    key: TDep::PrecheckResult,
+    creator: AnyPtr,
+    // Position of the service within the built ServiceProvider
+    position: usize,
+    create: fn(&ServiceProvider<TS>, *const AutoFreePointer) -> T,
+}
+
+impl<T, TDep: Resolvable<TS>, TS: Strategy + 'static> TransientContext<T, TDep, TS> {
+    /// ctx must be the stage 2 data of a service registered by `register_transient_with::<T>` of ServiceBuilder<TDep>
+    unsafe fn from_erased<'a>(ctx: *const AutoFreePointer) -> &'a Self {
+        unsafe { FactoryContext::<Self>::from_erased(&*ctx) }.get()
+    }
+
+    fn resolve<'a>(&self, provider: &'a ServiceProvider<TS>) -> TDep::ItemPreChecked<'a> {
+        TDep::resolve_prechecked(provider, &self.key)
+    }
+}
+
 #[doc(hidden)]
 pub struct ServiceBuilder<'col, T: Resolvable<TS>, TS: Strategy + 'static = AnyStrategy>(
     pub &'col mut GenericServiceCollection<TS>,
@@ -548,29 +2978,169 @@ pub struct ServiceBuilder<'col, T: Resolvable<TS>, TS: Strategy + 'static = AnyS
 );
 
 impl<'col, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static> ServiceBuilder<'col, TDep, TS> {
+    #[cfg_attr(feature = "log", track_caller)]
     pub fn register<T: Identifyable<TS::Id>>(
         &mut self,
-        creator: fn(TDep::ItemPreChecked) -> T,
-    ) -> AliasBuilder<T, TS> {
-        type InnerContext<TDep, TS> = (<TDep as SealedResolvable<TS>>::PrecheckResult, AnyPtr);
-        extern "C" fn factory<
+        creator: fn(TDep::ItemPreChecked<'_>) -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        log_registration!("Register {} depending on {}", T, TDep);
+        fn create<T, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> T {
+            let ctx = unsafe { TransientContext::<T, TDep, TS>::from_erased(ctx) };
+            let creator: fn(TDep::ItemPreChecked<'_>) -> T =
+                unsafe { core::mem::transmute(ctx.creator) };
+            creator(ctx.resolve(provider))
+        }
+        self.register_transient_with(creator as AnyPtr, create::<T, TDep, TS>)
+    }
+
+    /// Same as `register`, but additionally passes the position of this service within the built
+    /// ServiceProvider to `creator`. The index is unique across all services of a ServiceProvider and
+    /// stable for its entire lifetime, so it can be used as key for dispatch tables.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// struct Route { index: usize, path: &'static str }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<()>().register_indexed(|index, ()| Route { index, path: "/a" });
+    /// collection.with::<()>().register_indexed(|index, ()| Route { index, path: "/b" });
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let indices: Vec<_> = provider.get_all::<Route>().map(|r| r.index).collect();
+    /// assert_ne!(indices[0], indices[1]);
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_indexed<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn(usize, TDep::ItemPreChecked<'_>) -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        log_registration!("Register indexed {} depending on {}", T, TDep);
+        fn create<T, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> T {
+            let ctx = unsafe { TransientContext::<T, TDep, TS>::from_erased(ctx) };
+            let creator: fn(usize, TDep::ItemPreChecked<'_>) -> T =
+                unsafe { core::mem::transmute(ctx.creator) };
+            creator(ctx.position, ctx.resolve(provider))
+        }
+        self.register_transient_with(creator as AnyPtr, create::<T, TDep, TS>)
+    }
+
+    /// Same as `register`, but additionally passes the resolving ServiceProvider to `creator`.
+    /// This allows to lazily resolve rarely used services without declaring a `WeakServiceProvider` dependency.
+    /// Services resolved via the provider are not part of the cycle detection at build time.
+    /// ```
+    /// use minfac::{Registered, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// collection.register(|| 2u16);
+    /// collection
+    ///     .with::<Registered<u8>>()
+    ///     .register_with_provider(|provider, byte| match byte {
+    ///         0 => 0u32,
+    ///         _ => provider.get::<u16>().expect("Registered above") as u32,
+    ///     });
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// assert_eq!(Some(2), provider.get::<u32>());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_with_provider<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: fn(&ServiceProvider<TS>, TDep::ItemPreChecked<'_>) -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        log_registration!("Register {} with provider depending on {}", T, TDep);
+        fn create<T, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> T {
+            let ctx = unsafe { TransientContext::<T, TDep, TS>::from_erased(ctx) };
+            let creator: fn(&ServiceProvider<TS>, TDep::ItemPreChecked<'_>) -> T =
+                unsafe { core::mem::transmute(ctx.creator) };
+            creator(provider, ctx.resolve(provider))
+        }
+        self.register_transient_with(creator as AnyPtr, create::<T, TDep, TS>)
+    }
+
+    /// Registers a `Factory<Args, T>`, which passes its dependencies together with the arguments of
+    /// `Factory::create` to `creator`, e.g. for services requiring parameters only known at runtime.
+    /// ```
+    /// use minfac::{Factory, Registered, ServiceCollection};
+    ///
+    /// struct Widget { offset: u8, label: &'static str }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 10u8);
+    /// collection
+    ///     .with::<Registered<u8>>()
+    ///     .register_factory_fn(|offset, label| Widget { offset, label });
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let factory = provider.get::<Factory<&'static str, Widget>>().unwrap();
+    /// let widget = factory.create("ok");
+    /// assert_eq!((10, "ok"), (widget.offset, widget.label));
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_factory_fn<Args: 'static, T: 'static>(
+        &mut self,
+        creator: fn(TDep::ItemPreChecked<'_>, Args) -> T,
+    ) -> AliasBuilder<'_, Factory<Args, T, TS>, TS>
+    where
+        Factory<Args, T, TS>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register factory of {} depending on {}", T, TDep);
+        fn create<Args, T, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static>(
+            provider: &ServiceProvider<TS>,
+            ctx: *const AutoFreePointer,
+        ) -> Factory<Args, T, TS> {
+            fn create_with_args<Args, T, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static>(
+                provider: &ServiceProvider<TS>,
+                ctx: &AutoFreePointer,
+                args: Args,
+            ) -> T {
+                let ctx =
+                    unsafe { TransientContext::<Factory<Args, T, TS>, TDep, TS>::from_erased(ctx) };
+                let creator: fn(TDep::ItemPreChecked<'_>, Args) -> T =
+                    unsafe { core::mem::transmute(ctx.creator) };
+                creator(ctx.resolve(provider), args)
+            }
+            unsafe { Factory::new(provider.into(), ctx, create_with_args::<Args, T, TDep, TS>) }
+        }
+        self.register_transient_with(creator as AnyPtr, create::<Args, T, TDep, TS>)
+    }
+
+    // `create` is called with the TransientContext of the service to create it
+    fn register_transient_with<T: Identifyable<TS::Id>>(
+        &mut self,
+        creator: AnyPtr,
+        create: fn(&ServiceProvider<TS>, *const AutoFreePointer) -> T,
+    ) -> AliasBuilder<'_, T, TS> {
+        type OuterContext<T, TS> = (
+            AnyPtr,
+            fn(&ServiceProvider<TS>, *const AutoFreePointer) -> T,
+        );
+        extern "C-unwind" fn factory<
             T: Identifyable<TS::Id>,
             TDep: Resolvable<TS> + 'static,
             TS: Strategy + 'static,
         >(
-            outer_ctx: AutoFreePointer, // No-Alloc
+            outer_ctx: &AutoFreePointer,
             ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS> {
-            let key = match TDep::precheck(ctx.final_ordered_types) {
+            let key = match TDep::precheck(&ctx.precheck_context) {
                 Ok(x) => x,
                 Err(x) => return RErr(x.into()),
             };
-            let data = TDep::iter_positions(ctx.final_ordered_types);
+            let data = TDep::iter_positions(&ctx.precheck_context);
             ctx.register_cyclic_reference_candidate(
-                type_name::<TDep::ItemPreChecked>(),
+                type_name::<TDep::ItemPreChecked<'static>>(),
                 DynTrait::from_value(data),
             );
-            extern "C" fn func<
+            extern "C-unwind" fn func<
                 T: Identifyable<TS::Id>,
                 TDep: Resolvable<TS> + 'static,
                 TS: Strategy + 'static,
@@ -579,30 +3149,123 @@ impl<'col, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static> ServiceBuilde
                 outer_ctx: *const AutoFreePointer,
             ) -> T {
                 let provider = unsafe { &*provider as &ServiceProvider<TS> };
-                let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
-                let (key, c): &InnerContext<TDep, TS> =
-                    unsafe { &*(outer_ctx.get_pointer() as *mut InnerContext<TDep, TS>) };
-                let creator: fn(TDep::ItemPreChecked) -> T = unsafe { std::mem::transmute(*c) };
-                let arg = TDep::resolve_prechecked(provider, key);
-                creator(arg)
+                let create =
+                    unsafe { TransientContext::<T, TDep, TS>::from_erased(outer_ctx) }.create;
+                create(provider, outer_ctx)
             }
-            let inner: InnerContext<TDep, TS> = (key, outer_ctx.get_pointer());
+            let (creator, create) =
+                *unsafe { FactoryContext::<OuterContext<T, TS>>::from_erased(outer_ctx) }.get();
+            let inner = TransientContext::<T, TDep, TS> {
+                key,
+                creator,
+                position: ctx.service_descriptor_pos,
+                create,
+            };
             ROk(UntypedFn::create(
                 func::<T, TDep, TS>,
-                AutoFreePointer::boxed(inner),
+                FactoryContext::new(inner).into_erased(),
             ))
         }
-        let factory = UntypedFnFactory::no_alloc(creator as AnyPtr, factory::<T, TDep, TS>);
+        let outer: OuterContext<T, TS> = (creator, create);
+        let factory = UntypedFnFactory::boxed(outer, factory::<T, TDep, TS>);
         self.0
             .producer_factories
-            .push(ServiceProducer::<TS>::new::<T>(factory));
+            .push(ServiceProducer::<TS>::new::<T>(factory).with_dependencies::<TDep>());
 
         AliasBuilder::new(self.0)
     }
+
+    #[cfg_attr(feature = "log", track_caller)]
     pub fn register_shared<T: Send + Sync>(
         &mut self,
-        creator: fn(TDep::ItemPreChecked) -> Arc<T>,
-    ) -> AliasBuilder<Arc<T>, TS>
+        creator: fn(TDep::ItemPreChecked<'_>) -> Arc<T>,
+    ) -> SharedBuilder<'_, T, TS>
+    where
+        Arc<T>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register shared {} depending on {}", Arc<T>, TDep);
+        self.register_shared_with::<T, Arc<T>, blocking::Immediate>(creator as AnyPtr)
+    }
+
+    /// Registers a shared service, which is created asynchronously. The first `get` drives the future
+    /// to completion on the current thread and caches its output like `register_shared` does.
+    /// Must not be called from within a thread of an async runtime: If the future waits for something
+    /// that runtime has to drive, the blocked thread deadlocks.
+    /// ```
+    /// use {minfac::{Registered, ServiceCollection}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 20i64);
+    /// collection
+    ///     .with::<Registered<i64>>()
+    ///     .register_shared_blocking(|base| async move { Arc::new(base as i32 + 22) });
+    /// let provider = collection.build().expect("Dependencies are registered");
+    /// assert_eq!(42, *provider.get::<Arc<i32>>().unwrap());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_shared_blocking<T: Send + Sync, F: Future<Output = Arc<T>>>(
+        &mut self,
+        creator: fn(TDep::ItemPreChecked<'_>) -> F,
+    ) -> SharedBuilder<'_, T, TS>
+    where
+        Arc<T>: Identifyable<TS::Id>,
+    {
+        log_registration!("Register shared {} depending on {}", Arc<T>, TDep);
+        self.register_shared_with::<T, F, blocking::BlockOn>(creator as AnyPtr)
+    }
+
+    /// Registers a shared service, which is constructed on a background thread, so callers don't have
+    /// to wait for expensive services like a large index. The creator resolves the dependencies on the
+    /// calling thread and returns a closure, which constructs the service on a new thread.
+    /// Resolving `Arc<Background<T>>` starts the thread, e.g. right after building the provider.
+    /// Resolving `Arc<T>` starts it if necessary and blocks until the service is constructed.
+    /// The returned builder belongs to `Arc<Background<T>>`.
+    /// ```
+    /// use {minfac::{Background, Registered, ServiceCollection}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1000u32);
+    /// collection
+    ///     .with::<Registered<u32>>()
+    ///     .register_shared_background(|size| move || Arc::new((0..size).collect::<Vec<_>>()));
+    /// let provider = collection.build().expect("Dependencies are registered");
+    ///
+    /// let index = provider.get::<Arc<Background<Vec<u32>>>>().unwrap();
+    /// // Unrelated work, while the index is built
+    /// assert_eq!(1000, provider.get::<Arc<Vec<u32>>>().unwrap().len());
+    /// assert!(index.is_ready());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_shared_background<
+        T: Send + Sync + 'static,
+        F: FnOnce() -> Arc<T> + Send + 'static,
+    >(
+        &mut self,
+        creator: fn(TDep::ItemPreChecked<'_>) -> F,
+    ) -> SharedBuilder<'_, Background<T>, TS>
+    where
+        Arc<T>: Identifyable<TS::Id>,
+        Arc<Background<T>>: Identifyable<TS::Id>,
+    {
+        fn wait<T>(background: Arc<Background<T>>) -> Arc<T> {
+            background.wait()
+        }
+        self.0
+            .with::<Registered<Arc<Background<T>>>>()
+            .register_shared(wait::<T>);
+        log_registration!(
+            "Register shared {} depending on {}",
+            Arc<Background<T>>,
+            TDep
+        );
+        self.register_shared_with::<Background<T>, F, blocking::Spawn>(creator as AnyPtr)
+    }
+
+    // `creator` is a `fn(TDep::ItemPreChecked<'_>) -> R`, whose output is turned into the service by `M`
+    fn register_shared_with<T: Send + Sync, R, M: blocking::Completion<R, Output = Arc<T>>>(
+        &mut self,
+        creator: AnyPtr,
+    ) -> SharedBuilder<'_, T, TS>
     where
         Arc<T>: Identifyable<TS::Id>,
     {
@@ -610,58 +3273,99 @@ impl<'col, TDep: Resolvable<TS> + 'static, TS: Strategy + 'static> ServiceBuilde
             <TDep as SealedResolvable<TS>>::PrecheckResult,
             AnyPtr,
             usize,
+            SharedServiceTeardown,
         );
-        extern "C" fn factory<
+        extern "C-unwind" fn factory<
             T: Send + Sync,
+            R,
+            M: blocking::Completion<R, Output = Arc<T>>,
             TDep: Resolvable<TS> + 'static,
             TS: Strategy + 'static,
         >(
-            outer_ctx: AutoFreePointer,
+            outer_ctx: &AutoFreePointer,
             ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS>
         where
             Arc<T>: Identifyable<TS::Id>,
         {
-            let service_state_idx = ctx.reserve_state_space();
-            let key = match TDep::precheck(ctx.final_ordered_types) {
+            let key = match TDep::precheck(&ctx.precheck_context) {
                 Ok(x) => x,
                 Err(x) => return RErr(x.into()),
             };
-            let data = TDep::iter_positions(ctx.final_ordered_types);
+            // Reserved after precheck, so failing prechecks don't occupy a state slot
+            let service_state_idx = ctx.reserve_state_space();
+            let data = TDep::iter_positions(&ctx.precheck_context);
             ctx.register_cyclic_reference_candidate(
-                type_name::<TDep::ItemPreChecked>(),
+                type_name::<TDep::ItemPreChecked<'static>>(),
                 DynTrait::from_value(data),
             );
-            extern "C" fn func<
+            #[allow(improper_ctypes_definitions)]
+            extern "C-unwind" fn func<
                 T: Send + Sync + 'static,
+                R,
+                M: blocking::Completion<R, Output = Arc<T>>,
                 TDep: Resolvable<TS> + 'static,
                 TS: Strategy + 'static,
             >(
                 provider: *const ServiceProvider<TS>,
                 outer_ctx: *const AutoFreePointer,
             ) -> Arc<T> {
+                unsafe { get_shared::<T, R, M, TDep, TS>(provider, outer_ctx).clone_inner::<T>() }
+            }
+            extern "C-unwind" fn shared_pointer<
+                T: Send + Sync + 'static,
+                R,
+                M: blocking::Completion<R, Output = Arc<T>>,
+                TDep: Resolvable<TS> + 'static,
+                TS: Strategy + 'static,
+            >(
+                provider: *const ServiceProvider<TS>,
+                outer_ctx: *const AutoFreePointer,
+            ) -> *const ArcAutoFreePointer {
+                get_shared::<T, R, M, TDep, TS>(provider, outer_ctx)
+            }
+            fn get_shared<
+                'a,
+                T: Send + Sync + 'static,
+                R,
+                M: blocking::Completion<R, Output = Arc<T>>,
+                TDep: Resolvable<TS> + 'static,
+                TS: Strategy + 'static,
+            >(
+                provider: *const ServiceProvider<TS>,
+                outer_ctx: *const AutoFreePointer,
+            ) -> &'a ArcAutoFreePointer {
                 let provider = unsafe { &*provider as &ServiceProvider<TS> };
                 let outer_ctx = unsafe { &*outer_ctx as &AutoFreePointer };
-                let (key, c, service_state_idx): &InnerContext<TDep, TS> =
-                    unsafe { &*(outer_ctx.get_pointer() as *mut InnerContext<TDep, TS>) };
-                provider.get_or_initialize_pos(*service_state_idx, || {
-                    let creator: fn(TDep::ItemPreChecked) -> Arc<T> =
+                let (key, c, service_state_idx, teardown): &InnerContext<TDep, TS> =
+                    unsafe { FactoryContext::<InnerContext<TDep, TS>>::from_erased(outer_ctx) }
+                        .get();
+                provider.get_or_initialize_pos(*service_state_idx, *teardown, || {
+                    let creator: fn(TDep::ItemPreChecked<'_>) -> R =
                         unsafe { std::mem::transmute(*c) };
-                    creator(TDep::resolve_prechecked(provider, key))
+                    M::complete(creator(TDep::resolve_prechecked(provider, key)))
                 })
             }
-            let inner: InnerContext<TDep, TS> = (key, outer_ctx.get_pointer(), service_state_idx);
+            let inner: InnerContext<TDep, TS> = (
+                key,
+                outer_ctx.get_pointer(),
+                service_state_idx,
+                ctx.teardown,
+            );
             ROk(UntypedFn::create(
-                func::<T, TDep, TS>,
-                AutoFreePointer::boxed(inner),
-            ))
+                func::<T, R, M, TDep, TS>,
+                FactoryContext::new(inner).into_erased(),
+            )
+            .with_shared_pointer(shared_pointer::<T, R, M, TDep, TS>))
         }
-        let factory = UntypedFnFactory::no_alloc(creator as AnyPtr, factory::<T, TDep, TS>);
-        self.0
-            .producer_factories
-            .push(ServiceProducer::<TS>::new::<Arc<T>>(factory));
+        let factory = UntypedFnFactory::no_alloc(creator, factory::<T, R, M, TDep, TS>);
+        self.0.producer_factories.push(
+            ServiceProducer::<TS>::new::<Arc<T>>(factory)
+                .with_dependencies::<TDep>()
+                .with_kind(ServiceKind::Shared),
+        );
 
-        AliasBuilder::new(self.0)
+        SharedBuilder::new(self.0)
     }
 }
 
@@ -2,6 +2,7 @@ use core::fmt::Debug;
 use core::fmt::Formatter;
 use std::fmt::Write;
 
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
 pub extern "C-unwind" fn default_error_handler(error: &LifetimeError) {
     #[cfg(feature = "std")]
     if !std::thread::panicking() {
@@ -18,6 +19,11 @@ impl LifetimeError {
     pub(crate) fn new(error: OutlivedLifetimeErrorVariants) -> Self {
         Self(error)
     }
+
+    #[cfg(all(feature = "std", any(debug_assertions, feature = "runtime_checks")))]
+    pub(crate) fn variant(&self) -> &OutlivedLifetimeErrorVariants {
+        &self.0
+    }
 }
 
 impl Debug for LifetimeError {
@@ -66,10 +72,16 @@ impl FromIterator<DanglingCheckerResult> for DanglingCheckerResults {
     }
 }
 
+impl DanglingCheckerResults {
+    pub(crate) fn as_slice(&self) -> &[DanglingCheckerResult] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
 impl Debug for DanglingCheckerResults {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.write_char('[')?;
-        let mut data = unsafe { std::slice::from_raw_parts(self.ptr, self.len) }.into_iter();
+        let mut data = self.as_slice().iter();
 
         if let Some(next) = data.next() {
             f.write_fmt(format_args!("{:?}", next))?;
@@ -104,15 +116,28 @@ impl DanglingCheckerResult {
             typename_len: typename.len(),
         }
     }
+
+    #[cfg(all(feature = "std", any(debug_assertions, feature = "runtime_checks")))]
+    pub(crate) fn remaining_references(&self) -> usize {
+        self.remaining_references
+    }
+
+    pub(crate) fn typename(&self) -> &'static str {
+        unsafe {
+            let slice = std::slice::from_raw_parts(self.typename_ptr, self.typename_len);
+            std::str::from_utf8_unchecked(slice)
+        }
+    }
 }
 
 impl Debug for DanglingCheckerResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        let typename = unsafe {
-            let slice = std::slice::from_raw_parts(self.typename_ptr, self.typename_len);
-            std::str::from_utf8_unchecked(slice)
-        };
-        write!(f, "{} (remaining {})", typename, self.remaining_references)
+        write!(
+            f,
+            "{} (remaining {})",
+            self.typename(),
+            self.remaining_references
+        )
     }
 }
 
@@ -135,7 +160,6 @@ mod tests {
             DanglingCheckerResult::new(42, "foo::baz"),
         ]
         .into_iter()
-        .into_iter()
         .collect();
         assert_eq!(
             "Some instances outlived their ServiceProvider: [foo::bar (remaining 5), foo::baz (remaining 42)]",
@@ -145,5 +169,5 @@ mod tests {
 
     #[deny(improper_ctypes_definitions)]
     #[allow(dead_code)]
-    pub extern "C" fn assert_stable_abi(_i: LifetimeError) {}
+    pub extern "C-unwind" fn assert_stable_abi(_i: LifetimeError) {}
 }
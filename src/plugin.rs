@@ -0,0 +1,158 @@
+use crate::{stable_abi::StableAbiStrategy, BuildError, GenericServiceCollection, ServiceProvider};
+use alloc::{string::String, sync::Arc};
+use core::fmt::{self, Display, Formatter};
+use libloading::{Library, Symbol};
+use std::ffi::OsStr;
+
+/// Signature of the symbol, which is called by `load_plugin` to register the services of a plugin
+pub type PluginRegistrar = extern "C" fn(&mut GenericServiceCollection<StableAbiStrategy>);
+
+/// Possible errors when calling GenericServiceCollection::load_plugin()
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum PluginError {
+    /// The dynamic library couldn't be loaded
+    Load(libloading::Error),
+    /// The dynamic library doesn't export `symbol`
+    MissingSymbol {
+        symbol: String,
+        source: libloading::Error,
+    },
+}
+
+impl Display for PluginError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load(e) => write!(f, "Failed to load plugin: {e}"),
+            PluginError::MissingSymbol { symbol, source } => {
+                write!(f, "Plugin doesn't export '{symbol}': {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PluginError::Load(e) | PluginError::MissingSymbol { source: e, .. } => Some(e),
+        }
+    }
+}
+
+impl GenericServiceCollection<StableAbiStrategy> {
+    /// Loads the dynamic library at `path` and calls its `symbol` to register services.
+    /// The library stays loaded until all ServiceProviders built from this collection are dropped,
+    /// because producers and shared services contain code of the library.
+    ///
+    /// ```no_run
+    /// use minfac::{GenericServiceCollection, stable_abi::StableAbiStrategy};
+    ///
+    /// let mut collection = GenericServiceCollection::<StableAbiStrategy>::new();
+    /// unsafe { collection.load_plugin("target/debug/libplugin.so", "register") }
+    ///     .expect("Plugin should be loadable");
+    /// let provider = collection.build().expect("Plugin dependencies should be registered");
+    /// ```
+    ///
+    /// # Safety
+    /// Loading a library runs its initialization routines. `symbol` must be a `PluginRegistrar`,
+    /// which is compiled against the same version of minfac.
+    pub unsafe fn load_plugin(
+        &mut self,
+        path: impl AsRef<OsStr>,
+        symbol: &str,
+    ) -> Result<(), PluginError> {
+        let library = Library::new(path).map_err(PluginError::Load)?;
+        let registrar: Symbol<PluginRegistrar> =
+            library
+                .get(symbol.as_bytes())
+                .map_err(|source| PluginError::MissingSymbol {
+                    symbol: symbol.into(),
+                    source,
+                })?;
+        let registrar = *registrar;
+        registrar(self);
+        self.libraries.push(Arc::new(library));
+        Ok(())
+    }
+}
+
+/// Loads plugins into a collection and builds a ServiceProvider, which owns all loaded libraries.
+/// The libraries are unloaded only after the ServiceProvider and all of its shared services are dropped,
+/// so no `Library` has to be kept alive manually.
+///
+/// ```no_run
+/// use minfac::PluginHost;
+///
+/// let mut host = PluginHost::new();
+/// unsafe { host.load("target/debug/libplugin.so") }.expect("Plugin should be loadable");
+/// let provider = host.build().expect("Plugin dependencies should be registered");
+/// ```
+#[derive(Default)]
+pub struct PluginHost {
+    collection: GenericServiceCollection<StableAbiStrategy>,
+}
+
+impl PluginHost {
+    /// Symbol, which `load` calls to register the services of a plugin
+    pub const REGISTRAR: &'static str = "register";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads plugins in addition to the services already registered in `collection`
+    pub fn with_collection(collection: GenericServiceCollection<StableAbiStrategy>) -> Self {
+        Self { collection }
+    }
+
+    /// Loads the plugin at `path` by calling its `PluginHost::REGISTRAR` symbol
+    ///
+    /// # Safety
+    /// Same as for `GenericServiceCollection::load_plugin`
+    pub unsafe fn load(&mut self, path: impl AsRef<OsStr>) -> Result<&mut Self, PluginError> {
+        self.collection.load_plugin(path, Self::REGISTRAR)?;
+        Ok(self)
+    }
+
+    pub fn build(
+        self,
+    ) -> Result<ServiceProvider<StableAbiStrategy>, BuildError<StableAbiStrategy>> {
+        self.collection.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_missing_library_fails() {
+        let mut collection = GenericServiceCollection::<StableAbiStrategy>::new();
+        let result = unsafe { collection.load_plugin("./not_existing_plugin.so", "register") };
+        assert!(matches!(result, Err(PluginError::Load(_))), "{result:?}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn plugin_host_reports_missing_registrar() {
+        let mut host = PluginHost::new();
+        match unsafe { host.load("libc.so.6") } {
+            Err(PluginError::MissingSymbol { symbol, .. }) => {
+                assert_eq!(PluginHost::REGISTRAR, symbol)
+            }
+            e => panic!("Expected MissingSymbol, got {:?}", e.map(|_| ())),
+        }
+        assert!(host.build().is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn load_library_without_registrar_fails() {
+        let mut collection = GenericServiceCollection::<StableAbiStrategy>::new();
+        let result = unsafe { collection.load_plugin("libc.so.6", "minfac_register") };
+        match result {
+            Err(PluginError::MissingSymbol { symbol, .. }) => assert_eq!("minfac_register", symbol),
+            e => panic!("Expected MissingSymbol, got {e:?}"),
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use super::*;
 use crate::{
-    service_provider::{ServiceProvider, WeakServiceProvider},
+    service_provider::{LazyService, ScopeLocalSlot, ServiceProvider, WeakServiceProvider},
     strategy::{Identifyable, Strategy},
 };
+use alloc::sync::Weak;
 use core::{
     iter::{empty, once, Chain, Empty, Once},
     ops::Range,
@@ -16,51 +17,166 @@ pub trait Resolvable<TS: Strategy + 'static = AnyStrategy>: SealedResolvable<TS>
 pub trait SealedResolvable<TS: Strategy + 'static> {
     /// Used if it's uncertain, wether a type is initializable, e.g.
     /// - Option<i32> for provider.get<Singleton<i32>>()
-    type Item;
+    type Item<'a>;
     /// If a resolvable is used as a dependency for another service, ServiceCollection::build() ensures
     /// that the dependency can be initialized. So it's currently used:
     /// - provider.get<SingletonServices<i32>>() -> EmptyIterator if nothing is registered
     /// - collection.with::<Singleton<i32>>().register_singleton(|_prechecked_i32: i32| {})
-    type ItemPreChecked;
+    ///
+    /// The lifetime allows to borrow from the ServiceProvider, e.g. Shared<T> resolves to &'a T
+    type ItemPreChecked<'a>;
 
     type PrecheckResult;
     type TypeIdsIter: Iterator<Item = usize> + 'static + Send + Sync;
 
     /// Resolves a type with the specified provider. There might be multiple calls to this method with
     /// parent ServiceProviders. It will therefore not necessarily be an alias for provider.get() in the future.
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item;
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_>;
 
     /// Called internally when resolving dependencies.
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         key: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked;
+    ) -> Self::ItemPreChecked<'a>;
 
-    fn precheck(ordered_types: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>>;
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>>;
 
     // Iterates all positions involved in resolving the type. This is required for checking
     // missing or cyclic dependencies
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter;
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter;
+}
+
+/// Describes all services in the order they are stored within a ServiceProvider.
+/// Resolvables use it to find the positions of their dependencies when building a ServiceProvider
+pub struct PrecheckContext<'a, TS: Strategy + 'static> {
+    types: &'a [TS::Id],
+    kinds: &'a [ServiceKind],
+    index: Option<&'a PositionIndex<TS>>,
+    // Keys of keyed services registered in the collection being built. Empty for built ServiceProviders
+    keys: &'a [(TS::Id, Arc<dyn Any + Send + Sync>)],
+    // Position of the service whose dependencies are prechecked. None for built ServiceProviders
+    current: Option<usize>,
+}
+
+/// Range of positions for each distinct id of the ordered types. It's built once per build, so prechecks
+/// only have to search the distinct ids instead of all services. It's not a HashMap, because `Strategy::Id`
+/// isn't required to implement `Hash`.
+pub(crate) type PositionIndex<TS> = RVec<(<TS as Strategy>::Id, usize, usize)>;
+
+pub(crate) fn build_position_index<TS: Strategy + 'static>(types: &[TS::Id]) -> PositionIndex<TS> {
+    let mut index = PositionIndex::<TS>::new();
+    for (pos, id) in types.iter().enumerate() {
+        match index.last_mut() {
+            Some((last, _, to)) if last == id => *to = pos + 1,
+            _ => index.push((*id, pos, pos + 1)),
+        }
+    }
+    index
+}
+
+impl<'a, TS: Strategy + 'static> PrecheckContext<'a, TS> {
+    pub(crate) fn new(types: &'a [TS::Id], kinds: &'a [ServiceKind]) -> Self {
+        debug_assert_eq!(types.len(), kinds.len());
+        Self {
+            types,
+            kinds,
+            index: None,
+            keys: &[],
+            current: None,
+        }
+    }
+
+    /// The index has to be built from the same types
+    pub(crate) fn with_index(mut self, index: &'a PositionIndex<TS>) -> Self {
+        debug_assert_eq!(self.types.len(), index.last().map_or(0, |(_, _, to)| *to));
+        self.index = Some(index);
+        self
+    }
+
+    pub(crate) fn with_keys(mut self, keys: &'a [(TS::Id, Arc<dyn Any + Send + Sync>)]) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub(crate) fn with_current(mut self, position: usize) -> Self {
+        self.current = Some(position);
+        self
+    }
+
+    /// Position of the `T` registered right before the current service, if the current service is a `T` itself
+    fn previous_position<T: Identifyable<TS::Id>>(&self) -> Option<usize> {
+        let current = self.current?;
+        let positions = self.positions(&T::get_id());
+        (positions.start < current && current < positions.end).then(|| current - 1)
+    }
+
+    /// Position of the last `KeyedService<K, T>` registered with `key` in the collection being built
+    fn keyed_position<K: PartialEq + 'static, T: 'static>(&self, key: &K) -> Option<usize>
+    where
+        KeyedService<K, T>: Identifyable<TS::Id>,
+        ServiceKey<K, T>: Identifyable<TS::Id>,
+    {
+        let id = ServiceKey::<K, T>::get_id();
+        let keys = self
+            .keys
+            .iter()
+            .filter(|(key_id, _)| *key_id == id)
+            .filter_map(|(_, registered)| registered.downcast_ref::<K>());
+        let count = keys.clone().count();
+        let (nth, _) = keys
+            .enumerate()
+            .filter(|(_, registered)| *registered == key)
+            .last()?;
+        // Keys of this collection belong to the last services, after the ones inherited from parents
+        Some(self.positions(&KeyedService::<K, T>::get_id()).end - count + nth)
+    }
+
+    /// Positions of all services with `id`, which is empty if there are none
+    pub(crate) fn positions(&self, id: &TS::Id) -> Range<usize> {
+        if let Some(index) = self.index {
+            return binary_search::binary_search_first_by_key(index, id, |(id, _, _)| id)
+                .map_or(0..0, |pos| index[pos].1..index[pos].2);
+        }
+        match binary_search::binary_search_first_by_key(self.types, id, |f| f) {
+            Some(x) => {
+                let to = binary_search::binary_search_last_by_key(&self.types[x..], id, |f| f)
+                    .unwrap()
+                    + x
+                    + 1;
+                x..to
+            }
+            None => 0..0,
+        }
+    }
+
+    /// Ordered ids of all services
+    pub fn types(&self) -> &'a [TS::Id] {
+        self.types
+    }
+
+    pub(crate) fn kinds(&self) -> &'a [ServiceKind] {
+        self.kinds
+    }
 }
 
 impl<TS: Strategy + 'static> SealedResolvable<TS> for () {
-    type Item = ();
-    type ItemPreChecked = ();
+    type Item<'a> = ();
+    type ItemPreChecked<'a> = ();
     type PrecheckResult = ();
     type TypeIdsIter = Empty<usize>;
 
-    fn resolve(_: &ServiceProvider<TS>) -> Self::Item {}
-    fn resolve_prechecked(
-        _: &ServiceProvider<TS>,
+    fn resolve(_: &ServiceProvider<TS>) -> Self::Item<'_> {}
+    fn resolve_prechecked<'a>(
+        _: &'a ServiceProvider<TS>,
         _: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
+    ) -> Self::ItemPreChecked<'a> {
     }
 
-    fn precheck(_ordered_types: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
         Ok(())
     }
 
-    fn iter_positions(_: &[TS::Id]) -> Self::TypeIdsIter {
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
         empty()
     }
 }
@@ -69,33 +185,33 @@ impl<TS: Strategy + 'static> Resolvable<TS> for () {}
 impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>> SealedResolvable<TS>
     for (T0, T1)
 {
-    type Item = (T0::Item, T1::Item);
-    type ItemPreChecked = (T0::ItemPreChecked, T1::ItemPreChecked);
+    type Item<'a> = (T0::Item<'a>, T1::Item<'a>);
+    type ItemPreChecked<'a> = (T0::ItemPreChecked<'a>, T1::ItemPreChecked<'a>);
     type PrecheckResult = (T0::PrecheckResult, T1::PrecheckResult);
     type TypeIdsIter = Chain<T0::TypeIdsIter, T1::TypeIdsIter>;
 
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item {
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
         (provider.resolve::<T0>(), provider.resolve::<T1>())
     }
 
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         key: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
+    ) -> Self::ItemPreChecked<'a> {
         (
             T0::resolve_prechecked(provider, &key.0),
             T1::resolve_prechecked(provider, &key.1),
         )
     }
 
-    fn precheck(ordered_types: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
-        let r0 = T0::precheck(ordered_types)?;
-        let r1 = T1::precheck(ordered_types)?;
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let r0 = T0::precheck(ctx)?;
+        let r1 = T1::precheck(ctx)?;
         Ok((r0, r1))
     }
 
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter {
-        T0::iter_positions(types).chain(T1::iter_positions(types))
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        T0::iter_positions(ctx).chain(T1::iter_positions(ctx))
     }
 }
 impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>> Resolvable<TS> for (T0, T1) {}
@@ -103,22 +219,26 @@ impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>> Resolvable<
 impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>, T2: Resolvable<TS>>
     SealedResolvable<TS> for (T0, T1, T2)
 {
-    type Item = (T0::Item, T1::Item, T2::Item);
-    type ItemPreChecked = (T0::ItemPreChecked, T1::ItemPreChecked, T2::ItemPreChecked);
+    type Item<'a> = (T0::Item<'a>, T1::Item<'a>, T2::Item<'a>);
+    type ItemPreChecked<'a> = (
+        T0::ItemPreChecked<'a>,
+        T1::ItemPreChecked<'a>,
+        T2::ItemPreChecked<'a>,
+    );
     type PrecheckResult = (T0::PrecheckResult, T1::PrecheckResult, T2::PrecheckResult);
     type TypeIdsIter = Chain<Chain<T0::TypeIdsIter, T1::TypeIdsIter>, T2::TypeIdsIter>;
 
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item {
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
         (
             provider.resolve::<T0>(),
             provider.resolve::<T1>(),
             provider.resolve::<T2>(),
         )
     }
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         key: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
+    ) -> Self::ItemPreChecked<'a> {
         (
             T0::resolve_prechecked(provider, &key.0),
             T1::resolve_prechecked(provider, &key.1),
@@ -126,17 +246,17 @@ impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>, T2: Resolva
         )
     }
 
-    fn precheck(ordered_types: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
-        let r0 = T0::precheck(ordered_types)?;
-        let r1 = T1::precheck(ordered_types)?;
-        let r2 = T2::precheck(ordered_types)?;
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let r0 = T0::precheck(ctx)?;
+        let r1 = T1::precheck(ctx)?;
+        let r2 = T2::precheck(ctx)?;
         Ok((r0, r1, r2))
     }
 
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter {
-        T0::iter_positions(types)
-            .chain(T1::iter_positions(types))
-            .chain(T2::iter_positions(types))
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        T0::iter_positions(ctx)
+            .chain(T1::iter_positions(ctx))
+            .chain(T2::iter_positions(ctx))
     }
 }
 impl<TS: Strategy + 'static, T0: Resolvable<TS>, T1: Resolvable<TS>, T2: Resolvable<TS>>
@@ -152,12 +272,12 @@ impl<
         T3: Resolvable<TS>,
     > SealedResolvable<TS> for (T0, T1, T2, T3)
 {
-    type Item = (T0::Item, T1::Item, T2::Item, T3::Item);
-    type ItemPreChecked = (
-        T0::ItemPreChecked,
-        T1::ItemPreChecked,
-        T2::ItemPreChecked,
-        T3::ItemPreChecked,
+    type Item<'a> = (T0::Item<'a>, T1::Item<'a>, T2::Item<'a>, T3::Item<'a>);
+    type ItemPreChecked<'a> = (
+        T0::ItemPreChecked<'a>,
+        T1::ItemPreChecked<'a>,
+        T2::ItemPreChecked<'a>,
+        T3::ItemPreChecked<'a>,
     );
     type PrecheckResult = (
         T0::PrecheckResult,
@@ -169,7 +289,7 @@ impl<
     type TypeIdsIter =
         Chain<Chain<Chain<T0::TypeIdsIter, T1::TypeIdsIter>, T2::TypeIdsIter>, T3::TypeIdsIter>;
 
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item {
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
         (
             provider.resolve::<T0>(),
             provider.resolve::<T1>(),
@@ -177,10 +297,10 @@ impl<
             provider.resolve::<T3>(),
         )
     }
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         key: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
+    ) -> Self::ItemPreChecked<'a> {
         (
             T0::resolve_prechecked(provider, &key.0),
             T1::resolve_prechecked(provider, &key.1),
@@ -189,19 +309,19 @@ impl<
         )
     }
 
-    fn precheck(ordered_types: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
-        let r0 = T0::precheck(ordered_types)?;
-        let r1 = T1::precheck(ordered_types)?;
-        let r2 = T2::precheck(ordered_types)?;
-        let r3 = T3::precheck(ordered_types)?;
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let r0 = T0::precheck(ctx)?;
+        let r1 = T1::precheck(ctx)?;
+        let r2 = T2::precheck(ctx)?;
+        let r3 = T3::precheck(ctx)?;
         Ok((r0, r1, r2, r3))
     }
 
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter {
-        T0::iter_positions(types)
-            .chain(T1::iter_positions(types))
-            .chain(T2::iter_positions(types))
-            .chain(T3::iter_positions(types))
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        T0::iter_positions(ctx)
+            .chain(T1::iter_positions(ctx))
+            .chain(T2::iter_positions(ctx))
+            .chain(T3::iter_positions(ctx))
     }
 }
 impl<
@@ -216,22 +336,25 @@ impl<
 
 impl<TS: Strategy + 'static> SealedResolvable<TS> for WeakServiceProvider<TS> {
     // Doesn't make sense to call from the outside
-    type Item = ();
-    type ItemPreChecked = Self;
+    type Item<'a> = ();
+    type ItemPreChecked<'a> = Self;
     type PrecheckResult = ();
     type TypeIdsIter = Empty<usize>;
 
-    fn resolve(_provider: &ServiceProvider<TS>) -> Self::Item {}
+    fn resolve(_provider: &ServiceProvider<TS>) -> Self::Item<'_> {}
 
-    fn resolve_prechecked(provider: &ServiceProvider<TS>, _: &()) -> Self::ItemPreChecked {
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        _: &(),
+    ) -> Self::ItemPreChecked<'a> {
         provider.into()
     }
 
-    fn precheck(_: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
         Ok(())
     }
 
-    fn iter_positions(_types: &[TS::Id]) -> Self::TypeIdsIter {
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
         empty()
     }
 }
@@ -244,89 +367,698 @@ pub(crate) unsafe fn resolve_unchecked<TS: Strategy + 'static, T: Identifyable<T
 ) -> T {
     let entry = provider.get_producers().get_unchecked(pos);
     debug_assert_eq!(entry.get_result_type_id(), &T::get_id());
+    #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+    if core::mem::size_of::<T>() == 0 {
+        provider.count_zero_sized_construction();
+    }
+    #[cfg(feature = "resolution_depth_limit")]
+    let _depth = crate::service_provider::ResolutionDepthGuard::enter(type_name::<T>());
     entry.execute::<T>(provider)
 }
 
 impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for AllRegistered<T> {
-    type Item = ServiceIterator<T, TS>;
-    type ItemPreChecked = ServiceIterator<T, TS>;
+    type Item<'a> = ServiceIterator<T, TS>;
+    type ItemPreChecked<'a> = ServiceIterator<T, TS>;
     type PrecheckResult = ();
     type TypeIdsIter = Range<usize>;
 
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item {
-        let next_pos = binary_search::binary_search_first_by_key(
-            provider.get_producers(),
-            &T::get_id(),
-            |f| f.get_result_type_id(),
-        );
-        ServiceIterator::new(provider.into(), next_pos)
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        ServiceIterator::new(
+            provider.into(),
+            provider.precheck_context().positions(&T::get_id()),
+        )
     }
 
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         _: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
+    ) -> Self::ItemPreChecked<'a> {
         Self::resolve(provider)
     }
 
-    fn precheck(_: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
         // Todo: Implement to avoid lookup during service resolution
         Ok(())
     }
 
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter {
-        let id = T::get_id();
-        let first = binary_search::binary_search_first_by_key(types, &id, |f| f);
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        ctx.positions(&T::get_id())
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for AllRegistered<T> {}
 
-        match first {
-            Some(x) => {
-                let to = binary_search::binary_search_last_by_key(&types[x..], &id, |f| f).unwrap()
-                    + x
-                    + 1;
-                x..to
-            }
-            None => 0..0,
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for CountOf<T> {
+    type Item<'a> = usize;
+    type ItemPreChecked<'a> = usize;
+    type PrecheckResult = usize;
+    // The instances are never constructed, so they can't be part of a cycle
+    type TypeIdsIter = Empty<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        provider.precheck_context().positions(&T::get_id()).len()
+    }
+
+    fn resolve_prechecked<'a>(
+        _: &'a ServiceProvider<TS>,
+        count: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        *count
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        Ok(ctx.positions(&T::get_id()).len())
+    }
+
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        empty()
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for CountOf<T> {}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for FromParent<T> {
+    type Item<'a> = Option<T>;
+    type ItemPreChecked<'a> = Option<T>;
+    type PrecheckResult = ();
+    // Parents are built already, so their services can't depend on the current provider
+    type TypeIdsIter = Empty<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        provider.parents().find_map(|parent| parent.get::<T>())
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        _: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        Self::resolve(provider)
+    }
+
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        Ok(())
+    }
+
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        empty()
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for FromParent<T> {}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for AtLeastOne<T> {
+    type Item<'a> = Option<ServiceIterator<T, TS>>;
+    type ItemPreChecked<'a> = ServiceIterator<T, TS>;
+    type PrecheckResult = Range<usize>;
+    type TypeIdsIter = Range<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|positions| Self::resolve_prechecked(provider, &positions))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        positions: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        ServiceIterator::new(provider.into(), positions.clone())
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let positions = ctx.positions(&T::get_id());
+        if positions.is_empty() {
+            Err(BuildError::<TS>::new_missing_dependency::<T>())
+        } else {
+            Ok(positions)
         }
     }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        ctx.positions(&T::get_id())
+    }
 }
-impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for AllRegistered<T> {}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for AtLeastOne<T> {}
 
 impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for Registered<T> {
-    type Item = Option<T>;
-    type ItemPreChecked = T;
+    type Item<'a> = Option<T>;
+    type ItemPreChecked<'a> = T;
     type PrecheckResult = usize;
     type TypeIdsIter = Once<usize>;
 
-    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item {
-        binary_search::binary_search_last_by_key(provider.get_producers(), &T::get_id(), |f| {
-            f.get_result_type_id()
-        })
-        .map(|index| unsafe { resolve_unchecked::<TS, Self::ItemPreChecked>(provider, index) })
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        provider
+            .precheck_context()
+            .positions(&T::get_id())
+            .last()
+            .map(|index| unsafe { resolve_unchecked::<TS, T>(provider, index) })
     }
 
-    fn resolve_prechecked(
-        provider: &ServiceProvider<TS>,
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
         index: &Self::PrecheckResult,
-    ) -> Self::ItemPreChecked {
-        unsafe { resolve_unchecked::<TS, Self::ItemPreChecked>(provider, *index) }
+    ) -> Self::ItemPreChecked<'a> {
+        unsafe { resolve_unchecked::<TS, T>(provider, *index) }
     }
 
-    fn precheck(producers: &[TS::Id]) -> Result<Self::PrecheckResult, BuildError<TS>> {
-        binary_search::binary_search_last_by_key(producers, &T::get_id(), |f| f)
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        ctx.positions(&T::get_id())
+            .last()
             .ok_or_else(BuildError::<TS>::new_missing_dependency::<T>)
     }
 
-    fn iter_positions(types: &[TS::Id]) -> Self::TypeIdsIter {
-        let position = binary_search::binary_search_last_by_key(
-            types,
-            &Self::ItemPreChecked::get_id(),
-            |f| f
-        ).expect("type be found. This shouldn't be possible, as MissingDependency should have been checked");
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let position = ctx.positions(&T::get_id()).last().expect(
+            "type be found. This shouldn't be possible, as MissingDependency should have been checked",
+        );
         once(position)
     }
 }
 impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for Registered<T> {}
 
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS>
+    for PreviousRegistered<T>
+{
+    type Item<'a> = Option<T>;
+    type ItemPreChecked<'a> = T;
+    type PrecheckResult = usize;
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(_: &ServiceProvider<TS>) -> Self::Item<'_> {
+        None
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        unsafe { resolve_unchecked::<TS, T>(provider, *index) }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        ctx.previous_position::<T>()
+            .ok_or_else(BuildError::<TS>::new_missing_dependency::<T>)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let position = ctx.previous_position::<T>().expect(
+            "type be found. This shouldn't be possible, as MissingDependency should have been checked",
+        );
+        once(position)
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for PreviousRegistered<T> {}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>, const N: usize> SealedResolvable<TS>
+    for RegisteredArray<T, N>
+{
+    type Item<'a> = Option<[T; N]>;
+    type ItemPreChecked<'a> = [T; N];
+    /// Position of the first of the N services
+    type PrecheckResult = usize;
+    type TypeIdsIter = Range<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|first| Self::resolve_prechecked(provider, &first))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        first: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        core::array::from_fn(|i| unsafe { resolve_unchecked::<TS, T>(provider, first + i) })
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let positions = ctx.positions(&T::get_id());
+        if positions.len() == N {
+            Ok(positions.start)
+        } else {
+            Err(BuildError::CountMismatch {
+                id: T::get_id(),
+                name: type_name::<T>(),
+                expected: N,
+                found: positions.len(),
+            })
+        }
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        ctx.positions(&T::get_id())
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>, const N: usize> Resolvable<TS>
+    for RegisteredArray<T, N>
+{
+}
+
+impl<TS: Strategy + 'static, C: FromServices> SealedResolvable<TS> for CollectInto<C>
+where
+    C::Service: Identifyable<TS::Id>,
+{
+    type Item<'a> = C;
+    type ItemPreChecked<'a> = C;
+    type PrecheckResult = Range<usize>;
+    type TypeIdsIter = Range<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::resolve_prechecked(
+            provider,
+            &Self::iter_positions(&provider.precheck_context()),
+        )
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        positions: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        positions
+            .clone()
+            .map(|pos| unsafe { resolve_unchecked::<TS, C::Service>(provider, pos) })
+            .collect()
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        Ok(Self::iter_positions(ctx))
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        ctx.positions(&C::Service::get_id())
+    }
+}
+impl<TS: Strategy + 'static, C: FromServices> Resolvable<TS> for CollectInto<C> where
+    C::Service: Identifyable<TS::Id>
+{
+}
+
+impl<TS: Strategy + 'static, TKey: TypedKey, T: 'static> SealedResolvable<TS> for Keyed<TKey, T>
+where
+    KeyedService<TKey::Key, T>: Identifyable<TS::Id>,
+    ServiceKey<TKey::Key, T>: Identifyable<TS::Id>,
+{
+    type Item<'a> = Option<T>;
+    type ItemPreChecked<'a> = T;
+    type PrecheckResult = usize;
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        provider.get_keyed::<TKey::Key, T>(&TKey::key())
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        unsafe { resolve_unchecked::<TS, KeyedService<TKey::Key, T>>(provider, *index) }.0
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        let key = TKey::key();
+        ctx.keyed_position::<TKey::Key, T>(&key)
+            .ok_or_else(|| BuildError::MissingKey {
+                name: type_name::<T>(),
+                key: format!("{key:?}"),
+            })
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let position = ctx.keyed_position::<TKey::Key, T>(&TKey::key()).expect(
+            "key be found. This shouldn't be possible, as MissingKey should have been checked",
+        );
+        once(position)
+    }
+}
+impl<TS: Strategy + 'static, TKey: TypedKey, T: 'static> Resolvable<TS> for Keyed<TKey, T>
+where
+    KeyedService<TKey::Key, T>: Identifyable<TS::Id>,
+    ServiceKey<TKey::Key, T>: Identifyable<TS::Id>,
+{
+}
+
+impl<TS: Strategy + 'static, T: Send + Sync + 'static> SealedResolvable<TS> for WeakArc<T>
+where
+    Arc<T>: Identifyable<TS::Id>,
+{
+    type Item<'a> = Option<Weak<T>>;
+    type ItemPreChecked<'a> = Weak<T>;
+    type PrecheckResult = usize;
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        <Registered<Arc<T>> as SealedResolvable<TS>>::resolve(provider).map(|x| Arc::downgrade(&x))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        Arc::downgrade(
+            &<Registered<Arc<T>> as SealedResolvable<TS>>::resolve_prechecked(provider, index),
+        )
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        <Registered<Arc<T>> as SealedResolvable<TS>>::precheck(ctx)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        <Registered<Arc<T>> as SealedResolvable<TS>>::iter_positions(ctx)
+    }
+}
+impl<TS: Strategy + 'static, T: Send + Sync + 'static> Resolvable<TS> for WeakArc<T> where
+    Arc<T>: Identifyable<TS::Id>
+{
+}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for Arced<T> {
+    type Item<'a> = Option<Arc<T>>;
+    type ItemPreChecked<'a> = Arc<T>;
+    type PrecheckResult = usize;
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        <Registered<T> as SealedResolvable<TS>>::resolve(provider).map(Arc::new)
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        Arc::new(<Registered<T> as SealedResolvable<TS>>::resolve_prechecked(
+            provider, index,
+        ))
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        <Registered<T> as SealedResolvable<TS>>::precheck(ctx)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        <Registered<T> as SealedResolvable<TS>>::iter_positions(ctx)
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for Arced<T> {}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for Either<T>
+where
+    Arc<T>: Identifyable<TS::Id>,
+{
+    type Item<'a> = Option<OwnedOrShared<T>>;
+    type ItemPreChecked<'a> = OwnedOrShared<T>;
+    // Position of T or, if the flag is set, of Arc<T>
+    type PrecheckResult = (bool, usize);
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        <Registered<T> as SealedResolvable<TS>>::resolve(provider)
+            .map(OwnedOrShared::Owned)
+            .or_else(|| {
+                <Registered<Arc<T>> as SealedResolvable<TS>>::resolve(provider)
+                    .map(OwnedOrShared::Shared)
+            })
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        (is_shared, index): &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        if *is_shared {
+            OwnedOrShared::Shared(unsafe { resolve_unchecked::<TS, Arc<T>>(provider, *index) })
+        } else {
+            OwnedOrShared::Owned(unsafe { resolve_unchecked::<TS, T>(provider, *index) })
+        }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        ctx.positions(&T::get_id())
+            .last()
+            .map(|i| (false, i))
+            .or_else(|| ctx.positions(&Arc::<T>::get_id()).last().map(|i| (true, i)))
+            .ok_or_else(BuildError::<TS>::new_missing_dependency::<T>)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let (_, position) = Self::precheck(ctx).expect(
+            "type be found. This shouldn't be possible, as MissingDependency should have been checked",
+        );
+        once(position)
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for Either<T> where
+    Arc<T>: Identifyable<TS::Id>
+{
+}
+
+// Returns the index of the first type in `ids` with a registered service, along with the position of its last service
+fn first_registered<TS: Strategy + 'static>(
+    ctx: &PrecheckContext<TS>,
+    ids: &[TS::Id],
+) -> Option<(usize, usize)> {
+    ids.iter()
+        .enumerate()
+        .find_map(|(variant, id)| ctx.positions(id).last().map(|pos| (variant, pos)))
+}
+
+impl<TS: Strategy + 'static, T0: Identifyable<TS::Id>, T1: Identifyable<TS::Id>, TOut>
+    SealedResolvable<TS> for OneOf<(T0, T1), TOut>
+where
+    T0: Into<TOut>,
+    T1: Into<TOut>,
+{
+    type Item<'a> = Option<TOut>;
+    type ItemPreChecked<'a> = TOut;
+    // Index of the chosen type and position of its service
+    type PrecheckResult = (usize, usize);
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|key| Self::resolve_prechecked(provider, &key))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        (variant, index): &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        match variant {
+            0 => unsafe { resolve_unchecked::<TS, T0>(provider, *index) }.into(),
+            _ => unsafe { resolve_unchecked::<TS, T1>(provider, *index) }.into(),
+        }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        first_registered(ctx, &[T0::get_id(), T1::get_id()])
+            .ok_or_else(BuildError::<TS>::new_missing_dependency::<T0>)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let (_, position) = Self::precheck(ctx).expect(
+            "type be found. This shouldn't be possible, as MissingDependency should have been checked",
+        );
+        once(position)
+    }
+}
+impl<TS: Strategy + 'static, T0: Identifyable<TS::Id>, T1: Identifyable<TS::Id>, TOut>
+    Resolvable<TS> for OneOf<(T0, T1), TOut>
+where
+    T0: Into<TOut>,
+    T1: Into<TOut>,
+{
+}
+
+impl<
+        TS: Strategy + 'static,
+        T0: Identifyable<TS::Id>,
+        T1: Identifyable<TS::Id>,
+        T2: Identifyable<TS::Id>,
+        TOut,
+    > SealedResolvable<TS> for OneOf<(T0, T1, T2), TOut>
+where
+    T0: Into<TOut>,
+    T1: Into<TOut>,
+    T2: Into<TOut>,
+{
+    type Item<'a> = Option<TOut>;
+    type ItemPreChecked<'a> = TOut;
+    // Index of the chosen type and position of its service
+    type PrecheckResult = (usize, usize);
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|key| Self::resolve_prechecked(provider, &key))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        (variant, index): &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        match variant {
+            0 => unsafe { resolve_unchecked::<TS, T0>(provider, *index) }.into(),
+            1 => unsafe { resolve_unchecked::<TS, T1>(provider, *index) }.into(),
+            _ => unsafe { resolve_unchecked::<TS, T2>(provider, *index) }.into(),
+        }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        first_registered(ctx, &[T0::get_id(), T1::get_id(), T2::get_id()])
+            .ok_or_else(BuildError::<TS>::new_missing_dependency::<T0>)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        let (_, position) = Self::precheck(ctx).expect(
+            "type be found. This shouldn't be possible, as MissingDependency should have been checked",
+        );
+        once(position)
+    }
+}
+impl<
+        TS: Strategy + 'static,
+        T0: Identifyable<TS::Id>,
+        T1: Identifyable<TS::Id>,
+        T2: Identifyable<TS::Id>,
+        TOut,
+    > Resolvable<TS> for OneOf<(T0, T1, T2), TOut>
+where
+    T0: Into<TOut>,
+    T1: Into<TOut>,
+    T2: Into<TOut>,
+{
+}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> SealedResolvable<TS> for Lazy<T> {
+    type Item<'a> = Option<LazyService<T, TS>>;
+    type ItemPreChecked<'a> = LazyService<T, TS>;
+    type PrecheckResult = usize;
+    // Dependencies of T are still considered for cycles, because get() could be called within the factory
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|index| Self::resolve_prechecked(provider, &index))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        unsafe { LazyService::new(provider.into(), *index) }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        <Registered<T> as SealedResolvable<TS>>::precheck(ctx)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        <Registered<T> as SealedResolvable<TS>>::iter_positions(ctx)
+    }
+}
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Resolvable<TS> for Lazy<T> {}
+
+impl<TS: Strategy + 'static, T: Send + 'static> SealedResolvable<TS> for ScopeLocal<T> {
+    type Item<'a> = &'a ScopeLocalSlot<T>;
+    type ItemPreChecked<'a> = &'a ScopeLocalSlot<T>;
+    type PrecheckResult = ();
+    type TypeIdsIter = Empty<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        provider.scope_local_slot()
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        _: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        provider.scope_local_slot()
+    }
+
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        Ok(())
+    }
+
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        empty()
+    }
+}
+impl<TS: Strategy + 'static, T: Send + 'static> Resolvable<TS> for ScopeLocal<T> {}
+
+impl<TS: Strategy + 'static, T: Clone + 'static> SealedResolvable<TS> for ResolutionContext<T> {
+    type Item<'a> = Option<T>;
+    type ItemPreChecked<'a> = Option<T>;
+    type PrecheckResult = ();
+    type TypeIdsIter = Empty<usize>;
+
+    fn resolve(_: &ServiceProvider<TS>) -> Self::Item<'_> {
+        crate::service_provider::current_context()
+    }
+
+    fn resolve_prechecked<'a>(
+        _: &'a ServiceProvider<TS>,
+        _: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        crate::service_provider::current_context()
+    }
+
+    fn precheck(_: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        Ok(())
+    }
+
+    fn iter_positions(_: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        empty()
+    }
+}
+impl<TS: Strategy + 'static, T: Clone + 'static> Resolvable<TS> for ResolutionContext<T> {}
+
+impl<TS: Strategy + 'static, T: Send + Sync + 'static> SealedResolvable<TS> for Shared<T>
+where
+    Arc<T>: Identifyable<TS::Id>,
+{
+    type Item<'a> = Option<&'a T>;
+    type ItemPreChecked<'a> = &'a T;
+    type PrecheckResult = usize;
+    type TypeIdsIter = Once<usize>;
+
+    fn resolve(provider: &ServiceProvider<TS>) -> Self::Item<'_> {
+        Self::precheck(&provider.precheck_context())
+            .ok()
+            .map(|index| Self::resolve_prechecked(provider, &index))
+    }
+
+    fn resolve_prechecked<'a>(
+        provider: &'a ServiceProvider<TS>,
+        index: &Self::PrecheckResult,
+    ) -> Self::ItemPreChecked<'a> {
+        unsafe {
+            let entry = provider.get_producers().get_unchecked(*index);
+            debug_assert_eq!(entry.get_result_type_id(), &Arc::<T>::get_id());
+            let pointer = entry
+                .get_shared_pointer(provider)
+                .expect("Precheck ensures that a shared service is at this position");
+            // The shared service lives as long as the provider it's stored in, which outlives 'a
+            &*(pointer as *const T)
+        }
+    }
+
+    fn precheck(ctx: &PrecheckContext<TS>) -> Result<Self::PrecheckResult, BuildError<TS>> {
+        ctx.positions(&Arc::<T>::get_id())
+            .rev()
+            .find(|i| ctx.kinds()[*i] == ServiceKind::Shared)
+            .ok_or_else(BuildError::<TS>::new_missing_dependency::<Arc<T>>)
+    }
+
+    fn iter_positions(ctx: &PrecheckContext<TS>) -> Self::TypeIdsIter {
+        once(Self::precheck(ctx).expect(
+            "shared service be found. This shouldn't be possible, as MissingDependency should have been checked",
+        ))
+    }
+}
+impl<TS: Strategy + 'static, T: Send + Sync + 'static> Resolvable<TS> for Shared<T> where
+    Arc<T>: Identifyable<TS::Id>
+{
+}
+
 #[cfg(test)]
 mod tests {
     use core::any::TypeId;
@@ -340,19 +1072,46 @@ mod tests {
             TypeId::of::<i64>(),
         ];
         types.sort();
+        let kinds = vec![ServiceKind::Transient; types.len()];
+        let ctx = PrecheckContext::new(&types, &kinds);
 
         assert_eq!(
             2,
-            <AllRegistered::<i32> as SealedResolvable<AnyStrategy>>::iter_positions(&types).count()
+            <AllRegistered::<i32> as SealedResolvable<AnyStrategy>>::iter_positions(&ctx).count()
         );
         assert_eq!(
             1,
-            <AllRegistered::<i64> as SealedResolvable<AnyStrategy>>::iter_positions(&types).count()
+            <AllRegistered::<i64> as SealedResolvable<AnyStrategy>>::iter_positions(&ctx).count()
         );
         assert_eq!(
             0,
-            <AllRegistered::<i128> as SealedResolvable<AnyStrategy>>::iter_positions(&types)
-                .count()
+            <AllRegistered::<i128> as SealedResolvable<AnyStrategy>>::iter_positions(&ctx).count()
         );
     }
+
+    #[test]
+    fn positions_with_index_are_equal_to_binary_search() {
+        let mut types = vec![
+            TypeId::of::<i32>(),
+            TypeId::of::<i64>(),
+            TypeId::of::<i32>(),
+            TypeId::of::<u8>(),
+            TypeId::of::<i32>(),
+        ];
+        types.sort();
+        let kinds = vec![ServiceKind::Transient; types.len()];
+        let index = build_position_index::<AnyStrategy>(&types);
+        let ctx = PrecheckContext::<AnyStrategy>::new(&types, &kinds);
+        let indexed_ctx = PrecheckContext::<AnyStrategy>::new(&types, &kinds).with_index(&index);
+
+        for id in [
+            TypeId::of::<i32>(),
+            TypeId::of::<i64>(),
+            TypeId::of::<u8>(),
+            TypeId::of::<u16>(),
+        ] {
+            assert_eq!(ctx.positions(&id), indexed_ctx.positions(&id));
+        }
+        assert_eq!(3, indexed_ctx.positions(&TypeId::of::<i32>()).len());
+    }
 }
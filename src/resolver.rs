@@ -0,0 +1,52 @@
+use crate::{
+    strategy::{Identifyable, Strategy},
+    AnyStrategy, Resolvable, ServiceIterator, ServiceProvider, WeakServiceProvider,
+};
+
+/// Resolve surface shared by `ServiceProvider` and `WeakServiceProvider`, so libraries and handlers can be
+/// generic over the DI backend and over which kind of provider they hold. All methods are generic over the
+/// resolved type, so the trait isn't object safe and each use is monomorphized.
+/// ```
+/// use minfac::{Resolver, ServiceCollection, WeakServiceProvider};
+///
+/// fn port(resolver: &impl Resolver) -> u16 {
+///     resolver.get::<u16>().unwrap_or(80)
+/// }
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 8080u16);
+/// let provider = collection.build().expect("Configuration is valid");
+/// assert_eq!(8080, port(&provider));
+/// assert_eq!(8080, port(&WeakServiceProvider::from(&provider)));
+/// ```
+pub trait Resolver<TS: Strategy + 'static = AnyStrategy> {
+    /// The provider, which resolves all services
+    fn service_provider(&self) -> &ServiceProvider<TS>;
+
+    /// Returns the last registered instance of `T`, like `ServiceProvider::get`
+    fn get<T: Identifyable<TS::Id>>(&self) -> Option<T> {
+        self.service_provider().get::<T>()
+    }
+
+    fn get_all<T: Identifyable<TS::Id>>(&self) -> ServiceIterator<T, TS> {
+        self.service_provider().get_all::<T>()
+    }
+
+    /// Resolves `T` like `ServiceProvider::resolve_unchecked`, which panics if any dependency is missing
+    #[track_caller]
+    fn resolve_unchecked<T: Resolvable<TS>>(&self) -> T::ItemPreChecked<'_> {
+        self.service_provider().resolve_unchecked::<T>()
+    }
+}
+
+impl<TS: Strategy + 'static> Resolver<TS> for ServiceProvider<TS> {
+    fn service_provider(&self) -> &ServiceProvider<TS> {
+        self
+    }
+}
+
+impl<TS: Strategy + 'static> Resolver<TS> for WeakServiceProvider<TS> {
+    fn service_provider(&self) -> &ServiceProvider<TS> {
+        self
+    }
+}
@@ -3,35 +3,135 @@ use crate::{
     lifetime::{
         DanglingCheckerResult, DanglingCheckerResults, LifetimeError, OutlivedLifetimeErrorVariants,
     },
+    resolvable::{PositionIndex, PrecheckContext},
     strategy::{Identifyable, Strategy},
     untyped::{ArcAutoFreePointer, AutoFreePointer, UntypedFn},
-    AllRegistered, AnyStrategy, InternalBuildResult, Registered, Resolvable, ServiceProducer,
-    TypeNamed, UntypedFnFactory, UntypedFnFactoryContext,
+    AllRegistered, AnyPtr, AnyStrategy, InternalBuildResult, KeyedService, LibraryHandle,
+    Registered, ResolutionError, Resolvable, ServiceKey, ServiceKind, ServiceOrigin,
+    ServiceProducer, TypeNamed, UntypedFnFactory, UntypedFnFactoryContext,
 };
 use abi_stable::std_types::{RArc, RVec};
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{boxed::Box, collections::TryReserveError, sync::Arc, vec::Vec};
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::{
-    any::{type_name, Any},
+    any::{type_name, Any, TypeId},
     fmt,
     fmt::{Debug, Formatter},
+    iter::Rev,
     marker::PhantomData,
-    mem::swap,
+    ops::{Deref, DerefMut, Range},
+    panic::AssertUnwindSafe,
 };
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+// Boxes the service of type `id` at the given position. Recorded by AliasBuilder::boxable, because only
+// the registration knows the concrete type
+pub(crate) type Boxer<TS> = (
+    <TS as Strategy>::Id,
+    fn(&ServiceProvider<TS>, usize) -> Box<dyn Any + Send>,
+);
+
+pub(crate) fn box_service<T: Identifyable<TS::Id> + Any + Send, TS: Strategy + 'static>(
+    provider: &ServiceProvider<TS>,
+    pos: usize,
+) -> Box<dyn Any + Send> {
+    // Only called with positions of the id T was recorded with
+    Box::new(unsafe { crate::resolvable::resolve_unchecked::<TS, T>(provider, pos) })
+}
 
 /// ServiceProviders are created directly from ServiceCollections or ServiceProviderFactories and can be used
 /// to retrieve services by type. ServiceProviders are final and cannot be modified anßymore. When a ServiceProvider goes
 /// out of scope, all related WeakServiceProviders and shared services have to be dropped already. Otherwise
-/// dropping the original ServiceProvider results in a call to minfac::ERROR_HANDLER, which panics in std and enabled debug_assertions
+/// dropping the original ServiceProvider results in a call to minfac::error_handler(), which panics in std if debug_assertions or the `runtime_checks` feature are enabled
+///
+/// # Threading
+/// ServiceProviders are `Send` and `Sync`. Transient services are created on the thread resolving them, so only
+/// shared services, which are cached for all threads, have to be `Send + Sync`. Each built state has exactly one
+/// root, because only `build()` creates one and neither clones nor WeakServiceProviders are roots. The root may be
+/// moved to and dropped on any thread. If debug_assertions or the `runtime_checks` feature are enabled, its drop
+/// check releases the shared services only if it holds the last reference to them. This is decided atomically, so
+/// finalizers never run while another thread can still resolve services. Otherwise minfac::error_handler() is called
+/// and the shared services are leaked. Without these checks, the shared services are released by whichever handle
+/// to the state is dropped last, on the thread dropping it.
+///
+/// Shared services, which aren't `Send + Sync`, are therefore rejected when registering them:
+/// ```compile_fail
+/// use {minfac::ServiceCollection, std::{rc::Rc, sync::Arc}};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register_shared(|| Arc::new(Rc::new(42)));
+/// ```
+/// Transient services may be neither, without affecting the ServiceProvider:
+/// ```
+/// use {minfac::ServiceCollection, std::rc::Rc};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| Rc::new(42));
+/// let provider = collection.build().expect("Configuration is valid");
+/// let value = std::thread::spawn(move || *provider.get::<Rc<i32>>().unwrap()).join().unwrap();
+/// assert_eq!(42, value);
+/// ```
 pub struct ServiceProvider<TS: Strategy + 'static = AnyStrategy> {
     immutable_state: RArc<ServiceProviderImmutableState<TS>>,
     service_states: RArc<ServiceProviderMutableState>,
-    #[cfg(debug_assertions)]
     is_root: bool,
 }
 
+// Moving providers to other threads is part of the public contract, so removing `Send` or `Sync` must not go unnoticed
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ServiceProvider>();
+    assert_send_sync::<WeakServiceProvider>();
+    assert_send_sync::<ServiceIterator<u8>>();
+    assert_send_sync::<LazyService<u8>>();
+    assert_send_sync::<Factory<core::cell::Cell<u8>, core::cell::Cell<u8>>>();
+};
+
+// Formats a service with its kind for alternate Debug formats
+struct DebugService(&'static str, ServiceKind);
+
+impl Debug for DebugService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self.1 {
+            ServiceKind::Transient => "transient",
+            ServiceKind::Shared => "shared",
+        };
+        write!(f, "{} ({kind})", self.0)
+    }
+}
+
+/// The alternate format `{:#?}` lists all services in the order they are stored.
+/// Type names are stored per service anyway, so this doesn't need additional memory.
+/// ```
+/// use {minfac::ServiceCollection, std::sync::Arc};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1u8);
+/// collection.register_shared(|| Arc::new(2u16));
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// assert_eq!("ServiceProvider (services: 2, with_state: 1)", format!("{provider:?}"));
+/// let detailed = format!("{provider:#?}");
+/// assert!(detailed.contains("u8 (transient)"), "{detailed}");
+/// assert!(detailed.contains("alloc::sync::Arc<u16> (shared)"), "{detailed}");
+/// ```
 impl<TS: Strategy + 'static> Debug for ServiceProvider<TS> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            let state = &self.immutable_state;
+            let services = state.type_names.iter().zip(state.kinds.iter());
+            return f
+                .debug_struct("ServiceProvider")
+                .field(
+                    "services",
+                    &services
+                        .map(|(name, kind)| DebugService(name, *kind))
+                        .collect::<Vec<_>>(),
+                )
+                .field("with_state", &self.service_states.shared_services.len())
+                .finish();
+        }
         f.write_fmt(format_args!(
             "ServiceProvider (services: {}, with_state: {})",
             self.immutable_state.producers.len(),
@@ -43,117 +143,983 @@ impl<TS: Strategy + 'static> Debug for ServiceProvider<TS> {
 /// Dropping ServiceProviders created by ServiceCollection::build() or ServiceProviderFactory::build()
 /// directly are expected to have no remaining clones when they are dropped. Clones could be used in services
 /// which have a dependency to ServiceProvider or ServiceIterators<T>, which are using ServiceProvider internally)
-#[cfg(debug_assertions)]
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
 #[allow(clippy::needless_collect)]
 impl<TS: Strategy + 'static> Drop for ServiceProvider<TS> {
     fn drop(&mut self) {
         if !self.is_root {
             return;
         }
+        #[cfg(feature = "log")]
+        match self.zero_sized_constructions() {
+            0 => {}
+            count => log::debug!("Constructed {count} transient services of zero sized types"),
+        }
 
         let mut swapped_service_states = RArc::new(ServiceProviderMutableState {
             base: None,
             shared_services: RVec::new(),
+            memoized: Mutex::new(Vec::new()),
+            scope_locals: Mutex::new(Vec::new()),
+            #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+            zero_sized_constructions: AtomicUsize::new(0),
+            _libraries: Vec::new(),
         });
-        swap(&mut swapped_service_states, &mut self.service_states);
+        core::mem::swap(&mut swapped_service_states, &mut self.service_states);
 
         match RArc::try_unwrap(swapped_service_states) {
-            Ok(service_states) => {
-                let checkers: Vec<_> = service_states
-                    .shared_services
-                    .into_iter()
-                    .filter_map(|c| {
-                        c.get().and_then(|x| {
-                            let weak = x.inner.downgrade();
-                            if weak.strong_count() > 0 {
-                                Some(TypeNamed {
-                                    inner: weak,
-                                    type_name: x.type_name,
-                                })
-                            } else {
-                                None
-                            }
-                        })
-                    })
-                    .collect();
-                let errors = checkers
-                    .into_iter()
-                    .filter_map(|x| {
-                        (x.inner.strong_count() > 0).then(|| {
-                            DanglingCheckerResult::new(x.inner.strong_count(), x.type_name)
-                        })
-                    })
-                    .collect::<DanglingCheckerResults>();
+            Ok(mut service_states) => {
+                let errors = service_states.release_shared_services();
+                drop(service_states);
 
                 if errors.len > 0 {
-                    unsafe {
-                        (crate::MINFAC_ERROR_HANDLER)(&LifetimeError::new(
-                            OutlivedLifetimeErrorVariants::SharedServices(errors),
-                        ))
-                    };
+                    crate::error_handler()(&LifetimeError::new(
+                        OutlivedLifetimeErrorVariants::SharedServices(errors),
+                    ));
                 }
             }
-            Err(x) => unsafe {
+            Err(x) => {
                 let remaining_references = RArc::strong_count(&x) - 1;
-                crate::MINFAC_ERROR_HANDLER(&LifetimeError::new(
+                crate::error_handler()(&LifetimeError::new(
                     OutlivedLifetimeErrorVariants::WeakServiceProvider {
                         remaining_references,
                     },
                 ));
-            },
+            }
         }
     }
 }
 
 impl<TS: Strategy + 'static> ServiceProvider<TS> {
-    pub fn resolve_unchecked<T: Resolvable<TS>>(&self) -> T::ItemPreChecked {
-        let precheck_key =
-            T::precheck(&self.immutable_state.types).expect("Resolve unkwnown service");
+    /// Resolves `T`, whose dependencies are expected to be registered.
+    /// Prefer `resolve`, `get` or `try_get` where a missing service is a regular outcome, e.g. in request handlers.
+    ///
+    /// # Panics
+    /// Panics if any dependency of `T` is missing. The message contains the type name of `T` and of the missing
+    /// dependency, and the panic reports the location of the caller.
+    /// ```should_panic
+    /// let provider = minfac::ServiceCollection::new().build().unwrap();
+    /// provider.resolve_unchecked::<minfac::Registered<i32>>();
+    /// ```
+    #[track_caller]
+    pub fn resolve_unchecked<T: Resolvable<TS>>(&self) -> T::ItemPreChecked<'_> {
+        // Closures don't propagate the caller location, so `unwrap_or_else` can't be used
+        let precheck_key = match T::precheck(&self.precheck_context()) {
+            Ok(x) => x,
+            Err(e) => panic!("Resolve unknown service {}: {e:?}", type_name::<T>()),
+        };
         T::resolve_prechecked(self, &precheck_key)
     }
 
+    /// Resolves `T` like `resolve_unchecked`, but returns None if any dependency is missing.
+    /// Shared services resolved via `Shared<T>` are borrowed from the ServiceProvider without
+    /// cloning their Arc.
+    ///
+    /// ```
+    /// use {minfac::{Registered, ServiceCollection, Shared}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(String::from("Hello")));
+    /// collection.register(|| 42i32);
+    /// let provider = collection.build().unwrap();
+    ///
+    /// let (text, number) = provider.resolve_refs::<(Shared<String>, Registered<i32>)>().unwrap();
+    /// assert_eq!((text.as_str(), number), ("Hello", 42));
+    /// assert!(provider.resolve_refs::<Shared<u8>>().is_none());
+    /// ```
+    pub fn resolve_refs<T: Resolvable<TS>>(&self) -> Option<T::ItemPreChecked<'_>> {
+        T::precheck(&self.precheck_context())
+            .ok()
+            .map(|key| T::resolve_prechecked(self, &key))
+    }
+
+    pub(crate) fn precheck_context(&self) -> PrecheckContext<'_, TS> {
+        PrecheckContext::new(&self.immutable_state.types, &self.immutable_state.kinds)
+            .with_index(&self.immutable_state.index)
+    }
+
     pub fn get<T: Identifyable<TS::Id>>(&self) -> Option<T> {
         self.resolve::<Registered<T>>()
     }
+
+    /// Same as `get`, but additionally returns the position of the producer which created the service.
+    /// Positions index all services of this provider ordered by type and registration, including
+    /// inherited services. They are equal for all providers built by the same ServiceProviderFactory.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1);
+    /// collection.register(|| 2);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let (value, position) = provider.get_with_source::<i32>().unwrap();
+    /// assert_eq!(2, value);
+    /// assert_eq!(1, position);
+    /// ```
+    pub fn get_with_source<T: Identifyable<TS::Id>>(&self) -> Option<(T, usize)> {
+        let pos = self.precheck_context().positions(&T::get_id()).last()?;
+        Some((
+            unsafe { crate::resolvable::resolve_unchecked::<TS, T>(self, pos) },
+            pos,
+        ))
+    }
+
+    /// Same as `get`, but additionally returns the type name of the service, e.g. for audit logs.
+    /// Like `core::any::type_name`, its format is only meant for diagnostics.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 42u8);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert_eq!(Some((42, "u8")), provider.get_named::<u8>());
+    /// ```
+    pub fn get_named<T: Identifyable<TS::Id>>(&self) -> Option<(T, &'static str)> {
+        self.get().map(|x| (x, type_name::<T>()))
+    }
+
+    /// Returns the `n`-th instance of `T` counted from the last registered one, e.g. for fallback chains.
+    /// `get_nth_from_last(0)` is equivalent to `get()`. None is returned, if less than `n + 1` instances exist.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| "fallback");
+    /// collection.register(|| "primary");
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert_eq!(Some("fallback"), provider.get_nth_from_last::<&str>(1));
+    /// assert_eq!(None, provider.get_nth_from_last::<&str>(2));
+    /// ```
+    pub fn get_nth_from_last<T: Identifyable<TS::Id>>(&self, n: usize) -> Option<T> {
+        let positions = self.precheck_context().positions(&T::get_id());
+        let pos = positions.rev().nth(n)?;
+        Some(unsafe { crate::resolvable::resolve_unchecked::<TS, T>(self, pos) })
+    }
+
+    /// Resolves `T` like `get` on the first call and returns clones of this instance afterwards.
+    /// This caches transient services at runtime without changing their registration, so `get` still
+    /// constructs a new instance for each call. The cache is keyed by the Rust type of `T` and shared by all
+    /// clones and WeakServiceProviders of this provider. Memoized services are released like shared services,
+    /// so they must not hold this provider.
+    /// ```
+    /// use {
+    ///     core::sync::atomic::{AtomicUsize, Ordering},
+    ///     minfac::ServiceCollection,
+    /// };
+    ///
+    /// static CREATED: AtomicUsize = AtomicUsize::new(0);
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| CREATED.fetch_add(1, Ordering::Relaxed));
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert_eq!(Some(0), provider.memoize::<usize>());
+    /// assert_eq!(Some(0), provider.memoize::<usize>());
+    /// assert_eq!(Some(1), provider.get::<usize>());
+    /// ```
+    pub fn memoize<T: Identifyable<TS::Id> + Clone + Send + Sync>(&self) -> Option<T> {
+        let id = TypeId::of::<T>();
+        let cached = |memoized: &[(TypeId, Box<dyn Any + Send + Sync>)]| {
+            memoized
+                .iter()
+                .find(|(x, _)| *x == id)
+                .and_then(|(_, value)| value.downcast_ref::<T>().cloned())
+        };
+        let memoized = &self.service_states.memoized;
+        if let Some(value) = cached(&memoized.lock().unwrap_or_else(PoisonError::into_inner)) {
+            return Some(value);
+        }
+        // The lock isn't held during construction, because T might memoize its dependencies too
+        let value = self.get::<T>()?;
+        let mut memoized = memoized.lock().unwrap_or_else(PoisonError::into_inner);
+        // If another thread was faster, its instance is kept, so all callers share the same one
+        if let Some(value) = cached(&memoized) {
+            return Some(value);
+        }
+        memoized.push((id, Box::new(value.clone())));
+        Some(value)
+    }
+
     pub fn get_all<T: Identifyable<TS::Id>>(&self) -> ServiceIterator<T, TS> {
         self.resolve::<AllRegistered<T>>()
     }
 
-    pub(crate) fn resolve<T: Resolvable<TS>>(&self) -> T::Item {
+    /// Same as `get_all`, but the latest registered service is returned first, e.g. for middlewares
+    /// where the middleware of a later plugin should run outermost.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1);
+    /// collection.register(|| 2);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert_eq!(vec![2, 1], provider.get_all_rev::<i32>().collect::<Vec<_>>());
+    /// ```
+    pub fn get_all_rev<T: Identifyable<TS::Id>>(&self) -> Rev<ServiceIterator<T, TS>> {
+        self.get_all().rev()
+    }
+
+    /// Same as `get`, but the service is bound to the lifetime of this provider. Use it for services
+    /// which must not outlive their provider, e.g. shared services or a `WeakServiceProvider`. Clones of the
+    /// service aren't bound, see `Scoped`.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42));
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// let value = provider.get_scoped::<Arc<i32>>().unwrap();
+    /// assert_eq!(42, **value);
+    /// ```
+    pub fn get_scoped<T: Identifyable<TS::Id>>(&self) -> Option<Scoped<'_, T>> {
+        self.get().map(Scoped::new)
+    }
+
+    /// Same as `get_all`, but the iterator is bound to the lifetime of this provider
+    pub fn get_all_scoped<T: Identifyable<TS::Id>>(&self) -> Scoped<'_, ServiceIterator<T, TS>> {
+        Scoped::new(self.get_all())
+    }
+
+    /// Alias for `get_all`. Services are resolved lazily, one per call of `next()`, so skipped services
+    /// are never constructed. The iterator holds a WeakServiceProvider and must therefore be dropped
+    /// before this ServiceProvider, which is given when it's consumed in a `for` loop.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1);
+    /// collection.register(|| 2);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let mut sum = 0;
+    /// for i in provider.iter::<i32>() {
+    ///     sum += i;
+    /// }
+    /// assert_eq!(3, sum);
+    /// ```
+    pub fn iter<T: Identifyable<TS::Id>>(&self) -> ServiceIterator<T, TS> {
+        self.get_all()
+    }
+
+    /// Resolves the service of type `T`, which was registered with `key` by `register_keyed`.
+    /// Only the matching service is constructed. If `key` was registered multiple times, the last one is used.
+    pub fn get_keyed<K: PartialEq + 'static, T: 'static>(&self, key: &K) -> Option<T>
+    where
+        KeyedService<K, T>: Identifyable<TS::Id>,
+        ServiceKey<K, T>: Identifyable<TS::Id>,
+    {
+        // Keys are registered alongside their services, so both have the same order
+        let nth = self
+            .get_all::<ServiceKey<K, T>>()
+            .enumerate()
+            .filter(|(_, registered)| registered.0 == *key)
+            .last()?
+            .0;
+        let first = binary_search::binary_search_first_by_key(
+            self.get_producers(),
+            &KeyedService::<K, T>::get_id(),
+            UntypedFn::<TS>::get_result_type_id,
+        )?;
+        let service = unsafe {
+            crate::resolvable::resolve_unchecked::<TS, KeyedService<K, T>>(self, first + nth)
+        };
+        Some(service.0)
+    }
+
+    /// Resolves all instances of `T` and sorts them by key. The sort is stable, so services with
+    /// equal keys keep their registration order.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// struct Plugin { name: &'static str, order: u8 }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| Plugin { name: "last", order: 2 });
+    /// collection.register(|| Plugin { name: "first", order: 1 });
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let plugins = provider.get_all_sorted_by_key(|p: &Plugin| p.order);
+    /// assert_eq!(vec!["first", "last"], plugins.iter().map(|p| p.name).collect::<Vec<_>>());
+    /// ```
+    pub fn get_all_sorted_by_key<T: Identifyable<TS::Id>, K: Ord>(
+        &self,
+        key_fn: impl FnMut(&T) -> K,
+    ) -> Vec<T> {
+        let mut services: Vec<_> = self.get_all().collect();
+        services.sort_by_key(key_fn);
+        services
+    }
+
+    /// Resolves all services of type `T` and converts each of them, e.g. into a boxed trait object.
+    /// In contrast to `get_all`, no iterator holding a WeakServiceProvider is returned, so the result can
+    /// outlive this provider, as long as the converted services don't depend on it.
+    /// ```
+    /// use minfac::ServiceCollection;
+    /// trait Handler {
+    ///     fn name(&self) -> &'static str;
+    /// }
+    /// struct Index;
+    /// impl Handler for Index {
+    ///     fn name(&self) -> &'static str { "index" }
+    /// }
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| Index);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let handlers = provider.get_all_mapped(|x: Index| Box::new(x) as Box<dyn Handler>);
+    /// drop(provider);
+    /// assert_eq!(vec!["index"], handlers.iter().map(|h| h.name()).collect::<Vec<_>>());
+    /// ```
+    pub fn get_all_mapped<T: Identifyable<TS::Id>, TNew>(
+        &self,
+        convert: impl FnMut(T) -> TNew,
+    ) -> Vec<TNew> {
+        self.get_all::<T>().map(convert).collect()
+    }
+
+    /// Calls `visit` for each service of type `T`, which is dropped right after its call. In contrast to
+    /// `get_all`, nothing is allocated and no iterator holding a WeakServiceProvider survives the call.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1);
+    /// collection.register(|| 2);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let mut sum = 0;
+    /// provider.for_each(|i: i32| sum += i);
+    /// assert_eq!(3, sum);
+    /// ```
+    pub fn for_each<T: Identifyable<TS::Id>>(&self, visit: impl FnMut(T)) {
+        self.get_all::<T>().for_each(visit)
+    }
+
+    /// Same as `get`, but panics of the factory or its dependencies are caught and returned as
+    /// `ResolutionError::Panicked`, so a single misbehaving service doesn't unwind into the caller.
+    ///
+    /// The ServiceProvider is treated as `UnwindSafe`: Shared services, whose initialization panicked,
+    /// stay uninitialized and their factory is called again on the next resolution. Services which
+    /// were modified by the panicking factory (e.g. via interior mutability) might be left in an
+    /// inconsistent state. Panics are only caught with `panic = "unwind"` and the panic hook is
+    /// called as usual.
+    /// ```
+    /// use minfac::{ResolutionError, ServiceCollection};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| -> i32 { panic!("Misbehaving service") });
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// assert!(matches!(provider.try_get::<i32>(), Err(ResolutionError::Panicked { .. })));
+    /// assert!(matches!(provider.try_get::<i64>(), Err(ResolutionError::MissingService { .. })));
+    /// ```
+    pub fn try_get<T: Identifyable<TS::Id>>(&self) -> Result<T, ResolutionError> {
+        std::panic::catch_unwind(AssertUnwindSafe(|| self.get::<T>()))
+            .map_err(ResolutionError::panicked::<T>)?
+            .ok_or(ResolutionError::MissingService {
+                name: type_name::<T>(),
+            })
+    }
+
+    /// Same as `get_all`, but a panic of one service is returned as `ResolutionError::Panicked` and the
+    /// iteration continues with the next service. Plugin hosts can therefore skip misbehaving plugins.
+    /// The same unwind safety considerations as for `try_get` apply to each service.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1);
+    /// collection.register(|| -> i32 { panic!("Misbehaving plugin") });
+    /// collection.register(|| 3);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let working: Vec<i32> = provider.try_get_all::<i32>().filter_map(Result::ok).collect();
+    /// assert_eq!(vec![1, 3], working);
+    /// ```
+    pub fn try_get_all<T: Identifyable<TS::Id>>(
+        &self,
+    ) -> impl Iterator<Item = Result<T, ResolutionError>> {
+        // ServiceIterator moves to the next position before constructing a service
+        let mut iter = self.get_all::<T>();
+        core::iter::from_fn(move || {
+            std::panic::catch_unwind(AssertUnwindSafe(|| iter.next()))
+                .map_err(ResolutionError::panicked::<T>)
+                .transpose()
+        })
+    }
+
+    /// Resolves the last shared service registered with `id` without knowing its type at compile time,
+    /// e.g. for bridges to scripting languages. As shared services are registered as `Arc<T>`, `id`
+    /// is the id of `Arc<T>`, whereas `ArcAutoFreePointer::try_clone_as::<T>()` recovers the service.
+    /// It checks the TypeId of `T`, so using a wrong type returns None instead of causing UB.
+    /// Like `Arc<T>` of shared services, the returned handle has to be dropped before the ServiceProvider.
+    /// Transient services have no handle, so None is returned for them, even if they are registered.
+    ///
+    /// Transient services cannot be resolved without their type, so None is returned for them.
+    /// ```
+    /// use {minfac::ServiceCollection, std::{any::TypeId, sync::Arc}};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42i32));
+    /// collection.register(|| 1u8);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let erased = provider.get_erased(TypeId::of::<Arc<i32>>()).unwrap();
+    /// assert_eq!(None, erased.try_clone_as::<i64>());
+    /// assert_eq!(Some(Arc::new(42)), erased.try_clone_as::<i32>());
+    /// assert!(provider.get_erased(TypeId::of::<u8>()).is_none());
+    /// ```
+    pub fn get_erased(&self, id: TS::Id) -> Option<ArcAutoFreePointer> {
+        let producers = self.get_producers();
+        let pos = binary_search::binary_search_last_by_key(
+            producers,
+            &id,
+            UntypedFn::get_result_type_id,
+        )?;
+        unsafe { producers.get_unchecked(pos).get_shared(self) }.cloned()
+    }
+
+    /// Resolves the last service registered with `id` into a `Box<dyn Any + Send>`, e.g. for dynamic hosts,
+    /// which don't know the type at compile time. Unlike `get_erased`, transient services are supported,
+    /// but their type must have been marked with `AliasBuilder::boxable` when registering it, because boxing
+    /// requires the concrete type. None is returned for unregistered or unmarked types.
+    pub fn get_boxed(&self, id: TS::Id) -> Option<Box<dyn Any + Send>> {
+        let (_, boxer) = self.immutable_state.boxers.iter().find(|(x, _)| *x == id)?;
+        let pos = binary_search::binary_search_last_by_key(
+            self.get_producers(),
+            &id,
+            UntypedFn::get_result_type_id,
+        )?;
+        Some(boxer(self, pos))
+    }
+
+    pub(crate) fn boxers(&self) -> &[Boxer<TS>] {
+        &self.immutable_state.boxers
+    }
+
+    /// Runs `scope` with an ambient `context`, which every service resolved within it can read via
+    /// `ResolutionContext<C>` without it being registered, e.g. for a correlation id. Unlike a child provider built by
+    /// `build_factory`, services of this provider can depend on it as well.
+    /// Contexts are nested: The innermost context of type `C` is visible, until its `scope` returns.
+    ///
+    /// The context is local to the calling thread and only visible during `scope`. In async code, services
+    /// should therefore be resolved within `scope` and the resolved values moved into the future, instead
+    /// of resolving after an `.await`. Shared services are cached with the context of the resolution,
+    /// which initialized them, so only transient services should read it.
+    /// ```
+    /// use minfac::{ResolutionContext, ServiceCollection};
+    ///
+    /// #[derive(Clone)]
+    /// struct RequestId(u32);
+    /// struct Logger(Option<u32>);
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection
+    ///     .with::<ResolutionContext<RequestId>>()
+    ///     .register(|id| Logger(id.map(|x| x.0)));
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let logger = provider.with_context(RequestId(42), || provider.get::<Logger>().unwrap());
+    /// assert_eq!(Some(42), logger.0);
+    /// assert_eq!(None, provider.get::<Logger>().unwrap().0);
+    /// ```
+    pub fn with_context<C: 'static, TResult>(
+        &self,
+        context: C,
+        scope: impl FnOnce() -> TResult,
+    ) -> TResult {
+        let _guard = ContextGuard::enter(Box::new(context));
+        scope()
+    }
+
+    /// Initializes all shared services, which weren't resolved yet, so later resolutions don't pay
+    /// for their construction. Shared services inherited from a parent are initialized in the parent.
+    pub fn warm_up(&self) {
+        for producer in self.get_producers().iter() {
+            unsafe { producer.get_shared(self) };
+        }
+    }
+
+    /// Initializes the shared services, which the last registered `T` depends on directly or transitively, and
+    /// returns a new `T`. In contrast to `warm_up`, unrelated shared services stay uninitialized. This includes
+    /// dependencies, which `T` would resolve on demand only, e.g. via `Lazy` or `AllRegistered`.
+    /// ```
+    /// use {minfac::{Lazy, LazyService, ServiceCollection}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(1u8));
+    /// collection.register_shared(|| Arc::new(2u16));
+    /// collection
+    ///     .with::<Lazy<Arc<u8>>>()
+    ///     .register(|x: LazyService<Arc<u8>>| x);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let lazy = provider.resolve_subgraph::<LazyService<Arc<u8>>>();
+    /// assert!(lazy.is_some());
+    /// assert_eq!(vec!["alloc::sync::Arc<u16>"], provider.uninitialized_shared());
+    /// ```
+    pub fn resolve_subgraph<T: Identifyable<TS::Id>>(&self) -> Option<T> {
+        let position = self.precheck_context().positions(&T::get_id()).last()?;
+        let producers = self.get_producers();
+        let dependencies = &self.immutable_state.dependencies;
+        let mut visited = alloc::vec![false; producers.len()];
+        let mut pending = alloc::vec![position];
+        while let Some(current) = pending.pop() {
+            if core::mem::replace(&mut visited[current], true) {
+                continue;
+            }
+            unsafe { producers[current].get_shared(self) };
+            if let Some(x) = dependencies.get(current) {
+                pending.extend(x.iter().copied());
+            }
+        }
+        self.get::<T>()
+    }
+
+    /// Lists the services, which resolving the last registered `T` constructs, without constructing any of them.
+    /// Dependencies are listed before their dependents, so the last entry is `T` itself. Like `resolve_subgraph`,
+    /// dependencies resolved on demand only, e.g. via `Lazy`, are included. Transient services are listed each time
+    /// they are constructed, e.g. twice if two dependents use them. Shared services are listed once and are only
+    /// constructed, if they weren't resolved before.
+    /// ```
+    /// use {minfac::{Registered, ServiceCollection}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(1u8));
+    /// collection.with::<Registered<Arc<u8>>>().register(|x| *x as u16);
+    /// collection.with::<(Registered<u16>, Registered<Arc<u8>>)>().register(|(a, b)| a as u32 + *b as u32);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let plan = provider.explain::<u32>().unwrap();
+    /// assert_eq!(vec!["alloc::sync::Arc<u8>", "u16", "u32"], plan.type_names().collect::<Vec<_>>());
+    /// assert_eq!(vec!["alloc::sync::Arc<u8>"], plan.shared().collect::<Vec<_>>());
+    /// assert_eq!(vec!["alloc::sync::Arc<u8>"], provider.uninitialized_shared());
+    /// ```
+    pub fn explain<T: Identifyable<TS::Id>>(&self) -> Option<ResolutionPlan> {
+        let position = self.precheck_context().positions(&T::get_id()).last()?;
+        let state = &self.immutable_state;
+        // Only shared services are constructed once, transient ones are planned for each dependent
+        let mut planned_shared = alloc::vec![false; state.types.len()];
+        let mut steps = Vec::new();
+        // The flag is set, once all dependencies of the position are planned
+        let mut pending = alloc::vec![(position, false)];
+        while let Some((current, is_planned)) = pending.pop() {
+            if is_planned {
+                steps.push((state.type_names[current], state.kinds[current]));
+                continue;
+            }
+            if state.kinds[current] == ServiceKind::Shared
+                && core::mem::replace(&mut planned_shared[current], true)
+            {
+                continue;
+            }
+            pending.push((current, true));
+            if let Some(x) = state.dependencies.get(current) {
+                pending.extend(x.iter().rev().map(|x| (*x, false)));
+            }
+        }
+        Some(ResolutionPlan { steps })
+    }
+
+    /// Returns true if both ServiceProviders refer to the same state, analogous to `Arc::ptr_eq`.
+    /// Providers built separately, even from the same ServiceProviderFactory, are never equal.
+    /// ```
+    /// use minfac::{ServiceCollection, WeakServiceProvider};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<WeakServiceProvider>().register(|p| p);
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// let weak = provider.get::<WeakServiceProvider>().unwrap();
+    ///
+    /// assert!(weak.ptr_eq(&provider));
+    /// assert!(provider.ptr_eq(&weak));
+    /// ```
+    pub fn ptr_eq(&self, other: &ServiceProvider<TS>) -> bool {
+        core::ptr::eq(&*self.immutable_state, &*other.immutable_state)
+            && core::ptr::eq(&*self.service_states, &*other.service_states)
+    }
+
+    /// Returns true if both ServiceProviders were built by the same ServiceProviderFactory or
+    /// ServiceCollection::build(), even if their shared services differ, e.g. to cache per factory.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let factory = ServiceCollection::new().build_factory::<i32>().expect("Configuration is valid");
+    /// let first = factory.build(1);
+    /// let second = factory.build(2);
+    /// let other = ServiceCollection::new().build().expect("Configuration is valid");
+    ///
+    /// assert!(first.same_build(&second));
+    /// assert!(!first.ptr_eq(&second));
+    /// assert!(!first.same_build(&other));
+    /// ```
+    pub fn same_build(&self, other: &ServiceProvider<TS>) -> bool {
+        core::ptr::eq(&*self.immutable_state, &*other.immutable_state)
+    }
+
+    /// Leaks the ServiceProvider, so it lives until the program ends, e.g. for handlers of a server which
+    /// require `'static`. Because it's never dropped, WeakServiceProviders and shared services may be kept
+    /// anywhere without calling the minfac::error_handler(). Shared services are never finalized or dropped.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 42);
+    /// let provider: &'static _ = collection.build().expect("Configuration is valid").leak_static();
+    ///
+    /// let handler = std::thread::spawn(|| provider.get::<i32>());
+    /// assert_eq!(Some(42), handler.join().unwrap());
+    /// ```
+    pub fn leak_static(self) -> &'static ServiceProvider<TS> {
+        Box::leak(Box::new(self))
+    }
+
+    /// Returns true for ServiceProviders created by ServiceCollection::build() or ServiceProviderFactory::build().
+    /// Root providers own their shared services, so all derived handles have to be dropped before them.
+    /// ```
+    /// use minfac::{ServiceCollection, WeakServiceProvider};
+    ///
+    /// let provider = ServiceCollection::new().build().expect("Configuration is valid");
+    /// assert!(provider.is_root());
+    /// assert!(!WeakServiceProvider::from(&provider).is_root());
+    /// ```
+    pub fn is_root(&self) -> bool {
+        self.is_root
+    }
+
+    /// Iterates all ancestors of a ServiceProvider built by a factory with parents, starting with the direct
+    /// parent, e.g. request -> app -> root. Providers built by ServiceCollection::build() have no parents.
+    /// The references are borrowed from this provider, so they cannot outlive it.
+    ///
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let root = ServiceCollection::new().build().expect("Configuration is valid");
+    /// let app_factory = ServiceCollection::new().with_parent(&root).build_factory::<i32>().unwrap();
+    /// let app = app_factory.build(1);
+    /// let request_factory = ServiceCollection::new().with_parent(&app).build_factory::<u32>().unwrap();
+    /// let request = request_factory.build(2);
+    ///
+    /// let parents: Vec<_> = request.parents().collect();
+    /// assert_eq!(2, parents.len());
+    /// assert_eq!(Some(1), parents[0].get::<i32>());
+    /// assert_eq!(0, parents[1].parents().count());
+    /// ```
+    pub fn parents(&self) -> impl Iterator<Item = &WeakServiceProvider<TS>> {
+        let mut pending: Vec<_> = self.immutable_state.parents.iter().rev().collect();
+        core::iter::from_fn(move || {
+            let next = pending.pop()?;
+            pending.extend(next.0.immutable_state.parents.iter().rev());
+            Some(next)
+        })
+    }
+
+    /// Drops all initialized shared services of this provider, so they are created again on their next
+    /// resolution. Finalizers run as if the provider was dropped. Values cached with `memoize` and the values
+    /// of all `ScopeLocal` slots are cleared first, because they might hold shared services. Fails without
+    /// resetting anything else if WeakServiceProviders are still alive or any shared service is still
+    /// referenced, so no second instance is created while the old one lives on. This includes references held
+    /// by other shared services, because the provider can't tell them apart from references held elsewhere.
+    ///
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42i32));
+    /// let mut provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let first = provider.get::<Arc<i32>>().unwrap();
+    /// assert!(provider.reset_shared().is_err(), "first is still referenced");
+    /// drop(first);
+    ///
+    /// provider.get::<Arc<i32>>();
+    /// assert!(provider.reset_shared().is_ok());
+    /// let mut initialized = 0;
+    /// provider.for_each_initialized_shared(|_, _| initialized += 1);
+    /// assert_eq!(0, initialized);
+    /// ```
+    pub fn reset_shared(&mut self) -> Result<(), LifetimeError> {
+        let strong_count = RArc::strong_count(&self.service_states);
+        let service_states = RArc::get_mut(&mut self.service_states).ok_or_else(|| {
+            LifetimeError::new(OutlivedLifetimeErrorVariants::WeakServiceProvider {
+                remaining_references: strong_count - 1,
+            })
+        })?;
+        service_states.release_caches();
+        let outstanding = service_states.referenced_shared_services();
+        if outstanding.len > 0 {
+            return Err(LifetimeError::new(
+                OutlivedLifetimeErrorVariants::SharedServices(outstanding),
+            ));
+        }
+        let errors = service_states.release_shared_services();
+        if errors.len > 0 {
+            Err(LifetimeError::new(
+                OutlivedLifetimeErrorVariants::SharedServices(errors),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of shared services, which store their state in this provider, no matter if they are initialized.
+    /// Shared services inherited from a parent are not counted, because their state lives in the parent.
+    pub fn shared_service_count(&self) -> usize {
+        self.service_states.shared_services.len()
+    }
+
+    /// Visits all shared services which are initialized already, passing their type name and a type-erased handle.
+    /// Shared services which were never resolved are skipped.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42i32));
+    /// collection.register_shared(|| Arc::new(42i64));
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// provider.get::<Arc<i32>>();
+    ///
+    /// let mut names = Vec::new();
+    /// provider.for_each_initialized_shared(|name, service| {
+    ///     names.push(name);
+    ///     assert_eq!(Some(42), service.try_clone_as::<i32>().map(|x| *x));
+    /// });
+    /// assert_eq!(vec!["alloc::sync::Arc<i32>"], names);
+    /// ```
+    pub fn for_each_initialized_shared(
+        &self,
+        mut visitor: impl FnMut(&'static str, &ArcAutoFreePointer),
+    ) {
+        for x in self.service_states.initialized_shared_services() {
+            visitor(x.type_name, &x.inner);
+        }
+    }
+
+    /// Counts the transient services of zero sized types like `()`, which were constructed by this ServiceProvider and
+    /// all its clones so far. They are usually dummy dependencies like `register(|_| ())`, whose construction is cheap,
+    /// but not necessarily the one of their dependencies. With the `log` feature, the count is logged when the root
+    /// ServiceProvider is dropped. This is only available with debug_assertions or the `runtime_checks` feature, so it
+    /// doesn't cost anything in releases.
+    /// ```
+    /// # // #[cfg(debug_assertions)] is still enabled for doctest, but not for the actual library in --release
+    /// # #[cfg(feature = "runtime_checks")]
+    /// # fn main() {
+    /// let mut collection = minfac::ServiceCollection::new();
+    /// collection.register(|| ());
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// provider.get::<()>();
+    /// assert_eq!(1, provider.zero_sized_constructions());
+    /// # }
+    /// # #[cfg(not(feature = "runtime_checks"))]
+    /// # fn main() {}
+    /// ```
+    #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+    pub fn zero_sized_constructions(&self) -> usize {
+        self.service_states
+            .zero_sized_constructions
+            .load(Ordering::Relaxed)
+    }
+
+    #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+    pub(crate) fn count_zero_sized_construction(&self) {
+        self.service_states
+            .zero_sized_constructions
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Lists the type names of shared services, which weren't resolved by this ServiceProvider so far.
+    /// This helps to find expensive registrations, which are never used. Services inherited from
+    /// a parent are listed by the parent.
+    /// ```
+    /// use {minfac::ServiceCollection, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_shared(|| Arc::new(42i32));
+    /// collection.register_shared(|| Arc::new(42i64));
+    /// let provider = collection.build().expect("Configuration is valid");
+    /// provider.get::<Arc<i32>>();
+    ///
+    /// assert_eq!(vec!["alloc::sync::Arc<i64>"], provider.uninitialized_shared());
+    /// ```
+    pub fn uninitialized_shared(&self) -> Vec<&'static str> {
+        self.service_states
+            .shared_services
+            .iter()
+            .zip(self.immutable_state.shared_type_names.iter())
+            .filter(|(state, _)| state.get().is_none())
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Describes all services including inherited ones in the order they are stored, e.g. for tools
+    /// visualizing the configuration or admin endpoints of a server.
+    /// ```
+    /// use {minfac::{ServiceCollection, ServiceKind}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// collection.register_shared(|| Arc::new(2u16));
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let shared = provider.describe().into_iter().find(|x| x.kind == ServiceKind::Shared).unwrap();
+    /// assert_eq!("alloc::sync::Arc<u16>", shared.type_name);
+    /// let source = provider.get_with_source::<Arc<u16>>().map(|(_, pos)| pos);
+    /// assert_eq!(Some(shared.index), source);
+    /// ```
+    pub fn describe(&self) -> Vec<ServiceDescriptor> {
+        let state = &self.immutable_state;
+        state
+            .type_names
+            .iter()
+            .zip(state.kinds.iter())
+            .enumerate()
+            .map(|(index, (type_name, kind))| ServiceDescriptor {
+                index,
+                type_name,
+                kind: *kind,
+            })
+            .collect()
+    }
+
+    /// Returns where the service resolved by `get::<T>()` is registered, or None if `T` isn't registered.
+    /// Services inherited from a grandparent are reported as `Parent` with the position of the parent
+    /// they were inherited through.
+    /// ```
+    /// use minfac::{ServiceCollection, ServiceOrigin};
+    ///
+    /// let mut parent_collection = ServiceCollection::new();
+    /// parent_collection.register(|| 1u8);
+    /// let parent = parent_collection.build().expect("Configuration is valid");
+    ///
+    /// let mut child_collection = ServiceCollection::new();
+    /// child_collection.register(|| 2u16);
+    /// let factory = child_collection
+    ///     .with_parent(&parent)
+    ///     .build_factory::<u32>()
+    ///     .expect("Configuration is valid");
+    /// let child = factory.build(3u32);
+    ///
+    /// assert_eq!(Some(ServiceOrigin::Parent(0)), child.origin_of::<u8>());
+    /// assert_eq!(Some(ServiceOrigin::Local), child.origin_of::<u16>());
+    /// assert_eq!(Some(ServiceOrigin::Base), child.origin_of::<u32>());
+    /// assert_eq!(None, child.origin_of::<u64>());
+    /// ```
+    pub fn origin_of<T: Identifyable<TS::Id>>(&self) -> Option<ServiceOrigin> {
+        let pos = self.precheck_context().positions(&T::get_id()).last()?;
+        Some(
+            self.immutable_state
+                .origins
+                .get(pos)
+                .copied()
+                .unwrap_or(ServiceOrigin::Local),
+        )
+    }
+
+    /// Lists all services resolvable from this ServiceProvider including inherited ones, so a plugin's
+    /// collection can be checked against it with `ServiceCollection::check_against` before it's registered.
+    pub fn export_manifest(&self) -> TypeManifest<TS> {
+        TypeManifest {
+            services: self
+                .immutable_state
+                .types
+                .iter()
+                .zip(self.immutable_state.type_names.iter())
+                .zip(self.immutable_state.kinds.iter())
+                .map(|((id, name), kind)| (*id, *name, *kind))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn resolve<T: Resolvable<TS>>(&self) -> T::Item<'_> {
         T::resolve(self)
     }
 
+    pub(crate) fn scope_local_slot<T: Send + 'static>(&self) -> &ScopeLocalSlot<T> {
+        let id = TypeId::of::<T>();
+        let mut slots = self
+            .service_states
+            .scope_locals
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let slot = match slots.iter().find(|(x, _)| *x == id) {
+            Some((_, slot)) => slot,
+            None => {
+                slots.push((id, Box::new(ScopeLocalSlot::<T>(Mutex::new(None)))));
+                &slots.last().unwrap().1
+            }
+        };
+        let slot = slot
+            .downcast_ref::<ScopeLocalSlot<T>>()
+            .expect("Slots are stored with the TypeId of their value");
+        // Boxes don't move when the Vec grows and slots are only removed through &mut self, which can't coexist with
+        // the returned reference
+        unsafe { &*(slot as *const ScopeLocalSlot<T>) }
+    }
+
     pub(crate) fn get_producers(&self) -> &RVec<UntypedFn<TS>> {
         &self.immutable_state.producers
     }
 
     pub(crate) fn new(
         immutable_state: RArc<ServiceProviderImmutableState<TS>>,
-        shared_services: RVec<OnceLock<TypeNamed<ArcAutoFreePointer>>>,
+        shared_services_count: usize,
         base: Option<AutoFreePointer>,
     ) -> Self {
-        Self {
+        let mut shared_services = RVec::with_capacity(shared_services_count);
+        shared_services.extend((0..shared_services_count).map(|_| OnceLock::default()));
+        Self::with_shared_services(immutable_state, shared_services, base)
+    }
+
+    /// Same as `new`, but fails instead of aborting if the shared services cannot be allocated
+    pub(crate) fn try_new(
+        immutable_state: RArc<ServiceProviderImmutableState<TS>>,
+        shared_services_count: usize,
+        base: Option<AutoFreePointer>,
+    ) -> Result<Self, TryReserveError> {
+        let mut shared_services = Vec::new();
+        shared_services.try_reserve_exact(shared_services_count)?;
+        shared_services.extend((0..shared_services_count).map(|_| OnceLock::default()));
+        Ok(Self::with_shared_services(
             immutable_state,
+            shared_services.into(),
+            base,
+        ))
+    }
 
+    fn with_shared_services(
+        immutable_state: RArc<ServiceProviderImmutableState<TS>>,
+        shared_services: RVec<OnceLock<SharedService>>,
+        base: Option<AutoFreePointer>,
+    ) -> Self {
+        Self {
             service_states: RArc::new(ServiceProviderMutableState {
                 shared_services,
                 base,
+                memoized: Mutex::new(Vec::new()),
+                scope_locals: Mutex::new(Vec::new()),
+                #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+                zero_sized_constructions: AtomicUsize::new(0),
+                _libraries: immutable_state._libraries.clone(),
             }),
-            #[cfg(debug_assertions)]
+            immutable_state,
             is_root: true,
         }
     }
 
     pub(crate) fn build_service_producer_for_base<T: Identifyable<TS::Id> + Clone + Send + Sync>(
     ) -> UntypedFnFactory<TS> {
-        extern "C" fn factory<
+        extern "C-unwind" fn factory<
             T: Identifyable<TS::Id> + Clone + 'static + Send + Sync,
             TS: Strategy + 'static,
         >(
-            stage_1_data: AutoFreePointer,
+            _stage_1_data: &AutoFreePointer,
             _ctx: &mut UntypedFnFactoryContext<TS>,
         ) -> InternalBuildResult<TS> {
-            extern "C" fn creator<
+            extern "C-unwind" fn creator<
                 T: Identifyable<TS::Id> + Clone + 'static + Send + Sync,
                 TS: Strategy + 'static,
             >(
@@ -166,7 +1132,11 @@ impl<TS: Strategy + 'static> ServiceProvider<TS> {
                     None => panic!("Expected ServiceProviderFactory to set a value for `base`"),
                 }
             }
-            Ok(UntypedFn::create(creator::<T, TS>, stage_1_data)).into()
+            Ok(UntypedFn::create(
+                creator::<T, TS>,
+                AutoFreePointer::no_alloc(core::ptr::null()),
+            ))
+            .into()
         }
 
         UntypedFnFactory::no_alloc(std::ptr::null(), factory::<T, TS>)
@@ -175,32 +1145,159 @@ impl<TS: Strategy + 'static> ServiceProvider<TS> {
     pub(crate) fn get_or_initialize_pos<T: Any + Send + Sync, TFn: Fn() -> Arc<T>>(
         &self,
         index: usize,
+        teardown: SharedServiceTeardown,
         initializer: TFn,
-    ) -> Arc<T> {
-        let pointer = self
-            .service_states
-            .shared_services
-            .get(index)
-            .unwrap()
-            .get_or_init(|| TypeNamed {
+    ) -> &ArcAutoFreePointer {
+        let cell = self.service_states.shared_services.get(index).unwrap();
+        if let Some(service) = cell.get() {
+            return &service.inner;
+        }
+        // Initializing the same OnceLock reentrantly would deadlock, so it panics instead
+        let _guard = InitializationGuard::enter(cell, type_name::<Arc<T>>());
+        &cell
+            .get_or_init(|| SharedService {
                 inner: ArcAutoFreePointer::new(initializer()),
                 type_name: type_name::<Arc<T>>(),
-            });
+                teardown,
+            })
+            .inner
+    }
+}
+
+std::thread_local! {
+    // Shared services, which are currently initialized by this thread
+    static INITIALIZING: core::cell::RefCell<Vec<*const OnceLock<SharedService>>> =
+        const { core::cell::RefCell::new(Vec::new()) };
+}
+
+struct InitializationGuard;
+
+impl InitializationGuard {
+    fn enter(cell: &OnceLock<SharedService>, type_name: &'static str) -> Self {
+        let cell = cell as *const _;
+        let is_reentrant = INITIALIZING.with(|x| {
+            let mut initializing = x.borrow_mut();
+            let is_reentrant = initializing.contains(&cell);
+            if !is_reentrant {
+                initializing.push(cell);
+            }
+            is_reentrant
+        });
+        if is_reentrant {
+            panic!("Shared service {type_name} was resolved while initializing itself, e.g. via WeakServiceProvider::get");
+        }
+        Self
+    }
+}
+
+impl Drop for InitializationGuard {
+    // Also runs if the initializer panics, so the service can be initialized again
+    fn drop(&mut self) {
+        INITIALIZING.with(|x| x.borrow_mut().pop());
+    }
+}
+
+std::thread_local! {
+    // Contexts of ServiceProvider::with_context, which are currently running on this thread
+    static CONTEXTS: core::cell::RefCell<Vec<Box<dyn Any>>> =
+        const { core::cell::RefCell::new(Vec::new()) };
+}
+
+struct ContextGuard;
+
+impl ContextGuard {
+    fn enter(context: Box<dyn Any>) -> Self {
+        CONTEXTS.with(|x| x.borrow_mut().push(context));
+        Self
+    }
+}
+
+impl Drop for ContextGuard {
+    // Also runs if the scope panics, so outer contexts become visible again
+    fn drop(&mut self) {
+        let context = CONTEXTS.with(|x| x.borrow_mut().pop());
+        // Dropped after the borrow ends, because its drop might use contexts itself
+        drop(context);
+    }
+}
+
+/// Returns a clone of the innermost context of type `C`, see `ServiceProvider::with_context`
+pub(crate) fn current_context<C: Clone + 'static>() -> Option<C> {
+    CONTEXTS.with(|x| {
+        x.borrow()
+            .iter()
+            .rev()
+            .find_map(|context| context.downcast_ref::<C>())
+            .cloned()
+    })
+}
+
+#[cfg(feature = "resolution_depth_limit")]
+static MAX_RESOLUTION_DEPTH: core::sync::atomic::AtomicUsize =
+    core::sync::atomic::AtomicUsize::new(256);
+
+#[cfg(feature = "resolution_depth_limit")]
+std::thread_local! {
+    // Number of nested resolutions on this thread, across all ServiceProviders
+    static RESOLUTION_DEPTH: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+/// Limits how deep resolutions may be nested within a single `get` and returns the previous limit.
+/// Build-time cycle detection cannot see services resolved dynamically, e.g. via an injected
+/// WeakServiceProvider. Exceeding the limit panics instead of overflowing the stack. Unlike lifetime errors,
+/// it isn't passed to the minfac::error_handler(). The default is 256.
+/// ```
+/// use minfac::{set_max_resolution_depth, ResolutionError, ServiceCollection, WeakServiceProvider};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection
+///     .with::<WeakServiceProvider>()
+///     .register(|p| p.get::<i32>().unwrap() + 1);
+/// let provider = collection.build().expect("Configuration is valid");
+///
+/// let previous = set_max_resolution_depth(16);
+/// assert!(matches!(provider.try_get::<i32>(), Err(ResolutionError::Panicked { .. })));
+/// set_max_resolution_depth(previous);
+/// ```
+#[cfg(feature = "resolution_depth_limit")]
+pub fn set_max_resolution_depth(depth: usize) -> usize {
+    MAX_RESOLUTION_DEPTH.swap(depth, core::sync::atomic::Ordering::Relaxed)
+}
 
-        unsafe { pointer.inner.clone_inner::<T>() }
+#[cfg(feature = "resolution_depth_limit")]
+pub(crate) struct ResolutionDepthGuard;
+
+#[cfg(feature = "resolution_depth_limit")]
+impl ResolutionDepthGuard {
+    pub(crate) fn enter(type_name: &'static str) -> Self {
+        let depth = RESOLUTION_DEPTH.with(|x| x.get());
+        let max_depth = MAX_RESOLUTION_DEPTH.load(core::sync::atomic::Ordering::Relaxed);
+        if depth >= max_depth {
+            panic!("Resolving {type_name} exceeded the maximum resolution depth of {max_depth}, e.g. because of unbounded resolutions via WeakServiceProvider::get");
+        }
+        RESOLUTION_DEPTH.with(|x| x.set(depth + 1));
+        Self
+    }
+}
+
+#[cfg(feature = "resolution_depth_limit")]
+impl Drop for ResolutionDepthGuard {
+    // Also runs while unwinding, so a caught panic doesn't leave the depth increased
+    fn drop(&mut self) {
+        RESOLUTION_DEPTH.with(|x| x.set(x.get() - 1));
     }
 }
 
-/// Weak ServiceProviders have the same public API as ServiceProviders, but cannot outlive
-/// their original ServiceProvider. If they do, the minfac::ERROR_HANDLER is called.
+/// Weak ServiceProviders dereference to a ServiceProvider, so they have the same public API, but cannot outlive
+/// their original ServiceProvider. If they do, the minfac::error_handler() is called.
 ///
 /// In contrast to std::sync::Arc<T> / std::sync::Weak<T>, WeakServiceProviders prevent
-/// their parent from being vanished, if minfac::ERROR_HANDLER doesn't panic
+/// their parent from being vanished, if minfac::error_handler() doesn't panic
 pub struct WeakServiceProvider<TS: Strategy + 'static = AnyStrategy>(ServiceProvider<TS>);
 
 impl<TS: Strategy + 'static> WeakServiceProvider<TS> {
-    fn resolve<T: Resolvable<TS>>(&self) -> T::Item {
-        T::resolve(&self.0)
+    pub(crate) fn as_provider(&self) -> &ServiceProvider<TS> {
+        &self.0
     }
 
     /// Reference for Arc<self> must be kept for the entire lifetime of the new ServiceProvider
@@ -213,36 +1310,36 @@ impl<TS: Strategy + 'static> WeakServiceProvider<TS> {
             .producers
             .iter()
             .zip(static_self.0.immutable_state.types.iter())
-            .map(move |(parent_producer, parent_type)| {
-                // parents are part of ServiceProviderImmutableState to live as long as the inherited UntypedFn
-                extern "C" fn factory<TS: Strategy + 'static>(
-                    outer_ctx: AutoFreePointer,
-                    _: &mut UntypedFnFactoryContext<TS>,
-                ) -> InternalBuildResult<TS> {
-                    let ptr = outer_ctx.get_pointer() as *mut OuterContextType<TS>;
-                    unsafe {
-                        let (parent_producer, static_self) = &*ptr;
-                        Ok(parent_producer.bind(&static_self.0)).into()
+            .zip(static_self.0.immutable_state.type_names.iter())
+            .zip(static_self.0.immutable_state.kinds.iter())
+            .map(
+                move |(((parent_producer, parent_type), parent_type_name), kind)| {
+                    // parents are part of ServiceProviderImmutableState to live as long as the inherited UntypedFn
+                    extern "C-unwind" fn factory<TS: Strategy + 'static>(
+                        outer_ctx: &AutoFreePointer,
+                        _: &mut UntypedFnFactoryContext<TS>,
+                    ) -> InternalBuildResult<TS> {
+                        let ptr = outer_ctx.get_pointer() as *mut OuterContextType<TS>;
+                        unsafe {
+                            let (parent_producer, static_self) = &*ptr;
+                            Ok(parent_producer.bind(&static_self.0)).into()
+                        }
                     }
-                }
-                let factory =
-                    UntypedFnFactory::boxed((parent_producer, static_self), factory::<TS>);
-                ServiceProducer::<TS>::new_with_type(factory, *parent_type)
-            })
+                    let factory =
+                        UntypedFnFactory::boxed((parent_producer, static_self), factory::<TS>);
+                    ServiceProducer::<TS>::new_with_type(factory, *parent_type, parent_type_name)
+                        .with_kind(*kind)
+                },
+            )
     }
+}
 
-    pub fn resolve_unchecked<T: Resolvable<TS>>(&self) -> T::ItemPreChecked {
-        let precheck_key =
-            T::precheck(&self.0.immutable_state.types).expect("Resolve unkwnown service");
-        T::resolve_prechecked(&self.0, &precheck_key)
-    }
+/// Exposes the entire API of the ServiceProvider, so both can't diverge
+impl<TS: Strategy + 'static> Deref for WeakServiceProvider<TS> {
+    type Target = ServiceProvider<TS>;
 
-    pub fn get<T: Identifyable<TS::Id>>(&self) -> Option<T> {
-        self.resolve::<Registered<T>>()
-    }
-
-    pub fn get_all<T: Identifyable<TS::Id>>(&self) -> ServiceIterator<T, TS> {
-        self.resolve::<AllRegistered<T>>()
+    fn deref(&self) -> &ServiceProvider<TS> {
+        &self.0
     }
 }
 
@@ -251,7 +1348,6 @@ impl<TS: Strategy + 'static> Clone for WeakServiceProvider<TS> {
         Self(ServiceProvider::<TS> {
             immutable_state: self.0.immutable_state.clone(),
             service_states: self.0.service_states.clone(),
-            #[cfg(debug_assertions)]
             is_root: false,
         })
     }
@@ -262,70 +1358,516 @@ impl<'a, TS: Strategy + 'static> From<&'a ServiceProvider<TS>> for WeakServicePr
         WeakServiceProvider(ServiceProvider {
             immutable_state: provider.immutable_state.clone(),
             service_states: provider.service_states.clone(),
-            #[cfg(debug_assertions)]
             is_root: false,
         })
     }
 }
 
+/// Service of a ServiceProvider, created by `ServiceProvider::describe`
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceDescriptor {
+    /// Position of the service, like the one returned by `ServiceProvider::get_with_source`
+    pub index: usize,
+    pub type_name: &'static str,
+    pub kind: ServiceKind,
+}
+
+/// Services constructed to resolve a service, created by `ServiceProvider::explain`.
+/// The alternate format `{:#?}` additionally shows, whether a service is shared or transient.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ResolutionPlan {
+    steps: Vec<(&'static str, ServiceKind)>,
+}
+
+impl ResolutionPlan {
+    /// Type names of all services in the order they are constructed
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.steps.iter().map(|(name, _)| *name)
+    }
+
+    /// Type names of the shared services, which are only constructed if they weren't resolved before
+    pub fn shared(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.steps
+            .iter()
+            .filter(|(_, kind)| *kind == ServiceKind::Shared)
+            .map(|(name, _)| *name)
+    }
+
+    /// Type names of the transient services, which are constructed on every resolution
+    pub fn transient(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.steps
+            .iter()
+            .filter(|(_, kind)| *kind == ServiceKind::Transient)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl Debug for ResolutionPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            f.debug_list()
+                .entries(
+                    self.steps
+                        .iter()
+                        .map(|(name, kind)| DebugService(name, *kind)),
+                )
+                .finish()
+        } else {
+            f.debug_list().entries(self.type_names()).finish()
+        }
+    }
+}
+
+/// Services a host provides, created by `ServiceProvider::export_manifest`. Under `StableAbiStrategy`,
+/// ids are stable across compilation units, so plugins can be checked against the host they are loaded into.
+pub struct TypeManifest<TS: Strategy + 'static = AnyStrategy> {
+    pub(crate) services: Vec<(TS::Id, &'static str, ServiceKind)>,
+}
+
+impl<TS: Strategy + 'static> TypeManifest<TS> {
+    /// Returns true, if at least one service with `id` is provided
+    pub fn contains(&self, id: &TS::Id) -> bool {
+        self.services.iter().any(|(x, ..)| x == id)
+    }
+
+    /// Type names of all provided services, in the order they are stored by the ServiceProvider
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.services.iter().map(|(_, name, _)| *name)
+    }
+}
+
+impl<TS: Strategy + 'static> Clone for TypeManifest<TS> {
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+        }
+    }
+}
+
+impl<TS: Strategy + 'static> Debug for TypeManifest<TS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.type_names()).finish()
+    }
+}
+
 pub(crate) struct ServiceProviderImmutableState<TS: Strategy + 'static> {
     types: RVec<TS::Id>,
+    // Kept for every producer for diagnostics like Debug. It's one pointer per service, so it isn't optional
+    type_names: RVec<&'static str>,
+    kinds: RVec<ServiceKind>,
+    // Positions of each distinct id in types, so resolutions don't have to search all services
+    index: PositionIndex<TS>,
     producers: RVec<UntypedFn<TS>>,
     // Unsafe-Code, which generates UntypedFn from parent, relies on the fact that parent ServiceProvider outlives this state
-    _parents: RVec<WeakServiceProvider<TS>>,
+    parents: RVec<WeakServiceProvider<TS>>,
+    // Type name of each shared service state, in the order of ServiceProviderMutableState::shared_services
+    shared_type_names: RVec<&'static str>,
+    // Positions of the services each service depends on, e.g. to initialize only the services required by one type
+    dependencies: RVec<RVec<usize>>,
+    // Where each service is registered, see ServiceProvider::origin_of
+    origins: RVec<ServiceOrigin>,
+    // Types, which are resolvable via ServiceProvider::get_boxed, including the ones of parents
+    boxers: Vec<Boxer<TS>>,
+    // Dynamic libraries, which contain the code of producers. Must be dropped last
+    _libraries: Vec<LibraryHandle>,
 }
 
 impl<TS: Strategy + 'static> ServiceProviderImmutableState<TS> {
     pub(crate) fn new(
         types: RVec<TS::Id>,
+        type_names: RVec<&'static str>,
+        kinds: RVec<ServiceKind>,
+        index: PositionIndex<TS>,
         producers: RVec<UntypedFn<TS>>,
-        _parents: RVec<WeakServiceProvider<TS>>,
+        parents: RVec<WeakServiceProvider<TS>>,
+        _libraries: Vec<LibraryHandle>,
     ) -> Self {
         Self {
             types,
+            type_names,
+            kinds,
+            index,
             producers,
-            _parents,
+            parents,
+            shared_type_names: RVec::new(),
+            dependencies: RVec::new(),
+            origins: RVec::new(),
+            boxers: Vec::new(),
+            _libraries,
         }
     }
+
+    pub(crate) fn with_shared_type_names(mut self, names: RVec<&'static str>) -> Self {
+        self.shared_type_names = names;
+        self
+    }
+
+    pub(crate) fn with_dependencies(mut self, dependencies: RVec<RVec<usize>>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    pub(crate) fn with_origins(mut self, origins: RVec<ServiceOrigin>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    pub(crate) fn with_boxers(mut self, boxers: Vec<Boxer<TS>>) -> Self {
+        self.boxers = boxers;
+        self
+    }
 }
 
 pub(crate) struct ServiceProviderMutableState {
     // Placeholder for the type which is provided when serviceProvider is built from ServiceFactory
     base: Option<AutoFreePointer>,
-    shared_services: RVec<OnceLock<TypeNamed<ArcAutoFreePointer>>>,
+    shared_services: RVec<OnceLock<SharedService>>,
+    // Transient services cached by `ServiceProvider::memoize`, keyed by their Rust type
+    memoized: Mutex<Vec<(TypeId, Box<dyn Any + Send + Sync>)>>,
+    #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+    zero_sized_constructions: AtomicUsize,
+    // Boxed ScopeLocalSlot<T> for each T. References to them are bound to a shared borrow of the ServiceProvider,
+    // so slots must only be removed through &mut access, like `reset_shared` or dropping the state
+    scope_locals: Mutex<Vec<(TypeId, Box<dyn Any + Send + Sync>)>>,
+    // Shared services might be dropped by code of these libraries, so they must be dropped last
+    _libraries: Vec<LibraryHandle>,
+}
+
+/// Runs finalizers of initialized shared services, before their `Arc` is released
+impl Drop for ServiceProviderMutableState {
+    fn drop(&mut self) {
+        drop(self.take_shared_services_by_drop_priority());
+    }
+}
+
+pub(crate) struct SharedService {
+    inner: ArcAutoFreePointer,
+    type_name: &'static str,
+    teardown: SharedServiceTeardown,
+}
+
+/// Configures how a shared service is released, see `SharedBuilder::on_drop` and `SharedBuilder::drop_priority`
+#[derive(Clone, Copy, Default)]
+pub(crate) struct SharedServiceTeardown {
+    pub(crate) finalizer: Option<SharedServiceFinalizer>,
+    pub(crate) drop_priority: i32,
+}
+
+/// Type-erased `fn(&T)`, which is called for the value of an initialized shared service `Arc<T>`
+#[derive(Clone, Copy)]
+pub(crate) struct SharedServiceFinalizer {
+    caller: extern "C-unwind" fn(value: AnyPtr, finalizer: AnyPtr),
+    finalizer: AnyPtr,
+}
+
+unsafe impl Send for SharedServiceFinalizer {}
+unsafe impl Sync for SharedServiceFinalizer {}
+
+impl SharedServiceFinalizer {
+    pub(crate) fn new<T>(finalizer: fn(&T)) -> Self {
+        extern "C-unwind" fn caller<T>(value: AnyPtr, finalizer: AnyPtr) {
+            let finalizer: fn(&T) = unsafe { core::mem::transmute(finalizer) };
+            finalizer(unsafe { &*(value as *const T) })
+        }
+        Self {
+            caller: caller::<T>,
+            finalizer: finalizer as AnyPtr,
+        }
+    }
+
+    /// Unsafe constraint: `value` must point to an `Arc<T>` with the same T the finalizer was created with
+    unsafe fn call(&self, value: &ArcAutoFreePointer) {
+        (self.caller)(value.get_pointer(), self.finalizer)
+    }
+}
+
+impl ServiceProviderMutableState {
+    fn initialized_shared_services(&self) -> impl Iterator<Item = &SharedService> {
+        self.shared_services.iter().filter_map(OnceLock::get)
+    }
+
+    /// Takes all initialized shared services from the highest to the lowest drop priority and runs their finalizers
+    /// in this order. Services with equal priorities keep the order of their state slots.
+    /// Memoized services and scope locals are released beforehand, because they might hold shared services.
+    fn take_shared_services_by_drop_priority(&mut self) -> Vec<SharedService> {
+        self.release_caches();
+        let mut taken: Vec<_> = self
+            .shared_services
+            .iter_mut()
+            .filter_map(OnceLock::take)
+            .collect();
+        taken.sort_by_key(|x| core::cmp::Reverse(x.teardown.drop_priority));
+        for x in taken.iter() {
+            if let Some(finalizer) = &x.teardown.finalizer {
+                // Finalizers are stored together with the Arc<T> they were created for
+                unsafe { finalizer.call(&x.inner) };
+            }
+        }
+        taken
+    }
+
+    /// Drops memoized services and all scope local slots
+    fn release_caches(&mut self) {
+        drop(core::mem::take(
+            self.memoized
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        ));
+        drop(core::mem::take(
+            self.scope_locals
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        ));
+    }
+
+    /// Returns the initialized shared services, which are referenced by anything but their slot
+    fn referenced_shared_services(&self) -> DanglingCheckerResults {
+        self.shared_services
+            .iter()
+            .filter_map(OnceLock::get)
+            .filter_map(|x| {
+                let remaining = x.inner.downgrade().strong_count() - 1;
+                (remaining > 0).then(|| DanglingCheckerResult::new(remaining, x.type_name))
+            })
+            .collect()
+    }
+
+    /// Runs finalizers and releases all initialized shared services, leaving their slots uninitialized.
+    /// Returns the services which are still kept alive by references from outside the ServiceProvider.
+    fn release_shared_services(&mut self) -> DanglingCheckerResults {
+        let released = self.take_shared_services_by_drop_priority();
+        let checkers: Vec<_> = released
+            .iter()
+            .map(|x| TypeNamed {
+                inner: x.inner.downgrade(),
+                type_name: x.type_name,
+            })
+            .collect();
+        drop(released);
+
+        checkers
+            .into_iter()
+            .filter_map(|x| {
+                (x.inner.strong_count() > 0)
+                    .then(|| DanglingCheckerResult::new(x.inner.strong_count(), x.type_name))
+            })
+            .collect()
+    }
+}
+
+/// Mutable slot for a value of type `T`, which is local to a ServiceProvider, see `ScopeLocal<T>`
+pub struct ScopeLocalSlot<T>(Mutex<Option<T>>);
+
+impl<T> ScopeLocalSlot<T> {
+    /// Stores `value`, returning the previous value
+    pub fn set(&self, value: T) -> Option<T> {
+        self.lock().replace(value)
+    }
+
+    /// Removes the value, leaving the slot empty
+    pub fn take(&self) -> Option<T> {
+        self.lock().take()
+    }
+
+    /// Returns a clone of the value
+    pub fn get(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<T>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Defers the construction of the last registered `T` until `get()` is called, e.g. for expensive
+/// dependencies which are rarely used. Each call creates a new instance for transient services.
+///
+/// LazyServices hold a WeakServiceProvider, so they must not outlive the ServiceProvider they
+/// were resolved from. Otherwise, the minfac::error_handler() is called when the provider is dropped.
+pub struct LazyService<T, TS: Strategy + 'static = AnyStrategy> {
+    pos: usize,
+    provider: WeakServiceProvider<TS>,
+    item_type: PhantomData<T>,
+}
+
+impl<T, TS: Strategy + 'static> LazyService<T, TS> {
+    /// pos must be the position of a service of type `T` within provider
+    pub(crate) unsafe fn new(provider: WeakServiceProvider<TS>, pos: usize) -> Self {
+        Self {
+            pos,
+            provider,
+            item_type: PhantomData,
+        }
+    }
+}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> LazyService<T, TS> {
+    /// Resolves the service. The service is constructed on every call, unless it's a shared service.
+    pub fn get(&self) -> T {
+        unsafe { crate::resolvable::resolve_unchecked::<TS, T>(&self.provider.0, self.pos) }
+    }
+}
+
+/// Creates a new `T` from arguments of the caller and the dependencies declared by
+/// `ServiceBuilder::register_factory_fn`. Dependencies are resolved on each call of `create()`.
+///
+/// Factories hold a WeakServiceProvider, so they must not outlive the ServiceProvider they
+/// were resolved from. Otherwise, the minfac::error_handler() is called when the provider is dropped.
+pub struct Factory<Args, T, TS: Strategy + 'static = AnyStrategy> {
+    provider: WeakServiceProvider<TS>,
+    // Points into the producers of provider, which are kept alive by it
+    context: *const AutoFreePointer,
+    create: fn(&ServiceProvider<TS>, &AutoFreePointer, Args) -> T,
+}
+
+// Only refers to the ServiceProvider and immutable data owned by it, which are both Send + Sync
+unsafe impl<Args, T, TS: Strategy + 'static> Send for Factory<Args, T, TS> {}
+unsafe impl<Args, T, TS: Strategy + 'static> Sync for Factory<Args, T, TS> {}
+
+impl<Args, T, TS: Strategy + 'static> Factory<Args, T, TS> {
+    /// context must be owned by a producer of provider and be accepted by create
+    pub(crate) unsafe fn new(
+        provider: WeakServiceProvider<TS>,
+        context: *const AutoFreePointer,
+        create: fn(&ServiceProvider<TS>, &AutoFreePointer, Args) -> T,
+    ) -> Self {
+        Self {
+            provider,
+            context,
+            create,
+        }
+    }
+
+    pub fn create(&self, args: Args) -> T {
+        (self.create)(self.provider.as_provider(), unsafe { &*self.context }, args)
+    }
+}
+
+/// Service `T`, which cannot outlive the ServiceProvider `'p` it was resolved from. Storing the `Scoped`
+/// beyond the provider's scope is a compile error instead of a call to the minfac::error_handler() at runtime.
+/// ```compile_fail
+/// use {minfac::{ServiceCollection, WeakServiceProvider}};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.with::<WeakServiceProvider>().register(|p| p);
+/// let provider = collection.build().unwrap();
+/// let weak = provider.get_scoped::<WeakServiceProvider>().unwrap();
+/// drop(provider);
+/// weak.is_root();
+/// ```
+/// Only the `Scoped` itself is bound to `'p`. Clones of `T` aren't, so they are checked at runtime like any
+/// other service:
+/// ```
+/// use {minfac::{ServiceCollection, WeakServiceProvider}};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.with::<WeakServiceProvider>().register(|p| p);
+/// let provider = collection.build().unwrap();
+/// let escaped: WeakServiceProvider = (*provider.get_scoped::<WeakServiceProvider>().unwrap()).clone();
+/// // Dropping provider before escaped would call the minfac::error_handler()
+/// drop(escaped);
+/// ```
+pub struct Scoped<'p, T> {
+    inner: T,
+    provider: PhantomData<&'p ()>,
+}
+
+impl<T> Scoped<'_, T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            provider: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for Scoped<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Scoped<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: Iterator> Iterator for Scoped<'_, T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
 }
 
 /// Type used to retrieve all instances `T` of a `ServiceProvider`.
 /// Services are built just in time when calling `next()`
 pub struct ServiceIterator<T, TS: Strategy + 'static = AnyStrategy> {
-    next_pos: Option<usize>,
+    next_pos: usize,
+    // Exclusive end of the positions of `T`, so `next()` doesn't need to compare ids
+    end: usize,
     provider: WeakServiceProvider<TS>,
     item_type: PhantomData<T>,
 }
 
 impl<T, TS: Strategy + 'static> ServiceIterator<T, TS> {
-    pub(crate) fn new(provider: WeakServiceProvider<TS>, next_pos: Option<usize>) -> Self {
+    /// positions must contain services of type `T` only
+    pub(crate) fn new(provider: WeakServiceProvider<TS>, positions: Range<usize>) -> Self {
         Self {
             provider,
             item_type: PhantomData,
-            next_pos,
+            next_pos: positions.start,
+            end: positions.end,
         }
     }
 }
 
-impl<'a, TS: Strategy + 'static, T: Identifyable<TS::Id>> Iterator for ServiceIterator<T, TS> {
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> ServiceIterator<T, TS> {
+    /// Yields each remaining service together with the position of its producer. Unlike `enumerate()`,
+    /// positions are the same as the ones returned by `get_with_source` or `describe`, so they can be used
+    /// to correlate services with other data, e.g. a routing table.
+    /// ```
+    /// use minfac::ServiceCollection;
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register(|| 1u8);
+    /// collection.register(|| 1i32);
+    /// collection.register(|| 2i32);
+    /// let provider = collection.build().expect("Configuration is valid");
+    ///
+    /// let services: Vec<_> = provider.get_all::<i32>().enumerate_indices().collect();
+    /// let positions: Vec<_> = provider.describe().into_iter()
+    ///     .filter(|s| s.type_name == "i32")
+    ///     .map(|s| s.index)
+    ///     .collect();
+    /// assert_eq!(vec![(positions[0], 1), (positions[1], 2)], services);
+    /// ```
+    pub fn enumerate_indices(
+        self,
+    ) -> impl DoubleEndedIterator<Item = (usize, T)> + ExactSizeIterator {
+        let provider = self.provider;
+        (self.next_pos..self.end).map(move |i| {
+            (i, unsafe {
+                crate::resolvable::resolve_unchecked::<TS, T>(&provider.0, i)
+            })
+        })
+    }
+}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> Iterator for ServiceIterator<T, TS> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.next_pos.map(|i| {
-            self.next_pos = self
-                .provider
-                .0
-                .immutable_state
-                .producers
-                .get(i + 1)
-                .and_then(|next| (next.get_result_type_id() == &T::get_id()).then(|| i + 1));
-
+        (self.next_pos < self.end).then(|| {
+            let i = self.next_pos;
+            self.next_pos += 1;
             unsafe { crate::resolvable::resolve_unchecked::<TS, T>(&self.provider.0, i) }
         })
     }
@@ -334,30 +1876,45 @@ impl<'a, TS: Strategy + 'static, T: Identifyable<TS::Id>> Iterator for ServiceIt
     where
         Self: Sized,
     {
-        self.next_pos.map(|i| {
-            let pos = binary_search::binary_search_last_by_key(
-                &self.provider.0.immutable_state.producers[i..],
-                &T::get_id(),
-                UntypedFn::<TS>::get_result_type_id,
-            );
-            let pos = pos.expect("to be present if next_pos has value");
-            unsafe { crate::resolvable::resolve_unchecked::<TS, T>(&self.provider.0, i + pos) }
+        (self.next_pos < self.end).then(|| unsafe {
+            crate::resolvable::resolve_unchecked::<TS, T>(&self.provider.0, self.end - 1)
         })
     }
+
     fn count(self) -> usize
     where
         Self: Sized,
     {
-        self.next_pos
-            .map(|i| {
-                let pos = binary_search::binary_search_last_by_key(
-                    &self.provider.0.immutable_state.producers[i..],
-                    &T::get_id(),
-                    UntypedFn::get_result_type_id,
-                )
-                .expect("having at least one item because has next_pos");
-                pos + 1
-            })
-            .unwrap_or(0)
+        self.end - self.next_pos
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next_pos;
+        (len, Some(len))
+    }
+
+    // Skipped services are not constructed
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.next_pos = self.next_pos.saturating_add(n).min(self.end);
+        self.next()
     }
 }
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> DoubleEndedIterator
+    for ServiceIterator<T, TS>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        (self.next_pos < self.end).then(|| {
+            self.end -= 1;
+            unsafe { crate::resolvable::resolve_unchecked::<TS, T>(&self.provider.0, self.end) }
+        })
+    }
+
+    // Skipped services are not constructed
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.end = self.end.saturating_sub(n).max(self.next_pos);
+        self.next_back()
+    }
+}
+
+impl<TS: Strategy + 'static, T: Identifyable<TS::Id>> ExactSizeIterator for ServiceIterator<T, TS> {}
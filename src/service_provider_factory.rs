@@ -2,13 +2,12 @@ use crate::{
     service_provider::ServiceProviderImmutableState,
     strategy::{Identifyable, Strategy},
     untyped::AutoFreePointer,
-    AnyStrategy, GenericServiceCollection, ProducerValidationResult, ServiceProducer,
-    ServiceProvider, WeakServiceProvider,
+    AnyStrategy, GenericServiceCollection, ProducerValidationResult, ServiceOrigin,
+    ServiceProducer, ServiceProvider, WeakServiceProvider,
 };
 use abi_stable::std_types::{RArc, RVec};
-use alloc::vec::Vec;
+use alloc::{collections::TryReserveError, vec::Vec};
 use core::{clone::Clone, marker::PhantomData};
-use std::sync::OnceLock;
 
 /// Performs all checks to build a ServiceProvider on premise that an instance of type T will be available.
 /// Therefore, multiple ServiceProvider with a different base can be created very efficiently.
@@ -26,12 +25,57 @@ use std::sync::OnceLock;
 /// assert_eq!(Some(1i64), provider1.get::<i64>());
 /// assert_eq!(Some(2i64), provider2.get::<i64>());
 /// ```
+///
+/// Factories are cheap to clone and can be registered as instances, so services can create scopes themselves,
+/// e.g. a handler building a ServiceProvider for each request:
+/// ```
+/// use {minfac::{Registered, ServiceCollection, ServiceProviderFactory}, std::sync::Arc};
+///
+/// struct Handler(ServiceProviderFactory<u32>);
+/// impl Handler {
+///     fn handle(&self, request: u32) -> Option<u64> {
+///         self.0.build(request).get::<u64>()
+///     }
+/// }
+///
+/// let mut request_collection = ServiceCollection::new();
+/// request_collection.with::<Registered<u32>>().register(|r| r as u64 * 2);
+/// let scope_factory = request_collection.build_factory::<u32>().expect("Config should be valid");
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register_instance(scope_factory);
+/// collection
+///     .with::<Registered<ServiceProviderFactory<u32>>>()
+///     .register_shared(|f| Arc::new(Handler(f)));
+/// let provider = collection.build().expect("Config should be valid");
+///
+/// let handler = provider.get::<Arc<Handler>>().unwrap();
+/// assert_eq!(Some(2), handler.handle(1));
+/// assert_eq!(Some(4), handler.handle(2));
+/// ```
 pub struct ServiceProviderFactory<T: Clone + Send + Sync, TS: Strategy + 'static = AnyStrategy> {
     service_states_count: usize,
     immutable_state: RArc<crate::service_provider::ServiceProviderImmutableState<TS>>,
     anticipated: PhantomData<T>,
 }
 
+// Derive would require `TS: Clone`, even though only the shared immutable state is cloned
+impl<T: Clone + Send + Sync, TS: Strategy + 'static> Clone for ServiceProviderFactory<T, TS> {
+    fn clone(&self) -> Self {
+        Self {
+            service_states_count: self.service_states_count,
+            immutable_state: self.immutable_state.clone(),
+            anticipated: PhantomData,
+        }
+    }
+}
+
+// Factories are injected into services which create scopes on any thread, so they must stay `Send` and `Sync`
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ServiceProviderFactory<()>>();
+};
+
 pub struct ServiceProviderFactoryBuilder<TS: Strategy + 'static> {
     collection: GenericServiceCollection<TS>,
     providers: RVec<WeakServiceProvider<TS>>,
@@ -49,11 +93,170 @@ impl<TS: Strategy + 'static> ServiceProviderFactoryBuilder<TS> {
             providers,
         }
     }
+    /// Doesn't inherit any parent services of type `T`, so they are neither resolvable by `get` nor by `get_all`
+    /// of the child, unless the child registers them itself. Inherited services depending on `T`
+    /// keep resolving it from their parent.
+    pub fn exclude<T: Identifyable<TS::Id>>(mut self) -> Self {
+        let id = T::get_id();
+        if !self.collection.parent_overrides.contains(&id) {
+            self.collection.parent_overrides.push(id);
+        }
+        self
+    }
+
     pub fn build_factory<T: Identifyable<TS::Id> + Clone + Send + Sync>(
         self,
     ) -> Result<ServiceProviderFactory<T, TS>, super::BuildError<TS>> {
         ServiceProviderFactory::<T, TS>::create(self.collection, self.providers)
     }
+
+    pub fn build_multi_base_factory(self) -> MultiBaseFactory<TS> {
+        MultiBaseFactory::new(self.collection, self.providers)
+    }
+}
+
+// Producers of all parents, which aren't excluded by the child collection
+fn inherited_producers<TS: Strategy + 'static>(
+    collection: &GenericServiceCollection<TS>,
+    parents: &[WeakServiceProvider<TS>],
+) -> Vec<ServiceProducer<TS>> {
+    parents
+        .iter()
+        .enumerate()
+        .flat_map(|(i, parent)| {
+            unsafe { parent.clone_producers() }
+                .map(move |producer| producer.with_origin(ServiceOrigin::Parent(i)))
+        })
+        .filter(|producer| !collection.parent_overrides.contains(&producer.identifier))
+        .collect()
+}
+
+fn base_producer<T: Identifyable<TS::Id> + Clone + Send + Sync, TS: Strategy + 'static>(
+) -> ServiceProducer<TS> {
+    ServiceProducer::<TS>::new::<T>(ServiceProvider::<TS>::build_service_producer_for_base::<T>())
+        .with_origin(ServiceOrigin::Base)
+}
+
+/// Validates `collection` with a producer for the base type `T` in the slot after all registered services.
+/// The slot is released afterwards, so the collection stays unchanged.
+fn validate_with_base<T: Identifyable<TS::Id> + Clone + Send + Sync, TS: Strategy + 'static>(
+    collection: &mut GenericServiceCollection<TS>,
+    parents: RVec<WeakServiceProvider<TS>>,
+) -> Result<(usize, RArc<ServiceProviderImmutableState<TS>>), super::BuildError<TS>> {
+    let parent_service_factories = inherited_producers(collection, &parents);
+    collection.producer_factories.push(base_producer::<T, TS>());
+    let validation = collection.validate_producers(parent_service_factories, None);
+    collection.producer_factories.pop();
+    Ok(create_state(validation?, parents))
+}
+
+fn create_state<TS: Strategy + 'static>(
+    validation: ProducerValidationResult<TS>,
+    parents: RVec<WeakServiceProvider<TS>>,
+) -> (usize, RArc<ServiceProviderImmutableState<TS>>) {
+    let ProducerValidationResult {
+        producers,
+        types,
+        type_names,
+        kinds,
+        index,
+        shared_type_names,
+        dependencies,
+        origins,
+        mut boxers,
+        libraries,
+    } = validation;
+    for parent in parents.iter() {
+        for boxer in parent.as_provider().boxers() {
+            if !boxers.iter().any(|(id, _)| *id == boxer.0) {
+                boxers.push(*boxer);
+            }
+        }
+    }
+
+    let service_states_count = shared_type_names.len();
+    let immutable_state = RArc::new(
+        ServiceProviderImmutableState::<TS>::new(
+            types, type_names, kinds, index, producers, parents, libraries,
+        )
+        .with_shared_type_names(shared_type_names)
+        .with_dependencies(dependencies)
+        .with_origins(origins)
+        .with_boxers(boxers),
+    );
+    (service_states_count, immutable_state)
+}
+
+/// Creates ServiceProviders for any base type from the same registrations, without registering them again.
+/// Inherited and registered producers are collected and ordered once. Each base type is inserted at its
+/// position within them on its first use, which is validated like `build_factory::<T>()` would.
+/// Subsequent builds for the same base type are as cheap as `ServiceProviderFactory::build`.
+/// Unlike `build_factory::<T>()`, the base isn't a source of `alias_all`, because aliases are expanded
+/// before the base type is known.
+/// ```
+/// use {minfac::{AllRegistered, ServiceCollection, ServiceIterator}};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.register(|| 1i32);
+/// collection
+///     .with::<AllRegistered<i32>>()
+///     .register(|i: ServiceIterator<i32>| i.count());
+/// let mut factory = collection.build_multi_base_factory();
+///
+/// assert_eq!(Some(2), factory.build(2i32).expect("i32 is a valid base").get::<usize>());
+/// assert_eq!(Some(1), factory.build("request").expect("&str is a valid base").get::<usize>());
+/// ```
+pub struct MultiBaseFactory<TS: Strategy + 'static = AnyStrategy> {
+    collection: GenericServiceCollection<TS>,
+    parents: RVec<WeakServiceProvider<TS>>,
+    // Producers of the parents and the collection, ordered like in built ServiceProviders
+    ordered: Vec<ServiceProducer<TS>>,
+    factories: Vec<(TS::Id, usize, RArc<ServiceProviderImmutableState<TS>>)>,
+}
+
+impl<TS: Strategy + 'static> MultiBaseFactory<TS> {
+    pub(crate) fn new(
+        collection: GenericServiceCollection<TS>,
+        parents: RVec<WeakServiceProvider<TS>>,
+    ) -> Self {
+        let ordered = collection.ordered_producers(inherited_producers(&collection, &parents));
+        Self {
+            collection,
+            parents,
+            ordered,
+            factories: Vec::new(),
+        }
+    }
+
+    /// Builds a ServiceProvider with `remaining` as its base. Only successful validations are cached,
+    /// so an invalid base type is validated again on each call.
+    pub fn build<T: Identifyable<TS::Id> + Clone + Send + Sync>(
+        &mut self,
+        remaining: T,
+    ) -> Result<ServiceProvider<TS>, super::BuildError<TS>> {
+        let id = T::get_id();
+        let (service_states_count, immutable_state) =
+            match self.factories.iter().find(|(x, _, _)| x == &id) {
+                Some((_, count, state)) => (*count, state.clone()),
+                None => {
+                    let base = base_producer::<T, TS>();
+                    let position = self
+                        .ordered
+                        .partition_point(|x| x.order_key() <= base.order_key());
+                    let mut ordered: Vec<_> = self.ordered.iter().collect();
+                    ordered.insert(position, &base);
+                    let validation = self.collection.validate_ordered_producers(ordered, None)?;
+                    let (count, state) = create_state(validation, self.parents.clone());
+                    self.factories.push((id, count, state.clone()));
+                    (count, state)
+                }
+            };
+        Ok(ServiceProvider::new(
+            immutable_state,
+            service_states_count,
+            Some(AutoFreePointer::boxed(remaining)),
+        ))
+    }
 }
 
 impl<TS: Strategy + 'static, T: Identifyable<TS::Id> + Clone + Send + Sync>
@@ -63,26 +266,8 @@ impl<TS: Strategy + 'static, T: Identifyable<TS::Id> + Clone + Send + Sync>
         mut collection: GenericServiceCollection<TS>,
         parents: RVec<WeakServiceProvider<TS>>,
     ) -> Result<Self, super::BuildError<TS>> {
-        let parent_service_factories: Vec<_> = parents
-            .iter()
-            .flat_map(|parent| unsafe { parent.clone_producers() })
-            .collect();
-
-        collection
-            .producer_factories
-            .push(ServiceProducer::<TS>::new::<T>(
-                ServiceProvider::<TS>::build_service_producer_for_base::<T>(),
-            ));
-
-        let ProducerValidationResult {
-            producers,
-            types,
-            service_states_count,
-        } = collection.validate_producers(parent_service_factories)?;
-
-        let immutable_state = RArc::new(ServiceProviderImmutableState::<TS>::new(
-            types, producers, parents,
-        ));
+        let (service_states_count, immutable_state) =
+            validate_with_base::<T, TS>(&mut collection, parents)?;
 
         Ok(ServiceProviderFactory::<_, TS> {
             service_states_count,
@@ -93,7 +278,7 @@ impl<TS: Strategy + 'static, T: Identifyable<TS::Id> + Clone + Send + Sync>
 
     /// The ServiceProvider should always be assigned to a variable.
     /// Otherwise, a requested shared service it will outlive its ServiceProvider,
-    /// resulting in a panic if debug_assertions are enabled
+    /// resulting in a panic if debug_assertions or the `runtime_checks` feature are enabled
     /// ```
     /// # // don't actually run the test, because it fails for "cargo test --release"
     /// # // #[cfg(debug_assertions)] is still enabled for doctest, but not for the actual library
@@ -109,13 +294,29 @@ impl<TS: Strategy + 'static, T: Identifyable<TS::Id> + Clone + Send + Sync>
     /// # }
     /// ```
     pub fn build(&self, remaining: T) -> ServiceProvider<TS> {
-        let shared_services = (0..self.service_states_count)
-            .map(|_| OnceLock::default())
-            .collect();
-
         ServiceProvider::new(
             self.immutable_state.clone(),
-            shared_services,
+            self.service_states_count,
+            Some(AutoFreePointer::boxed(remaining)),
+        )
+    }
+
+    /// Same as `build`, but returns an error instead of aborting, if the state for shared services
+    /// cannot be allocated. Small allocations for `remaining` and the provider itself are still infallible.
+    /// ```
+    /// use {minfac::{Registered, ServiceCollection}, std::sync::Arc};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.with::<Registered<i32>>().register_shared(|v| Arc::new(v as i64));
+    /// let factory = collection.build_factory().expect("Configuration is valid");
+    /// let provider = factory.try_build(1).expect("Enough memory available");
+    ///
+    /// assert_eq!(Some(1i64), provider.get::<Arc<i64>>().map(|x| *x));
+    /// ```
+    pub fn try_build(&self, remaining: T) -> Result<ServiceProvider<TS>, TryReserveError> {
+        ServiceProvider::try_new(
+            self.immutable_state.clone(),
+            self.service_states_count,
             Some(AutoFreePointer::boxed(remaining)),
         )
     }
@@ -154,6 +355,70 @@ mod tests {
         assert_eq!(alloc::vec!(0, 1, 2), iterator.collect::<Vec<_>>());
     }
 
+    #[test]
+    fn override_parent_hides_inherited_services_of_the_same_type_only() {
+        let mut parent_collection = ServiceCollection::new();
+        parent_collection.register(|| 1i32);
+        parent_collection.register(|| 10i32);
+        parent_collection
+            .with::<Registered<i32>>()
+            .register(|i| i as i64);
+        let parent_provider = parent_collection
+            .build()
+            .expect("Building parent failed unexpectedly");
+
+        let mut child_collection = ServiceCollection::new();
+        child_collection.override_parent(|| 2i32);
+        child_collection
+            .with::<Registered<i32>>()
+            .register(|i| i as u32);
+        let child_factory = child_collection
+            .with_parent(&parent_provider)
+            .build_factory::<()>()
+            .unwrap();
+        let child_provider = child_factory.build(());
+
+        assert_eq!(
+            alloc::vec!(2),
+            child_provider.get_all::<i32>().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(2u32), child_provider.get::<u32>());
+        // Inherited services keep resolving dependencies from their parent
+        assert_eq!(Some(10i64), child_provider.get::<i64>());
+        assert_eq!(
+            alloc::vec!(1, 10),
+            parent_provider.get_all::<i32>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn excluded_parent_services_are_only_resolvable_if_registered_by_child() {
+        let mut parent_collection = ServiceCollection::new();
+        parent_collection.register(|| 1i32);
+        parent_collection.register(|| 1u8);
+        let parent_provider = parent_collection
+            .build()
+            .expect("Building parent failed unexpectedly");
+
+        let mut child_collection = ServiceCollection::new();
+        child_collection.register(|| 2u8);
+        let child_provider = child_collection
+            .with_parent(&parent_provider)
+            .exclude::<i32>()
+            .exclude::<u8>()
+            .build_factory::<()>()
+            .unwrap()
+            .build(());
+
+        assert_eq!(None, child_provider.get::<i32>());
+        assert_eq!(0, child_provider.get_all::<i32>().count());
+        assert_eq!(
+            alloc::vec!(2u8),
+            child_provider.get_all::<u8>().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(1i32), parent_provider.get::<i32>());
+    }
+
     #[test]
     fn uses_same_parent_arc_for_two_providers_from_the_same_child_factory() {
         let mut parent_provider = ServiceCollection::new();
@@ -165,7 +430,7 @@ mod tests {
         let mut child_provider = ServiceCollection::new();
         child_provider
             .with::<Registered<Arc<AtomicI32>>>()
-            .register(|i| Box::new(i));
+            .register(Box::new);
         let child_factory = child_provider
             .with_parent(&parent)
             .build_factory::<i64>()
@@ -224,6 +489,15 @@ mod tests {
         assert_eq!(Ok((2, 1)), result);
     }
 
+    #[test]
+    fn providers_of_the_same_factory_are_not_ptr_eq() {
+        let factory = ServiceCollection::new().build_factory::<i32>().unwrap();
+        let provider1 = factory.build(1);
+        let provider2 = factory.build(1);
+        assert!(provider1.ptr_eq(&provider1));
+        assert!(!provider1.ptr_eq(&provider2));
+    }
+
     #[test]
     fn register_unused_shared() {
         let mut collection = ServiceCollection::new();
@@ -256,4 +530,49 @@ mod tests {
             panic!("Expected to have missing dependency error");
         }
     }
+
+    #[test]
+    fn multi_base_factory_validates_each_base_type_once() {
+        let mut collection = ServiceCollection::new();
+        collection.with::<Registered<u8>>().register(|i| i as i64);
+        let mut factory = collection.build_multi_base_factory();
+
+        assert!(matches!(
+            factory.build(1i32),
+            Err(BuildError::MissingDependency { .. })
+        ));
+        assert_eq!(0, factory.factories.len());
+        assert_eq!(Some(2i64), factory.build(2u8).unwrap().get::<i64>());
+        assert_eq!(Some(3i64), factory.build(3u8).unwrap().get::<i64>());
+        assert_eq!(1, factory.factories.len());
+    }
+
+    #[test]
+    fn cloned_factories_build_providers_with_independent_shared_services() {
+        let mut collection = ServiceCollection::new();
+        collection
+            .with::<Registered<i32>>()
+            .register_shared(|i| Arc::new(i as i64));
+        let factory = collection.build_factory::<i32>().unwrap();
+        let cloned = factory.clone();
+        let original_provider = factory.build(1);
+        let cloned_provider = cloned.build(2);
+
+        let original_shared = original_provider.get::<Arc<i64>>().unwrap();
+        let cloned_shared = cloned_provider.get::<Arc<i64>>().unwrap();
+        assert_eq!(1, *original_shared);
+        assert_eq!(2, *cloned_shared);
+        assert!(!Arc::ptr_eq(
+            &original_shared,
+            &cloned_provider.get().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            &original_shared,
+            &original_provider.get().unwrap()
+        ));
+        drop(original_shared);
+        drop(cloned_shared);
+        drop(factory);
+        assert_eq!(Some(3i64), cloned.build(3).get::<Arc<i64>>().map(|x| *x));
+    }
 }
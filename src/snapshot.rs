@@ -0,0 +1,139 @@
+//! Snapshots of instances registered with `register_serializable_instance`, e.g. for fast warm starts of
+//! configuration-heavy applications. Only instances can be snapshotted, because factories have no value
+//! before they are called.
+
+use crate::{
+    strategy::{Identifyable, Strategy},
+    AliasBuilder, GenericServiceCollection,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::any::type_name;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+/// Version of the format written by `SerializedInstances::to_bytes`. It's only increased for
+/// incompatible changes, so snapshots stay loadable by other minor versions of minfac.
+const FORMAT_VERSION: u64 = 1;
+
+pub(crate) type Serializer = (
+    &'static str,
+    Arc<dyn Fn() -> Result<Value, serde_json::Error> + Send + Sync>,
+);
+
+/// Values of the instances registered with `register_serializable_instance`, keyed by their type name.
+/// Type names aren't guaranteed to be stable across compiler versions, so snapshots should be
+/// recreated when the application is rebuilt with another toolchain.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SerializedInstances {
+    instances: Map<String, Value>,
+}
+
+impl SerializedInstances {
+    /// Serializes the snapshot together with its format version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut snapshot = Map::new();
+        snapshot.insert("format_version".into(), FORMAT_VERSION.into());
+        snapshot.insert("instances".into(), Value::Object(self.instances.clone()));
+        serde_json::to_vec(&snapshot).expect("Json values are always serializable")
+    }
+
+    /// Reads a snapshot written by `to_bytes` of this or any other version with the same format version
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        use serde::de::Error;
+        let mut snapshot: Map<String, Value> =
+            serde_json::from_slice(bytes).map_err(SnapshotError::Format)?;
+        let found = snapshot
+            .get("format_version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| SnapshotError::Format(Error::missing_field("format_version")))?;
+        if found > FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion {
+                found,
+                supported: FORMAT_VERSION,
+            });
+        }
+        match snapshot.remove("instances") {
+            Some(Value::Object(instances)) => Ok(Self { instances }),
+            _ => Err(SnapshotError::Format(Error::missing_field("instances"))),
+        }
+    }
+
+    /// Returns true, if the snapshot contains an instance of `T`
+    pub fn contains<T>(&self) -> bool {
+        self.instances.contains_key(type_name::<T>())
+    }
+}
+
+/// Errors when creating or loading a snapshot of instances
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The bytes aren't a snapshot or an instance couldn't be serialized or deserialized
+    Format(serde_json::Error),
+    /// The snapshot was written by a version of minfac with an incompatible format
+    #[non_exhaustive]
+    UnsupportedVersion { found: u64, supported: u64 },
+    /// The snapshot doesn't contain an instance of type `name`
+    #[non_exhaustive]
+    MissingInstance { name: &'static str },
+}
+
+impl<TS: Strategy + 'static> GenericServiceCollection<TS> {
+    /// Registers an instance like `register_instance`, which is additionally part of `snapshot_instances`.
+    /// If multiple instances of the same type are registered, the snapshot contains the last one.
+    /// ```
+    /// use minfac::{ServiceCollection, SerializedInstances};
+    ///
+    /// let mut collection = ServiceCollection::new();
+    /// collection.register_serializable_instance(String::from("expensive config"));
+    /// let bytes = collection.snapshot_instances().unwrap().to_bytes();
+    ///
+    /// let snapshot = SerializedInstances::from_bytes(&bytes).unwrap();
+    /// let mut restored = ServiceCollection::new();
+    /// restored.load_instance::<String>(&snapshot).unwrap();
+    /// let provider = restored.build().expect("Configuration is valid");
+    /// assert_eq!(Some("expensive config"), provider.get::<String>().as_deref());
+    /// ```
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn register_serializable_instance<
+        T: Identifyable<TS::Id> + Clone + Serialize + 'static + Send + Sync,
+    >(
+        &mut self,
+        instance: T,
+    ) -> AliasBuilder<'_, T, TS> {
+        let value = instance.clone();
+        self.serializers.push((
+            type_name::<T>(),
+            Arc::new(move || serde_json::to_value(&value)),
+        ));
+        self.register_instance(instance)
+    }
+
+    /// Serializes all instances registered with `register_serializable_instance`
+    pub fn snapshot_instances(&self) -> Result<SerializedInstances, SnapshotError> {
+        let mut instances = Map::new();
+        for (name, serialize) in self.serializers.iter() {
+            instances.insert((*name).into(), serialize().map_err(SnapshotError::Format)?);
+        }
+        Ok(SerializedInstances { instances })
+    }
+
+    /// Registers the instance of `T` contained in `snapshot` like `register_serializable_instance`, so it's
+    /// part of the next snapshot as well. Each type has to be loaded explicitly, because it's needed to
+    /// deserialize the instance.
+    #[cfg_attr(feature = "log", track_caller)]
+    pub fn load_instance<
+        T: Identifyable<TS::Id> + Clone + Serialize + DeserializeOwned + 'static + Send + Sync,
+    >(
+        &mut self,
+        snapshot: &SerializedInstances,
+    ) -> Result<AliasBuilder<'_, T, TS>, SnapshotError> {
+        let name = type_name::<T>();
+        let value = snapshot
+            .instances
+            .get(name)
+            .ok_or(SnapshotError::MissingInstance { name })?;
+        let instance = T::deserialize(value).map_err(SnapshotError::Format)?;
+        Ok(self.register_serializable_instance(instance))
+    }
+}
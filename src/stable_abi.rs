@@ -26,7 +26,8 @@ pub struct StableAbiTypeId {
     child_name_hash: u64,
 }
 
-impl<T: StableAbi + 'static> Identifyable<StableAbiTypeId> for T {
+// Types with equal layouts and paths are interchangeable, as they are checked by abi_stable
+unsafe impl<T: StableAbi + 'static> Identifyable<StableAbiTypeId> for T {
     fn get_id() -> StableAbiTypeId {
         get_layout_typeid(Self::LAYOUT)
     }
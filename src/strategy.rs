@@ -3,16 +3,30 @@ use core::{
     fmt::Debug,
 };
 
+/// Defines how services are identified. `AnyStrategy` uses `TypeId`, so each Rust type is a separate service.
+/// Custom strategies can use their own `Id` to map several Rust types to the same key, which makes them
+/// interchangeable for `get` and `get_all`. See `Identifyable` for the requirements of such mappings.
 #[cfg_attr(feature = "stable_abi", abi_stable::sabi_trait)]
 pub trait Strategy: Debug + Send + Sync {
     type Id: Ord + Debug + Copy + PartialEq + Eq;
 }
 
-pub trait Identifyable<T: Ord>: 'static {
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not identifyable by `{T}`, so it cannot be used as a service",
+    note = "services of `StableAbiStrategy` have to implement `StableAbi`",
+    note = "shared services are identified as `Arc<T>`, so creators of `register_shared` have to return `Arc::new(...)`"
+)]
+/// Maps a Rust type to the id of a Strategy.
+///
+/// # Safety
+/// Services are stored type-erased and resolved by id only, so a service registered as `A` is returned
+/// as `B`, if both have the same id. Types sharing an id must therefore be interchangeable in memory,
+/// e.g. a `#[repr(transparent)]` newtype and its inner type. Otherwise, resolving them is undefined behavior.
+pub unsafe trait Identifyable<T: Ord>: 'static {
     fn get_id() -> T;
 }
 
-impl<T: Any> Identifyable<TypeId> for T {
+unsafe impl<T: Any> Identifyable<TypeId> for T {
     fn get_id() -> TypeId {
         TypeId::of::<T>()
     }
@@ -0,0 +1,100 @@
+use crate::{
+    lifetime::{default_error_handler, OutlivedLifetimeErrorVariants},
+    scoped_error_handler, LifetimeError,
+};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+std::thread_local! {
+    static LEAKS: RefCell<Option<Vec<LeakedService>>> = const { RefCell::new(None) };
+}
+
+/// Instance which was still referenced when its ServiceProvider was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeakedService {
+    /// A WeakServiceProvider outlived the ServiceProvider it was resolved from
+    WeakServiceProvider { remaining_references: usize },
+    /// A shared service outlived the ServiceProvider which created it
+    Shared {
+        type_name: &'static str,
+        remaining_references: usize,
+    },
+}
+
+/// Runs `scope` and returns all services, which outlived their ServiceProvider while it was running,
+/// instead of panicking at the first one like the default error handler does.
+///
+/// Leaks are only collected for providers dropped on the calling thread. Because the error handler is
+/// global, other threads report to the default handler until `scope` returns.
+/// ```
+/// use minfac::{test::{assert_no_leaks, LeakedService}, ServiceCollection, WeakServiceProvider};
+///
+/// let mut collection = ServiceCollection::new();
+/// collection.with::<WeakServiceProvider>().register(|p| p);
+/// let mut outliving = None;
+/// let leaks = assert_no_leaks(|| {
+///     let provider = collection.clone().build().expect("Configuration is valid");
+///     outliving = provider.get::<WeakServiceProvider>();
+/// });
+/// assert_eq!(
+///     Err(vec![LeakedService::WeakServiceProvider { remaining_references: 1 }]),
+///     leaks
+/// );
+/// drop(outliving);
+///
+/// assert_eq!(Ok(()), assert_no_leaks(|| drop(collection.build())));
+/// ```
+pub fn assert_no_leaks(scope: impl FnOnce()) -> Result<(), Vec<LeakedService>> {
+    let collector = Collector {
+        previous: LEAKS.with(|leaks| leaks.replace(Some(Vec::new()))),
+    };
+    let handler = scoped_error_handler(record_leaks);
+    scope();
+    drop(handler);
+    let leaks = LEAKS.with(|leaks| leaks.borrow_mut().take().unwrap_or_default());
+    drop(collector);
+
+    if leaks.is_empty() {
+        Ok(())
+    } else {
+        Err(leaks)
+    }
+}
+
+// Restores the collection of an enclosing assert_no_leaks, even if the scope panics
+struct Collector {
+    previous: Option<Vec<LeakedService>>,
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        LEAKS.with(|leaks| *leaks.borrow_mut() = previous);
+    }
+}
+
+extern "C-unwind" fn record_leaks(error: &LifetimeError) {
+    let recorded = LEAKS.with(|leaks| {
+        let mut leaks = leaks.borrow_mut();
+        let Some(leaks) = leaks.as_mut() else {
+            return false;
+        };
+        match error.variant() {
+            OutlivedLifetimeErrorVariants::WeakServiceProvider {
+                remaining_references,
+            } => leaks.push(LeakedService::WeakServiceProvider {
+                remaining_references: *remaining_references,
+            }),
+            OutlivedLifetimeErrorVariants::SharedServices(services) => {
+                leaks.extend(services.as_slice().iter().map(|s| LeakedService::Shared {
+                    type_name: s.typename(),
+                    remaining_references: s.remaining_references(),
+                }))
+            }
+        }
+        true
+    });
+    if !recorded {
+        default_error_handler(error);
+    }
+}
@@ -1,14 +1,19 @@
 use alloc::sync::Arc;
+use core::any::TypeId;
 
 use super::{super::AnyPtr, AutoFreePointer};
 
+/// Type-erased handle to a shared service, which is stored as `Arc<T>` within a ServiceProvider
 pub struct ArcAutoFreePointer {
     inner: AutoFreePointer,
     downgrade_ptr: extern "C" fn(AnyPtr) -> WeakInfo,
+    clone_ptr: fn(&ArcAutoFreePointer) -> ArcAutoFreePointer,
+    // Only comparable within the same compilation, so try_clone_as returns None across dynamic libraries
+    type_id: TypeId,
 }
 
 impl ArcAutoFreePointer {
-    pub fn new<T: Send + Sync>(i: Arc<T>) -> Self {
+    pub(crate) fn new<T: Send + Sync + 'static>(i: Arc<T>) -> Self {
         extern "C" fn dropper<T>(i: AnyPtr) {
             drop(unsafe { Arc::from_raw(i as *const T) });
         }
@@ -33,22 +38,45 @@ impl ArcAutoFreePointer {
             }
         }
 
+        fn clone<T: Send + Sync + 'static>(i: &ArcAutoFreePointer) -> ArcAutoFreePointer {
+            ArcAutoFreePointer::new(unsafe { i.clone_inner::<T>() })
+        }
+
         Self {
             inner: AutoFreePointer::new(Arc::into_raw(i) as AnyPtr, dropper::<T>),
             downgrade_ptr: downgrade::<T>,
+            clone_ptr: clone::<T>,
+            type_id: TypeId::of::<T>(),
         }
     }
+    /// Returns a clone of the `Arc<T>`, if this pointer was created from an `Arc<T>`.
+    /// Otherwise None is returned
+    pub fn try_clone_as<T: 'static>(&self) -> Option<Arc<T>> {
+        (self.type_id == TypeId::of::<T>()).then(|| unsafe { self.clone_inner::<T>() })
+    }
+    /// # Safety
+    /// `T` has to be the exact type of the `Arc<T>` this pointer was created from
     pub unsafe fn clone_inner<T>(&self) -> Arc<T> {
         let arc = Arc::from_raw(self.inner.get_pointer() as *const T);
         let r = arc.clone();
         let _ = Arc::into_raw(arc);
         r
     }
-    pub fn downgrade(&self) -> WeakInfo {
+    pub(crate) fn get_pointer(&self) -> AnyPtr {
+        self.inner.get_pointer()
+    }
+    pub(crate) fn downgrade(&self) -> WeakInfo {
         (self.downgrade_ptr)(self.inner.get_pointer())
     }
 }
 
+/// Increases the reference count of the shared service, like `Arc::clone`
+impl Clone for ArcAutoFreePointer {
+    fn clone(&self) -> Self {
+        (self.clone_ptr)(self)
+    }
+}
+
 #[repr(C)]
 pub struct WeakInfo {
     weak_ptr: extern "C" fn(AnyPtr) -> usize,
@@ -73,4 +101,23 @@ mod tests {
         drop(x);
         assert_eq!(1, Arc::strong_count(&cloned));
     }
+
+    #[test]
+    fn clone_shares_the_same_arc() {
+        let x = ArcAutoFreePointer::new(Arc::new(String::from("Test")));
+        let cloned = x.clone();
+        drop(x);
+        let arc = cloned.try_clone_as::<String>().unwrap();
+        assert_eq!(2, Arc::strong_count(&arc));
+    }
+
+    #[test]
+    fn try_clone_as_checks_type() {
+        let x = ArcAutoFreePointer::new(Arc::new(String::from("Test")));
+        assert_eq!(None, x.try_clone_as::<i32>());
+        assert_eq!(
+            Some("Test"),
+            x.try_clone_as::<String>().as_deref().map(String::as_str)
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use super::super::AnyPtr;
+use core::marker::PhantomData;
 
 #[repr(C)]
 #[cfg_attr(feature = "stable_abi", derive(abi_stable::StableAbi))]
@@ -45,3 +46,50 @@ impl Drop for AutoFreePointer {
         (self.dropper)(self.context)
     }
 }
+
+/// Owns a `T` like `AutoFreePointer::boxed`, but remembers its type. Contexts of factories are erased
+/// to `AutoFreePointer` to be passed to `extern` functions, and typed again within them with `from_erased`.
+#[repr(transparent)]
+pub struct FactoryContext<T> {
+    pointer: AutoFreePointer,
+    value_type: PhantomData<T>,
+}
+
+impl<T> FactoryContext<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            pointer: AutoFreePointer::boxed(value),
+            value_type: PhantomData,
+        }
+    }
+
+    /// Unsafe constraint: `pointer` must be erased from a `FactoryContext<T>` with the same `T`
+    pub unsafe fn from_erased(pointer: &AutoFreePointer) -> &Self {
+        &*(pointer as *const AutoFreePointer as *const Self)
+    }
+
+    pub fn get(&self) -> &T {
+        unsafe { &*(self.pointer.get_pointer() as *const T) }
+    }
+
+    pub fn into_erased(self) -> AutoFreePointer {
+        self.pointer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn erased_factory_context_is_typed_again_and_dropped_once() {
+        let value = Arc::new(42);
+        let erased = FactoryContext::new(value.clone()).into_erased();
+        let typed = unsafe { FactoryContext::<Arc<i32>>::from_erased(&erased) };
+        assert_eq!(42, **typed.get());
+        assert_eq!(2, Arc::strong_count(&value));
+        drop(erased);
+        assert_eq!(1, Arc::strong_count(&value));
+    }
+}
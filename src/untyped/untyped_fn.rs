@@ -4,31 +4,43 @@ use crate::{
     AnyPtr,
 };
 
-use super::AutoFreePointer;
+use super::{ArcAutoFreePointer, AutoFreePointer};
+
+type SharedPointerFn<TS> = extern "C-unwind" fn(
+    *const ServiceProvider<TS>,
+    *const AutoFreePointer,
+) -> *const ArcAutoFreePointer;
 
 #[repr(C)]
 pub struct UntypedFn<TS: Strategy + 'static> {
     result_type_id: TS::Id,
     factory_pointer: AnyPtr,
+    // Only available for shared services: Returns a pointer to `T` of the shared `Arc<T>`, initializing it if necessary
+    shared_pointer: Option<SharedPointerFn<TS>>,
     context: AutoFreePointer,
-    wrapper_creator:
-        unsafe extern "C" fn(*const UntypedFn<TS>, *const ServiceProvider<TS>) -> UntypedFn<TS>,
+    wrapper_creator: unsafe extern "C-unwind" fn(
+        *const UntypedFn<TS>,
+        *const ServiceProvider<TS>,
+    ) -> UntypedFn<TS>,
 }
 
+// Producers are function pointers with contexts, which aren't mutated after the build. Transient services are
+// created on the resolving thread. Cached services are `Send + Sync` and live in the OnceLocks of
+// ServiceProviderMutableState, which only the drop of the root releases.
 unsafe impl<TS: Strategy + 'static> Send for UntypedFn<TS> {}
 unsafe impl<TS: Strategy + 'static> Sync for UntypedFn<TS> {}
 
 impl<TS: Strategy + 'static> UntypedFn<TS> {
     pub fn create<T: Identifyable<TS::Id>>(
-        creator: extern "C" fn(*const ServiceProvider<TS>, *const AutoFreePointer) -> T,
+        creator: extern "C-unwind" fn(*const ServiceProvider<TS>, *const AutoFreePointer) -> T,
         context: AutoFreePointer,
     ) -> Self {
         type InnerContext<TS> = (*const UntypedFn<TS>, *const ServiceProvider<TS>);
-        extern "C" fn wrapper_creator<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+        extern "C-unwind" fn wrapper_creator<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
             inner: *const UntypedFn<TS>,
             provider: *const ServiceProvider<TS>,
         ) -> UntypedFn<TS> {
-            extern "C" fn new_factory<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
+            extern "C-unwind" fn new_factory<T: Identifyable<TS::Id>, TS: Strategy + 'static>(
                 _ignored_provider: *const ServiceProvider<TS>,
                 context: *const AutoFreePointer,
             ) -> T {
@@ -38,23 +50,75 @@ impl<TS: Strategy + 'static> UntypedFn<TS> {
                     (&**inner).execute::<T>(&**provider)
                 }
             }
+            extern "C-unwind" fn new_shared_pointer<TS: Strategy + 'static>(
+                _ignored_provider: *const ServiceProvider<TS>,
+                context: *const AutoFreePointer,
+            ) -> *const ArcAutoFreePointer {
+                unsafe {
+                    let (inner, provider) = &*((&*context as &AutoFreePointer).get_pointer()
+                        as *const InnerContext<TS>);
+                    (&**inner)
+                        .get_shared(&**provider)
+                        .expect("Only called if inner is a shared service")
+                }
+            }
+            let (is_shared, result_type_id) =
+                unsafe { ((*inner).shared_pointer.is_some(), (*inner).result_type_id) };
             let inner: InnerContext<TS> = (inner, provider);
-            UntypedFn::<TS>::create::<T>(new_factory, AutoFreePointer::boxed(inner))
+            let result = UntypedFn::<TS>::create::<T>(new_factory, AutoFreePointer::boxed(inner));
+            // Keeps ids of services registered with GenericServiceCollection::register_with_id()
+            let result = unsafe { result.with_result_type_id(result_type_id) };
+            if is_shared {
+                result.with_shared_pointer(new_shared_pointer::<TS>)
+            } else {
+                result
+            }
         }
         UntypedFn {
             result_type_id: T::get_id(),
             context,
             factory_pointer: creator as AnyPtr,
+            shared_pointer: None,
             wrapper_creator: wrapper_creator::<T, TS>,
         }
     }
+
+    /// Unsafe constraint: Callers resolving `id` must expect the type this UntypedFn was created with
+    pub unsafe fn with_result_type_id(mut self, id: TS::Id) -> Self {
+        self.result_type_id = id;
+        self
+    }
+
+    /// Marks this UntypedFn as a shared service. `shared_pointer` receives the same context as the creator
+    pub fn with_shared_pointer(mut self, shared_pointer: SharedPointerFn<TS>) -> Self {
+        self.shared_pointer = Some(shared_pointer);
+        self
+    }
+
+    /// Returns the shared service `Arc<T>`, which is initialized if necessary. None for transient services.
+    /// Unsafe constraint: Same as for execute
+    pub unsafe fn get_shared<'a>(
+        &self,
+        provider: &'a ServiceProvider<TS>,
+    ) -> Option<&'a ArcAutoFreePointer> {
+        self.shared_pointer
+            .map(|shared_pointer| &*(shared_pointer)(provider, &self.context))
+    }
+
+    /// Returns a pointer to `T` of the shared service `Arc<T>` without increasing its reference count.
+    /// The pointer is valid as long as `provider` lives
+    /// Unsafe constraint: Same as for execute
+    pub unsafe fn get_shared_pointer(&self, provider: &ServiceProvider<TS>) -> Option<AnyPtr> {
+        self.get_shared(provider)
+            .map(ArcAutoFreePointer::get_pointer)
+    }
     pub fn get_result_type_id(&self) -> &TS::Id {
         &self.result_type_id
     }
 
     // Unsafe constraint: Must be called with the same T as it was created
     pub unsafe fn execute<T>(&self, provider: &ServiceProvider<TS>) -> T {
-        let lambda: extern "C" fn(&ServiceProvider<TS>, &AutoFreePointer) -> T =
+        let lambda: extern "C-unwind" fn(&ServiceProvider<TS>, &AutoFreePointer) -> T =
             std::mem::transmute(self.factory_pointer);
         (lambda)(provider, &self.context)
     }
@@ -75,7 +139,7 @@ mod tests {
 
     #[test]
     fn create_execute_and_drop() {
-        extern "C" fn test<T: Identifyable<TS::Id> + Copy, TS: Strategy>(
+        extern "C-unwind" fn test<T: Identifyable<TS::Id> + Copy, TS: Strategy>(
             _provider: *const ServiceProvider<TS>,
             ctx: *const AutoFreePointer,
         ) -> T {
@@ -0,0 +1,89 @@
+// The error handler is global, so tests replacing it live in their own binary to not affect other tests
+#![cfg(any(debug_assertions, feature = "runtime_checks"))]
+
+use minfac::{
+    test::{assert_no_leaks, LeakedService},
+    Registered, ServiceCollection, WeakServiceProvider,
+};
+use std::sync::Arc;
+
+#[test]
+fn assert_no_leaks_reports_all_outliving_shared_services() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(1u8));
+    collection.register_shared(|| Arc::new(2u16));
+    collection.register_shared(|| Arc::new(3u32));
+    let mut outliving = Vec::new();
+
+    let leaks = assert_no_leaks(|| {
+        let provider = collection.build().expect("Configuration is valid");
+        outliving.push(provider.get::<Arc<u8>>().map(|x| x as Arc<dyn Send + Sync>));
+        outliving.push(
+            provider
+                .get::<Arc<u32>>()
+                .map(|x| x as Arc<dyn Send + Sync>),
+        );
+        provider.get::<Arc<u16>>();
+    })
+    .expect_err("Two services outlived the provider");
+
+    assert_eq!(2, leaks.len());
+    for type_name in ["alloc::sync::Arc<u8>", "alloc::sync::Arc<u32>"] {
+        assert!(leaks.contains(&LeakedService::Shared {
+            type_name,
+            remaining_references: 1
+        }));
+    }
+}
+
+#[test]
+fn assert_no_leaks_is_ok_for_services_dropped_in_scope() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(1u8));
+    collection
+        .with::<(WeakServiceProvider, Registered<Arc<u8>>)>()
+        .register(|(p, b)| (p, b));
+
+    assert_eq!(
+        Ok(()),
+        assert_no_leaks(|| {
+            let provider = collection.build().expect("Configuration is valid");
+            let services = provider.get::<(WeakServiceProvider, Arc<u8>)>();
+            drop(services);
+        })
+    );
+}
+
+#[test]
+fn assert_no_leaks_restores_enclosing_scope() {
+    let mut collection = ServiceCollection::new();
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let mut outliving = Vec::new();
+
+    let outer = assert_no_leaks(|| {
+        let inner = assert_no_leaks(|| {
+            outliving.push(
+                collection
+                    .clone()
+                    .build()
+                    .unwrap()
+                    .get::<WeakServiceProvider>(),
+            );
+        });
+        assert_eq!(1, inner.unwrap_err().len());
+        outliving.push(
+            collection
+                .clone()
+                .build()
+                .unwrap()
+                .get::<WeakServiceProvider>(),
+        );
+    });
+
+    assert_eq!(
+        Err(vec![LeakedService::WeakServiceProvider {
+            remaining_references: 1
+        }]),
+        outer
+    );
+}
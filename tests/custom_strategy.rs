@@ -0,0 +1,104 @@
+use minfac::{
+    BuildError, GenericServiceCollection, Identifyable, Registered, ServiceCollection, Strategy,
+};
+use std::any::TypeId;
+use std::sync::Arc;
+
+/// Identifies services by name, so `Arc<i32>` and `Wrapper` are the same service
+#[derive(Debug)]
+struct NamedStrategy;
+
+impl Strategy for NamedStrategy {
+    type Id = ServiceName;
+}
+
+// Has to be local to implement Identifyable for foreign types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ServiceName(&'static str);
+
+#[repr(transparent)]
+#[derive(Debug, PartialEq)]
+struct Wrapper(Arc<i32>);
+
+unsafe impl Identifyable<ServiceName> for Arc<i32> {
+    fn get_id() -> ServiceName {
+        ServiceName("number")
+    }
+}
+
+// Sound, because Wrapper is a transparent newtype of Arc<i32>
+unsafe impl Identifyable<ServiceName> for Wrapper {
+    fn get_id() -> ServiceName {
+        ServiceName("number")
+    }
+}
+
+unsafe impl Identifyable<ServiceName> for i64 {
+    fn get_id() -> ServiceName {
+        ServiceName("i64")
+    }
+}
+
+#[test]
+fn types_with_the_same_id_are_resolved_uniformly() {
+    let mut collection = GenericServiceCollection::<NamedStrategy>::new();
+    collection.register(|| Arc::new(1i32));
+    collection.register(|| Wrapper(Arc::new(2)));
+    collection
+        .with::<Registered<Arc<i32>>>()
+        .register(|number| *number as i64);
+    let provider = collection
+        .build()
+        .expect("Expected to have all dependencies");
+
+    assert_eq!(Some(Wrapper(Arc::new(2))), provider.get::<Wrapper>());
+    assert_eq!(Some(Arc::new(2)), provider.get::<Arc<i32>>());
+    assert_eq!(
+        vec![1, 2],
+        provider
+            .get_all::<Wrapper>()
+            .map(|w| *w.0)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(2i64), provider.get::<i64>());
+}
+
+#[test]
+fn map_id_reports_errors_in_terms_of_another_strategy() {
+    let mut collection = ServiceCollection::new();
+    collection.with::<Registered<i64>>().register(|x| x as i32);
+    let error = collection.build().expect_err("i64 is missing");
+
+    let mapped = error.map_id::<NamedStrategy>(|id| {
+        assert_eq!(TypeId::of::<i64>(), id);
+        ServiceName("i64")
+    });
+    match mapped {
+        BuildError::MissingDependency { id, name, .. } => {
+            assert_eq!(ServiceName("i64"), id);
+            assert_eq!("i64", name);
+        }
+        e => panic!("Unexpected error {e:?}"),
+    }
+}
+
+#[test]
+fn cyclic_dependency_description_only_depends_on_ids() {
+    let mut collection = GenericServiceCollection::<NamedStrategy>::new();
+    collection
+        .with::<Registered<Arc<i32>>>()
+        .register(|number| *number as i64);
+    collection
+        .with::<Registered<i64>>()
+        .register(|x| Arc::new(x as i32));
+
+    match collection.build() {
+        Err(error @ BuildError::CyclicDependency { .. }) => {
+            assert_eq!(
+                "Cyclic dependency alloc::sync::Arc<i32> -> i64 -> alloc::sync::Arc<i32>",
+                error.to_string()
+            )
+        }
+        _ => panic!("Expected BuildError::CyclicDependency"),
+    }
+}
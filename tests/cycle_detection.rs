@@ -9,7 +9,7 @@ fn handle_cyclic_references() {
 
     let err = col.build().expect_err("Expected to return error");
     let msg = match err {
-        BuildError::CyclicDependency { description, .. } => description,
+        BuildError::CyclicDependency { .. } => err.to_string(),
         _ => panic!("Expected BuildError::CyclicDependency"),
     };
     assert!(msg.contains("i32 -> i16"));
@@ -17,6 +17,25 @@ fn handle_cyclic_references() {
     assert!(msg.contains("i64 -> i32"));
 }
 
+#[test]
+fn cyclic_dependency_lists_the_type_names_along_the_cycle() {
+    let mut col = ServiceCollection::new();
+    col.with::<Registered<i64>>().register(|_| 0i16);
+    col.with::<Registered<i16>>().register(|_| 0i64);
+
+    let error = col.build().expect_err("Expected to return error");
+    let BuildError::CyclicDependency { path, .. } = &error else {
+        panic!("Expected BuildError::CyclicDependency");
+    };
+    assert_eq!(3, path.len());
+    assert_eq!(path.first(), path.last());
+    assert!(path.contains(&"i16") && path.contains(&"i64"));
+    assert_eq!(
+        format!("Cyclic dependency {}", path.join(" -> ")),
+        error.to_string()
+    );
+}
+
 #[test]
 fn one_of_multiple_dependencies_asks_for_dependent_should_trigger_cyclic_dependency() {
     let mut col = ServiceCollection::new();
@@ -26,8 +45,8 @@ fn one_of_multiple_dependencies_asks_for_dependent_should_trigger_cyclic_depende
 
     col.with::<AllRegistered<i32>>().register(|_| 42i64);
     let error = col.build().expect_err("Expected to return error");
-    let msg = if let BuildError::CyclicDependency { description, .. } = error {
-        description
+    let msg = if let BuildError::CyclicDependency { .. } = error {
+        error.to_string()
     } else {
         panic!("Expected error");
     };
@@ -0,0 +1,68 @@
+use minfac::{BuildError, Registered, ServiceCollection};
+use proptest::prelude::*;
+
+const I32: [fn() -> i32; 4] = [|| 0, || 1, || 2, || 3];
+const I64: [fn() -> i64; 4] = [|| 0, || 1, || 2, || 3];
+const U8: [fn() -> u8; 4] = [|| 0, || 1, || 2, || 3];
+
+// (type, value) of each registration in registration order
+fn create_collection(registrations: &[(u8, usize)]) -> ServiceCollection {
+    let mut collection = ServiceCollection::new();
+    for (kind, value) in registrations {
+        match kind {
+            0 => drop(collection.register(I32[*value])),
+            1 => drop(collection.register(I64[*value])),
+            _ => drop(collection.register(U8[*value])),
+        }
+    }
+    collection
+}
+
+fn registered_values(registrations: &[(u8, usize)], kind: u8) -> Vec<usize> {
+    registrations
+        .iter()
+        .filter(|(k, _)| *k == kind)
+        .map(|(_, v)| *v)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn get_all_keeps_registration_order(registrations in prop::collection::vec((0u8..3, 0usize..4), 0..40)) {
+        let provider = create_collection(&registrations).build().unwrap();
+        let again = create_collection(&registrations).build().unwrap();
+
+        let i32s: Vec<_> = provider.get_all::<i32>().map(|x| x as usize).collect();
+        let i64s: Vec<_> = provider.get_all::<i64>().map(|x| x as usize).collect();
+        let u8s: Vec<_> = provider.get_all::<u8>().map(|x| x as usize).collect();
+        prop_assert_eq!(registered_values(&registrations, 0), i32s);
+        prop_assert_eq!(registered_values(&registrations, 1), i64s);
+        prop_assert_eq!(registered_values(&registrations, 2), u8s);
+        prop_assert_eq!(provider.get::<i32>(), again.get::<i32>());
+        prop_assert_eq!(
+            provider.get_all::<i64>().collect::<Vec<_>>(),
+            again.get_all::<i64>().collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn cyclic_dependency_description_is_equal_for_each_build() {
+    fn build_error() -> String {
+        let mut col = ServiceCollection::new();
+        col.with::<Registered<i64>>().register(|_| 0i16);
+        col.with::<Registered<i16>>().register(|_| 0i32);
+        col.with::<Registered<i32>>().register(|_| 0i64);
+        col.with::<Registered<u8>>().register(|_| 0u16);
+        col.with::<Registered<u16>>().register(|_| 0u8);
+        match col.build() {
+            Err(error @ BuildError::CyclicDependency { .. }) => error.to_string(),
+            _ => panic!("Expected BuildError::CyclicDependency"),
+        }
+    }
+
+    let first = build_error();
+    for _ in 0..20 {
+        assert_eq!(first, build_error());
+    }
+}
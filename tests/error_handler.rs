@@ -0,0 +1,43 @@
+// The error handler is global, so tests replacing it live in their own binary to not affect other tests
+#![cfg(any(debug_assertions, feature = "runtime_checks"))]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use minfac::{
+    error_handler, swap_error_handler, ErrorHandler, LifetimeError, ServiceCollection,
+    WeakServiceProvider,
+};
+use std::sync::Arc;
+
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C-unwind" fn count_errors(_: &LifetimeError) {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+struct RestoreHandler(ErrorHandler);
+
+impl Drop for RestoreHandler {
+    fn drop(&mut self) {
+        swap_error_handler(self.0);
+    }
+}
+
+#[test]
+fn swapped_handler_is_called_for_lifetime_errors_and_restored() {
+    let original = error_handler();
+    {
+        let _restore = RestoreHandler(swap_error_handler(count_errors));
+        let mut collection = ServiceCollection::new();
+        collection.register_shared(|| Arc::new(1i32));
+        collection.with::<WeakServiceProvider>().register(|p| p);
+        let provider = collection.build().unwrap();
+        let shared = provider.get::<Arc<i32>>().unwrap();
+        let weak = provider.get::<WeakServiceProvider>().unwrap();
+
+        drop(weak);
+        drop(provider);
+        drop(shared);
+        assert_eq!(1, ERRORS.load(Ordering::Relaxed));
+    }
+    assert!(error_handler() as usize == original as usize);
+}
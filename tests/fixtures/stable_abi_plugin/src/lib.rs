@@ -0,0 +1,12 @@
+use {
+    abi_stable::std_types::RString,
+    minfac::{stable_abi::StableAbiStrategy, GenericServiceCollection, Registered},
+};
+
+#[no_mangle]
+pub extern "C" fn register(collection: &mut GenericServiceCollection<StableAbiStrategy>) {
+    collection
+        .with::<Registered<u32>>()
+        .register(|i| i as u64 * 2);
+    collection.register(|| RString::from("plugin"));
+}
@@ -0,0 +1,96 @@
+// Installs a global logger, so it lives in its own binary
+#![cfg(feature = "log")]
+
+use minfac::{Registered, ServiceCollection};
+use std::sync::{Arc, Mutex};
+
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl log::Log for Recorder {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+    fn log(&self, record: &log::Record) {
+        MESSAGES.lock().unwrap().push(record.args().to_string());
+    }
+    fn flush(&self) {}
+}
+
+#[test]
+fn registrations_are_logged_with_their_call_site() {
+    log::set_logger(&Recorder).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8).alias(|x| x as u16);
+    collection.register_instance(1i32);
+    collection
+        .with::<Registered<i32>>()
+        .register_shared(|x| Arc::new(x as i64));
+
+    let messages = MESSAGES.lock().unwrap();
+    let file = file!();
+    assert_eq!(4, messages.len(), "{messages:?}");
+    assert!(messages[0].starts_with("Register u8 at "), "{messages:?}");
+    assert!(
+        messages[1].starts_with("Register u16 depending on minfac::Registered<u8> at "),
+        "{messages:?}"
+    );
+    assert!(
+        messages[2].starts_with("Register instance i32 at "),
+        "{messages:?}"
+    );
+    assert!(
+        messages[3].starts_with(
+            "Register shared alloc::sync::Arc<i64> depending on minfac::Registered<i32> at "
+        ),
+        "{messages:?}"
+    );
+    assert!(messages.iter().all(|m| m.contains(file)), "{messages:?}");
+    drop(messages);
+
+    MESSAGES.lock().unwrap().clear();
+    let mut collection = ServiceCollection::new();
+    unsafe { collection.register_with_id(core::any::TypeId::of::<u8>(), || 1u8) };
+    collection.register_keyed("key", || 1u16);
+    collection.with::<()>().register_indexed(|i, ()| i as u32);
+    collection.with::<()>().register_with_provider(|_, ()| 1u64);
+    collection
+        .with::<()>()
+        .register_factory_fn(|(), x: u8| x as i8);
+
+    let messages = MESSAGES.lock().unwrap();
+    assert_eq!(6, messages.len(), "{messages:?}");
+    assert!(
+        messages[0].starts_with("Register u8 with a custom id at "),
+        "{messages:?}"
+    );
+    assert!(
+        messages[2].starts_with("Register keyed u16 at "),
+        "{messages:?}"
+    );
+    assert!(
+        messages[5].starts_with("Register factory of i8 depending on () at "),
+        "{messages:?}"
+    );
+    assert!(messages.iter().all(|m| m.contains(file)), "{messages:?}");
+    drop(messages);
+
+    #[cfg(any(debug_assertions, feature = "runtime_checks"))]
+    {
+        let mut collection = ServiceCollection::new();
+        collection.register(|| ());
+        collection.with::<Registered<()>>().register(|()| 1u8);
+        let provider = collection.build().unwrap();
+        provider.get::<u8>();
+        provider.get::<()>();
+        assert_eq!(2, provider.zero_sized_constructions());
+        drop(provider);
+        assert_eq!(
+            Some("Constructed 2 transient services of zero sized types"),
+            MESSAGES.lock().unwrap().last().map(String::as_str)
+        );
+    }
+}
@@ -0,0 +1,28 @@
+// The limit is global, so these tests have their own binary
+#![cfg(feature = "resolution_depth_limit")]
+
+use minfac::{set_max_resolution_depth, ResolutionError, ServiceCollection, WeakServiceProvider};
+
+#[test]
+fn exceeding_resolution_depth_panics_and_resets_depth() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    collection
+        .with::<WeakServiceProvider>()
+        .register(|p| p.get::<u32>().unwrap() + 1);
+    let provider = collection.build().unwrap();
+
+    let previous = set_max_resolution_depth(8);
+    match provider.try_get::<u32>() {
+        Err(ResolutionError::Panicked { message, .. }) => {
+            assert!(
+                message.contains("maximum resolution depth of 8"),
+                "{message}"
+            )
+        }
+        x => panic!("Expected the resolution to exceed the depth, got {x:?}"),
+    }
+    // The depth of the failed resolution was unwound, so shallow resolutions still work
+    assert_eq!(Some(1), provider.get::<u8>());
+    set_max_resolution_depth(previous);
+}
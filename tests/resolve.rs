@@ -1,6 +1,8 @@
 use core::sync::atomic::{AtomicI32, Ordering};
 use minfac::{
-    AllRegistered, BuildError, Registered, Resolvable, ServiceCollection, WeakServiceProvider,
+    AllRegistered, Arced, AtLeastOne, BuildError, CollectInto, Either, Factory, FromServices,
+    Keyed, Lazy, OneOf, OwnedOrShared, Registered, RegisteredArray, ResolutionError, Resolvable,
+    ScopeLocal, ServiceCollection, ServiceOrigin, Shared, TypedKey, WeakArc, WeakServiceProvider,
 };
 use std::sync::Arc;
 
@@ -385,3 +387,2240 @@ fn register_multiple_alias_per_type() {
     assert_eq!(Some(2i32), prov.get());
     assert_eq!(Some(4i64), prov.get());
 }
+
+#[test]
+fn alias_all_maps_each_registered_instance() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(ServiceImpl(Box::new(1))))
+        .alias_all(|a| a as Arc<dyn Service + Send + Sync>);
+    col.register_shared(|| Arc::new(ServiceImpl(Box::new(2))));
+    col.register_shared(|| Arc::new(ServiceImpl(Box::new(3))));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let values: Vec<_> = provider
+        .get_all::<Arc<dyn Service + Send + Sync>>()
+        .map(|s| s.get_value())
+        .collect();
+    assert_eq!(vec![1, 2, 3], values);
+    assert_eq!(
+        Some(3),
+        provider
+            .get::<Arc<dyn Service + Send + Sync>>()
+            .map(|s| s.get_value())
+    );
+}
+
+#[test]
+fn alias_all_of_alias_all_maps_each_instance() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i16).alias_all(|a| a as i64 * 10);
+    col.register(|| 1i8).alias_all(|a| a as i16 * 2);
+    col.register(|| 2i8);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(
+        vec![1i16, 2, 4],
+        provider.get_all::<i16>().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![10i64, 20, 40],
+        provider.get_all::<i64>().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn child_alias_of_parent_type_is_resolved_after_parents_are_merged() {
+    let mut parent = ServiceCollection::new();
+    parent.register_shared(|| Arc::new(ServiceImpl(Box::new(1))));
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child
+        .with::<Registered<Arc<ServiceImpl<Box<i32>>>>>()
+        .register(|a| ServiceImpl(Box::new(a.get_value() * 10)))
+        .alias(|a| Box::new(a) as Box<dyn Service + Send + Sync>);
+    assert!(matches!(
+        child.build(),
+        Err(BuildError::MissingDependency { .. })
+    ));
+
+    let mut child = ServiceCollection::new();
+    child
+        .with::<Registered<Arc<ServiceImpl<Box<i32>>>>>()
+        .register(|a| ServiceImpl(Box::new(a.get_value() * 10)))
+        .alias(|a| Box::new(a) as Box<dyn Service + Send + Sync>);
+    let factory = child
+        .with_parent(&parent_provider)
+        .build_factory::<()>()
+        .expect("Parent provides Arc<ServiceImpl<_>>");
+    let child_provider = factory.build(());
+
+    assert_eq!(
+        Some(10),
+        child_provider
+            .get::<Box<dyn Service + Send + Sync>>()
+            .map(|s| s.get_value())
+    );
+}
+
+#[test]
+fn multi_base_factory_orders_base_after_inherited_and_registered_services() {
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1u8);
+    parent.register(|| 2i32);
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.register(|| 3i32);
+    child
+        .with::<(Registered<u8>, AllRegistered<i32>)>()
+        .register(|(a, b)| a as i64 + b.sum::<i32>() as i64);
+    let mut factory = child
+        .with_parent(&parent_provider)
+        .build_multi_base_factory();
+
+    for _ in 0..2 {
+        let provider = factory.build(4i32).unwrap();
+        assert_eq!(vec![2, 3, 4], provider.get_all::<i32>().collect::<Vec<_>>());
+        assert_eq!(Some(10), provider.get::<i64>());
+    }
+    let provider = factory.build(5u16).unwrap();
+    assert_eq!(Some(5), provider.get::<u16>());
+    assert_eq!(Some(6), provider.get::<i64>());
+}
+
+#[test]
+fn child_shared_service_depends_on_parent_only_types() {
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1u8);
+    parent.register_shared(|| Arc::new(2u16));
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child
+        .with::<(Registered<u8>, Registered<Arc<u16>>)>()
+        .register_shared(|(a, b)| Arc::new(a as u32 + *b as u32))
+        .alias(|x| *x as u64);
+    let factory = child
+        .with_parent(&parent_provider)
+        .build_factory::<()>()
+        .expect("Parent provides u8 and Arc<u16>");
+    let child_provider = factory.build(());
+
+    assert_eq!(Some(3), child_provider.get::<Arc<u32>>().map(|x| *x));
+    assert_eq!(Some(3), child_provider.get::<u64>());
+}
+
+#[test]
+fn resolve_bundle_is_none_if_any_field_is_missing() {
+    struct Bundle {
+        a: u8,
+        b: u16,
+        c: Arc<u32>,
+        d: minfac::ServiceIterator<i64>,
+    }
+
+    let mut col = ServiceCollection::new();
+    col.register(|| 1u8);
+    col.register_shared(|| Arc::new(3u32));
+    let provider = col.build().unwrap();
+    assert!(minfac::resolve_bundle!(provider, Bundle {
+        a: Registered<u8>,
+        b: Registered<u16>,
+        c: Registered<Arc<u32>>,
+        d: AllRegistered<i64>,
+    })
+    .is_none());
+
+    let mut col = ServiceCollection::new();
+    col.register(|| 1u8);
+    col.register(|| 2u16);
+    col.register_shared(|| Arc::new(3u32));
+    let provider = col.build().unwrap();
+    let bundle = minfac::resolve_bundle!(provider, Bundle {
+        a: Registered<u8>,
+        b: Registered<u16>,
+        c: Registered<Arc<u32>>,
+        d: AllRegistered<i64>,
+    })
+    .unwrap();
+    assert_eq!((1, 2, 3), (bundle.a, bundle.b, *bundle.c));
+    assert_eq!(0, bundle.d.count());
+}
+
+#[test]
+fn enumerate_indices_yields_producer_positions_of_remaining_services() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i32);
+    col.register(|| 2i32);
+    col.register(|| 3i32);
+    let provider = col.build().unwrap();
+
+    // Positions of the same type are contiguous
+    let (_, last) = provider.get_with_source::<i32>().unwrap();
+    let expected = [(last - 2, 1), (last - 1, 2), (last, 3)];
+    let mut iter = provider.get_all::<i32>();
+    iter.next();
+    assert_eq!(expected[1..], iter.enumerate_indices().collect::<Vec<_>>());
+    assert_eq!(
+        expected.iter().rev().copied().collect::<Vec<_>>(),
+        provider
+            .get_all::<i32>()
+            .enumerate_indices()
+            .rev()
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn register_shared_background_constructs_on_another_thread() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(2u8));
+    col.with::<Registered<Arc<u8>>>()
+        .register_shared_background(|factor| {
+            move || Arc::new((*factor as u64, std::thread::current().id()))
+        });
+    let provider = col.build().unwrap();
+
+    let background = provider
+        .get::<Arc<minfac::Background<(u64, std::thread::ThreadId)>>>()
+        .unwrap();
+    let service = provider.get::<Arc<(u64, std::thread::ThreadId)>>().unwrap();
+    assert!(background.is_ready());
+    assert_eq!(2, service.0);
+    assert_ne!(std::thread::current().id(), service.1);
+    assert!(Arc::ptr_eq(&service, &background.wait()));
+}
+
+#[test]
+fn dropping_unawaited_background_service_joins_its_thread() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(2u8));
+    col.with::<Registered<Arc<u8>>>()
+        .register_shared_background(|factor| {
+            move || {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                Arc::new(*factor as u16)
+            }
+        });
+    let provider = col.build().unwrap();
+    let background = provider.get::<Arc<minfac::Background<u16>>>().unwrap();
+    drop(background);
+    // The creator still holds Arc<u8>, which must not outlive the provider
+    drop(provider);
+}
+
+#[test]
+fn get_boxed_resolves_marked_types_including_inherited_ones() {
+    use core::any::TypeId;
+
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1u8).boxable();
+    parent.register_shared(|| Arc::new(2u16)).boxable();
+    parent.register(|| 3u32);
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.register(|| 4u8);
+    child
+        .with::<Registered<u8>>()
+        .register(|x| x as i64)
+        .boxable();
+    let child_provider = child
+        .with_parent(&parent_provider)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+
+    let boxed = child_provider.get_boxed(TypeId::of::<u8>()).unwrap();
+    assert_eq!(Some(&4u8), boxed.downcast_ref());
+    let boxed = child_provider.get_boxed(TypeId::of::<i64>()).unwrap();
+    assert_eq!(Some(&4i64), boxed.downcast_ref());
+    let boxed = child_provider
+        .get_boxed(TypeId::of::<Arc<u16>>())
+        .unwrap()
+        .downcast::<Arc<u16>>()
+        .unwrap();
+    assert_eq!(2, **boxed);
+    drop(boxed);
+    assert!(child_provider.get_boxed(TypeId::of::<u32>()).is_none());
+    assert!(parent_provider.get_boxed(TypeId::of::<i64>()).is_none());
+}
+
+#[test]
+fn innermost_resolution_context_is_visible_until_its_scope_returns() {
+    let mut col = ServiceCollection::new();
+    col.with::<(
+        minfac::ResolutionContext<u32>,
+        minfac::ResolutionContext<&str>,
+    )>()
+    .register(|(id, name)| (id, name));
+    let provider = col.build().unwrap();
+    let get = || provider.get::<(Option<u32>, Option<&str>)>().unwrap();
+
+    provider.with_context(1u32, || {
+        assert_eq!((Some(1), None), get());
+        provider.with_context("inner", || {
+            provider.with_context(2u32, || assert_eq!((Some(2), Some("inner")), get()));
+            assert_eq!((Some(1), Some("inner")), get());
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            provider.with_context(3u32, || panic!("Scope panics"))
+        }));
+        assert!(result.is_err());
+        assert_eq!((Some(1), None), get());
+    });
+    assert_eq!((None, None), get());
+}
+
+#[test]
+fn resolution_context_is_local_to_the_resolving_thread() {
+    let mut col = ServiceCollection::new();
+    col.with::<minfac::ResolutionContext<u32>>().register(|x| x);
+    let provider = col.build().unwrap();
+
+    provider.with_context(1u32, || {
+        std::thread::scope(|s| {
+            s.spawn(|| assert_eq!(Some(None), provider.get::<Option<u32>>()));
+        });
+        assert_eq!(Some(Some(1)), provider.get::<Option<u32>>());
+    });
+}
+
+#[test]
+fn origin_of_reports_the_level_providing_each_service() {
+    let mut root = ServiceCollection::new();
+    root.register(|| 1u8);
+    root.register(|| 2u16);
+    let root_provider = root.build().unwrap();
+
+    let mut app = ServiceCollection::new();
+    app.register(|| 3u16);
+    app.register_shared(|| Arc::new(4u32));
+    let app_provider = app
+        .with_parent(&root_provider)
+        .build_factory::<i8>()
+        .unwrap()
+        .build(5);
+
+    assert_eq!(
+        Some(ServiceOrigin::Parent(0)),
+        app_provider.origin_of::<u8>()
+    );
+    assert_eq!(Some(ServiceOrigin::Local), app_provider.origin_of::<u16>());
+    assert_eq!(
+        Some(ServiceOrigin::Local),
+        app_provider.origin_of::<Arc<u32>>()
+    );
+    assert_eq!(Some(ServiceOrigin::Base), app_provider.origin_of::<i8>());
+
+    let mut request = ServiceCollection::new();
+    request.register(|| 6u64);
+    let request_provider = request
+        .with_parent(&app_provider)
+        .build_factory::<i16>()
+        .unwrap()
+        .build(7);
+
+    assert_eq!(
+        Some(ServiceOrigin::Parent(0)),
+        request_provider.origin_of::<u8>()
+    );
+    assert_eq!(
+        Some(ServiceOrigin::Parent(0)),
+        request_provider.origin_of::<u16>()
+    );
+    assert_eq!(
+        Some(ServiceOrigin::Parent(0)),
+        request_provider.origin_of::<i8>()
+    );
+    assert_eq!(
+        Some(ServiceOrigin::Local),
+        request_provider.origin_of::<u64>()
+    );
+    assert_eq!(
+        Some(ServiceOrigin::Base),
+        request_provider.origin_of::<i16>()
+    );
+    assert_eq!(None, request_provider.origin_of::<i32>());
+    assert_eq!(Some(ServiceOrigin::Local), root_provider.origin_of::<u8>());
+}
+
+#[test]
+fn explain_lists_transients_per_construction_and_shared_services_once() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1u8);
+    col.with::<Registered<u8>>()
+        .register_shared(|x| Arc::new(x as u16));
+    col.with::<(Registered<u8>, Registered<Arc<u16>>)>()
+        .register(|(a, b)| a as u32 + *b as u32);
+    let provider = col.build().unwrap();
+
+    let plan = provider.explain::<u32>().unwrap();
+    assert_eq!(
+        "[\"u8\", \"u8\", \"alloc::sync::Arc<u16>\", \"u32\"]",
+        format!("{plan:?}")
+    );
+    let detailed = format!("{plan:#?}");
+    assert!(
+        detailed.contains("alloc::sync::Arc<u16> (shared)"),
+        "{detailed}"
+    );
+    assert_eq!(
+        vec!["u8", "u8", "u32"],
+        plan.transient().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["alloc::sync::Arc<u16>"],
+        provider.uninitialized_shared()
+    );
+    assert!(provider.explain::<i64>().is_none());
+}
+
+#[test]
+fn register_from_env_reports_unparseable_variable() {
+    std::env::set_var("MINFAC_TEST_UNPARSEABLE", "not a number");
+    let mut col = ServiceCollection::new();
+    col.register_from_env::<u32>("MINFAC_TEST_UNPARSEABLE")
+        .alias(|x| x as u64);
+    match col.build() {
+        Err(BuildError::EnvironmentVariable { name, message, .. }) => {
+            assert_eq!("MINFAC_TEST_UNPARSEABLE", name);
+            assert!(message.contains("invalid digit"), "{message}");
+        }
+        _ => panic!("Build with unparseable variable should fail"),
+    }
+}
+
+#[test]
+fn alias_all_includes_instances_of_parent() {
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1i8);
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.register(|| 2i8).alias_all(|a| a as i16);
+    let factory = child
+        .with_parent(&parent_provider)
+        .build_factory::<i32>()
+        .unwrap();
+    let child_provider = factory.build(1);
+
+    assert_eq!(
+        vec![1i16, 2],
+        child_provider.get_all::<i16>().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn shared_service_depends_on_shared_service_registered_later() {
+    let mut col = ServiceCollection::new();
+    col.with::<Registered<Arc<i32>>>()
+        .register_shared(|i| Arc::new(*i as i64 * 2));
+    col.register_shared(|| Arc::new(21i32));
+    let provider = col.build().expect("Registration order shouldn't matter");
+
+    assert_eq!(Some(42), provider.get::<Arc<i64>>().map(|x| *x));
+}
+
+#[test]
+fn shared_service_depends_on_transient_registered_later() {
+    let mut col = ServiceCollection::new();
+    col.with::<(Registered<i32>, AllRegistered<i8>)>()
+        .register_shared(|(i, all)| Arc::new(i as i64 + all.map(|x| x as i64).sum::<i64>()));
+    col.register(|| 40i32);
+    col.register(|| 1i8);
+    col.register(|| 1i8);
+    let provider = col.build().expect("Registration order shouldn't matter");
+
+    assert_eq!(Some(42), provider.get::<Arc<i64>>().map(|x| *x));
+}
+
+#[test]
+fn shared_service_count_equals_shared_registrations() {
+    let mut col = ServiceCollection::new();
+    col.with::<Registered<Arc<i32>>>()
+        .register_shared(|i| Arc::new(*i as i64));
+    col.register_shared(|| Arc::new(1i32));
+    col.register(|| 1u8);
+    col.with::<Registered<u8>>().register(|x| x as u16);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(2, provider.shared_service_count());
+}
+
+#[test]
+fn register_indexed_passes_unique_position() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i32);
+    col.with::<Registered<i32>>()
+        .register_indexed(|index, i| (index, i as i64));
+    col.with::<Registered<i32>>()
+        .register_indexed(|index, i| (index, i as i64 + 1));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let first: Vec<_> = provider.get_all::<(usize, i64)>().collect();
+    let second: Vec<_> = provider.get_all::<(usize, i64)>().collect();
+    assert_eq!(first, second);
+    assert_eq!(
+        vec![1, 2],
+        first.iter().map(|(_, x)| *x).collect::<Vec<_>>()
+    );
+    assert_eq!(first[0].0 + 1, first[1].0);
+}
+
+#[test]
+fn try_get_converts_panic_of_dependency_to_error() {
+    let mut col = ServiceCollection::new();
+    col.register(|| -> i32 { panic!("Misbehaving {}", "dependency") });
+    col.with::<Registered<i32>>().register(|i| i as i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    match provider.try_get::<i64>() {
+        Err(ResolutionError::Panicked { name, message, .. }) => {
+            assert_eq!("i64", name);
+            assert_eq!("Misbehaving dependency", message);
+        }
+        x => panic!("Expected panic to be caught, got {x:?}"),
+    }
+}
+
+#[test]
+fn try_get_retries_shared_service_after_panic() {
+    static CALLS: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| {
+        if CALLS.fetch_add(1, Ordering::Relaxed) == 0 {
+            panic!("First initialization fails");
+        }
+        Arc::new(42i32)
+    });
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert!(matches!(
+        provider.try_get::<Arc<i32>>(),
+        Err(ResolutionError::Panicked { .. })
+    ));
+    assert_eq!(Ok(42), provider.try_get::<Arc<i32>>().map(|x| *x));
+}
+
+#[test]
+fn try_get_missing_service() {
+    let provider = ServiceCollection::new().build().unwrap();
+    match provider.try_get::<i32>() {
+        Err(ResolutionError::MissingService { name, .. }) => assert_eq!("i32", name),
+        x => panic!("Expected MissingService, got {x:?}"),
+    }
+}
+
+#[test]
+fn try_get_all_continues_after_panicking_service() {
+    let mut col = ServiceCollection::new();
+    col.register(|| -> i32 { panic!("First fails") });
+    col.register(|| 2);
+    col.register(|| -> i32 { panic!("Last fails") });
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let results: Vec<_> = provider.try_get_all::<i32>().collect();
+    assert_eq!(3, results.len());
+    assert!(
+        matches!(&results[0], Err(ResolutionError::Panicked { message, .. }) if message == "First fails")
+    );
+    assert_eq!(Ok(2), results[1]);
+    assert!(
+        matches!(&results[2], Err(ResolutionError::Panicked { message, .. }) if message == "Last fails")
+    );
+    assert_eq!(0, provider.try_get_all::<i64>().count());
+}
+
+#[test]
+fn register_all_shared_initializes_each_service_once() {
+    static CALLS: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_all_shared(&[
+        || {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            Arc::new(1i32)
+        },
+        || {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            Arc::new(2i32)
+        },
+    ])
+    .alias_all(|x| *x as i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(2, provider.shared_service_count());
+    let first: Vec<_> = provider.get_all::<Arc<i32>>().collect();
+    let second: Vec<_> = provider.get_all::<Arc<i32>>().collect();
+    assert_eq!(2, CALLS.load(Ordering::Relaxed));
+    assert!(first
+        .iter()
+        .zip(second.iter())
+        .all(|(a, b)| Arc::ptr_eq(a, b)));
+    assert_eq!(vec![1i64, 2], provider.get_all::<i64>().collect::<Vec<_>>());
+}
+
+#[test]
+fn register_shared_mutex_is_shared_across_threads() {
+    let mut col = ServiceCollection::new();
+    col.register_shared_mutex(|| core::cell::Cell::new(0i32));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let counter = provider
+                    .get::<Arc<std::sync::Mutex<core::cell::Cell<i32>>>>()
+                    .unwrap();
+                let guard = counter.lock().unwrap();
+                guard.set(guard.get() + 1);
+            });
+        }
+    });
+    let counter = provider
+        .get::<Arc<std::sync::Mutex<core::cell::Cell<i32>>>>()
+        .unwrap();
+    assert_eq!(4, counter.lock().unwrap().get());
+}
+
+#[test]
+fn register_struct_with_many_fields() {
+    #[derive(Debug, PartialEq)]
+    struct Wide {
+        a: u8,
+        b: u16,
+        c: u32,
+        d: u64,
+        e: Arc<i32>,
+    }
+    struct Empty {}
+    let mut col = ServiceCollection::new();
+    col.register(|| 1u8);
+    col.register(|| 2u16);
+    col.register(|| 3u32);
+    col.register(|| 4u64);
+    col.register_shared(|| Arc::new(5i32));
+    minfac::register_struct!(col, Wide {
+        a: Registered<u8>,
+        b: Registered<u16>,
+        c: Registered<u32>,
+        d: Registered<u64>,
+        e: Registered<Arc<i32>>,
+    });
+    minfac::register_struct!(col, shared Empty {}).alias(|_| 6i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let wide = provider.get::<Wide>().unwrap();
+    assert_eq!((1, 2, 3, 4, 5), (wide.a, wide.b, wide.c, wide.d, *wide.e));
+    drop(wide);
+    assert!(provider.get::<Arc<Empty>>().is_some());
+    assert_eq!(Some(6), provider.get::<i64>());
+}
+
+#[test]
+fn register_all_traits_shares_instance_only_for_shared_services() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    trait Id {
+        fn id(&self) -> i32;
+    }
+    trait Double {
+        fn double(&self) -> i32;
+    }
+    struct Service(i32);
+    impl Id for Service {
+        fn id(&self) -> i32 {
+            self.0
+        }
+    }
+    impl Double for Service {
+        fn double(&self) -> i32 {
+            self.0 * 2
+        }
+    }
+    fn create() -> Service {
+        Service(CREATED.fetch_add(1, Ordering::Relaxed))
+    }
+    let mut col = ServiceCollection::new();
+    minfac::register_all_traits!(
+        col,
+        shared || Arc::new(create()),
+        dyn Id + Send + Sync,
+        dyn Double + Send + Sync
+    );
+    minfac::register_all_traits!(col, create, dyn Id, dyn Double).alias(|s| s.0 as i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let shared_id = provider.get::<Arc<dyn Id + Send + Sync>>().unwrap().id();
+    let shared_double = provider
+        .get::<Arc<dyn Double + Send + Sync>>()
+        .unwrap()
+        .double();
+    assert_eq!(shared_id * 2, shared_double);
+    let transient_id = provider.get::<Box<dyn Id>>().unwrap().id();
+    let transient_double = provider.get::<Box<dyn Double>>().unwrap().double();
+    assert_eq!((transient_id + 1) * 2, transient_double);
+    assert_eq!(Some(transient_id as i64 + 2), provider.get::<i64>());
+}
+
+#[test]
+fn register_with_provider_resolves_lazily_from_resolving_provider() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(42i32));
+    col.with::<()>()
+        .register_with_provider(|provider, ()| provider.get::<Arc<i32>>().map(|x| *x as i64));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(Some(42)), provider.get::<Option<i64>>());
+    assert_eq!(Some(42), provider.get::<Arc<i32>>().map(|x| *x));
+}
+
+#[test]
+fn build_instrumented_reports_each_producer() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1u8);
+    col.register(|| 2u8);
+    col.register_shared(|| Arc::new(1i32));
+    col.with::<Registered<u8>>().register(|x| x as u16);
+    let mut names = Vec::new();
+    col.build_instrumented(|name, _| names.push(name))
+        .expect("Expected to have all dependencies");
+
+    names.sort_unstable();
+    assert_eq!(vec!["alloc::sync::Arc<i32>", "u16", "u8", "u8"], names);
+}
+
+#[test]
+fn resolve_arced_creates_new_instance_per_resolution() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 42i32);
+    col.with::<Arced<i32>>()
+        .register(|x| x as Arc<dyn core::any::Any + Send + Sync>);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let first = provider.resolve_unchecked::<Arced<i32>>();
+    let second = provider.resolve_unchecked::<Arced<i32>>();
+    assert_eq!((42, 42), (*first, *second));
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert!(provider.get::<Arc<i32>>().is_none());
+    assert!(provider
+        .get::<Arc<dyn core::any::Any + Send + Sync>>()
+        .is_some());
+}
+
+#[test]
+fn build_with_missing_arced_dep_fails() {
+    build_with_missing_dependency_fails::<Arced<String>>("String");
+}
+
+#[test]
+fn build_or_report_lists_registered_services() {
+    let result = std::panic::catch_unwind(|| {
+        let mut col = ServiceCollection::new();
+        col.with::<Registered<String>>()
+            .register(|s| s.len() as i64);
+        col.register(|| 1i32);
+        col.build_or_report();
+    });
+    let err = result.expect_err("Expected build_or_report to panic");
+    let msg = err
+        .downcast_ref::<String>()
+        .expect("Panic with formatted message");
+    assert!(msg.contains("MissingDependency"));
+    assert!(msg.contains("\n  i32"));
+    assert!(msg.contains("\n  i64 <- minfac::Registered<alloc::string::String>"));
+}
+
+#[test]
+fn build_or_report_returns_provider() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i32);
+    assert_eq!(Some(1), col.build_or_report().get::<i32>());
+}
+
+#[test]
+fn resolve_weak_arc_of_shared_service() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(42i32));
+    col.with::<WeakArc<i32>>()
+        .register(|weak| weak.upgrade().map(|x| *x as i64));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(Some(42i64)), provider.get::<Option<i64>>());
+    let weak = provider.resolve_unchecked::<WeakArc<i32>>();
+    assert_eq!(Some(42), weak.upgrade().map(|x| *x));
+    drop(provider);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn resolve_shared_reference_without_cloning_arc() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(String::from("Hello")));
+    col.register(|| 42i32);
+    col.with::<(Shared<String>, Registered<i32>)>()
+        .register(|(text, number)| format!("{text} {number}"));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some("Hello 42".to_string()), provider.get::<String>());
+    let text = provider.resolve_unchecked::<Shared<String>>();
+    assert_eq!("Hello", text);
+    let arc = provider.get::<Arc<String>>().unwrap();
+    assert_eq!(2, Arc::strong_count(&arc));
+    assert!(core::ptr::eq(text, &*arc));
+}
+
+#[test]
+fn tuples_mix_owned_and_borrowed_dependencies() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(String::from("Hello")));
+    col.register_shared(|| Arc::new(3u8));
+    col.register(|| 42i32);
+    col.with::<(Registered<i32>, Shared<String>, Shared<u8>)>()
+        .register_shared(|(number, text, repeat)| {
+            Arc::new(vec![format!("{text} {number}"); *repeat as usize])
+        });
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let (number, text, repeat) = provider
+        .resolve_refs::<(Registered<i32>, Shared<String>, Shared<u8>)>()
+        .unwrap();
+    assert_eq!((42, "Hello", 3), (number, text.as_str(), *repeat));
+    assert_eq!(
+        Some(3),
+        provider
+            .resolve_refs::<Shared<Vec<String>>>()
+            .map(|all| all.iter().filter(|x| *x == "Hello 42").count())
+    );
+}
+
+#[test]
+fn resolve_refs_of_missing_shared_service_is_none() {
+    let mut col = ServiceCollection::new();
+    col.register(|| Arc::new(1i32));
+    let provider = col.build().unwrap();
+
+    assert!(provider.resolve_refs::<Shared<i32>>().is_none());
+    assert!(provider.resolve_refs::<Registered<Arc<i32>>>().is_some());
+}
+
+#[test]
+fn build_with_transient_arc_for_shared_dep_fails() {
+    let mut col = ServiceCollection::new();
+    col.register(|| Arc::new(1i32));
+    col.with::<Shared<i32>>().register(|x| *x as i64);
+    match col.build() {
+        Err(BuildError::MissingDependency { name, .. }) => {
+            assert!(name.contains("Arc<i32>"), "{}", name)
+        }
+        Err(e) => panic!("Unexpected error {e:?}"),
+        Ok(_) => panic!("Expected MissingDependency"),
+    }
+}
+
+#[test]
+fn resolve_shared_reference_of_parent_provider() {
+    let mut parent = ServiceCollection::new();
+    parent.register_shared(|| Arc::new(7u8));
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.with::<Shared<u8>>().register(|x| *x as u16);
+    let factory = child
+        .with_parent(&parent_provider)
+        .build_factory::<i32>()
+        .unwrap();
+    let child_provider = factory.build(1);
+
+    assert_eq!(Some(7u16), child_provider.get::<u16>());
+    assert_eq!(Some(&7u8), child_provider.resolve_refs::<Shared<u8>>());
+}
+
+#[test]
+fn build_with_missing_shared_dep_fails() {
+    build_with_missing_dependency_fails::<Shared<String>>("String");
+}
+
+#[test]
+fn build_with_missing_weak_arc_dep_fails() {
+    build_with_missing_dependency_fails::<WeakArc<String>>("String");
+}
+
+#[test]
+#[should_panic(expected = "Failed to build ServiceProvider: MissingDependency")]
+fn assert_collection_valid_panics_for_invalid_collection() {
+    minfac::assert_collection_valid!(|col: &mut ServiceCollection| {
+        col.with::<Registered<String>>().register(|s| s.len());
+    });
+}
+
+#[test]
+fn finalizers_of_shared_services_are_called_only_if_initialized() {
+    static FINALIZED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(1i32))
+        .on_drop(|i| {
+            FINALIZED.fetch_add(*i, Ordering::Relaxed);
+        })
+        .alias(|a| *a as i64);
+    col.with::<Registered<i64>>()
+        .register_shared(|i| Arc::new(i as u32))
+        .on_drop(|_| {
+            FINALIZED.fetch_add(100, Ordering::Relaxed);
+        });
+    let provider = col.build().expect("Expected to have all dependencies");
+    assert_eq!(Some(1), provider.get::<i64>());
+    drop(provider);
+    assert_eq!(1, FINALIZED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn shared_services_are_released_from_highest_to_lowest_drop_priority() {
+    static RELEASED: std::sync::Mutex<Vec<&str>> = std::sync::Mutex::new(Vec::new());
+    struct Released(&'static str);
+    impl Drop for Released {
+        fn drop(&mut self) {
+            RELEASED.lock().unwrap().push(self.0);
+        }
+    }
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(Released("database")))
+        .drop_priority(-1);
+    col.register_shared(|| Arc::new((Released("default"),)));
+    col.register_shared(|| Arc::new(((Released("cache"),),)))
+        .drop_priority(10);
+    let mut provider = col.build().expect("Expected to have all dependencies");
+    let resolve_all = |provider: &minfac::ServiceProvider| {
+        provider.get::<Arc<Released>>();
+        provider.get::<Arc<(Released,)>>();
+        provider.get::<Arc<((Released,),)>>();
+    };
+
+    resolve_all(&provider);
+    provider.reset_shared().unwrap();
+    assert_eq!(
+        vec!["cache", "default", "database"],
+        core::mem::take(&mut *RELEASED.lock().unwrap())
+    );
+
+    resolve_all(&provider);
+    drop(provider);
+    assert_eq!(
+        vec!["cache", "default", "database"],
+        *RELEASED.lock().unwrap()
+    );
+}
+
+#[test]
+fn build_ref_keeps_collection_reusable_with_independent_instances() {
+    let mut col = ServiceCollection::new();
+    col.register_instance(42u32);
+    col.register_shared(|| Arc::new(1i32))
+        .alias_all(|a| *a as i64);
+    col.with::<Registered<u32>>().register(|i| i as u64);
+
+    let first = col.build_ref().expect("Expected to have all dependencies");
+    let second = col.build_ref().expect("Expected to have all dependencies");
+    for provider in [&first, &second] {
+        assert_eq!(Some(42u64), provider.get::<u64>());
+        assert_eq!(vec![1i64], provider.get_all::<i64>().collect::<Vec<_>>());
+    }
+    assert!(!Arc::ptr_eq(
+        &first.get::<Arc<i32>>().unwrap(),
+        &second.get::<Arc<i32>>().unwrap()
+    ));
+    drop(first);
+    assert_eq!(Some(42u32), col.build().unwrap().get::<u32>());
+}
+
+#[test]
+fn lazy_constructs_service_only_when_called() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register(|| CREATED.fetch_add(1, Ordering::Relaxed) as i64);
+    col.with::<Lazy<i64>>().register(|lazy| lazy);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let lazy = provider.get::<minfac::LazyService<i64>>().unwrap();
+    assert_eq!(0, CREATED.load(Ordering::Relaxed));
+    assert_eq!(0, lazy.get());
+    assert_eq!(1, lazy.get());
+    assert_eq!(2, CREATED.load(Ordering::Relaxed));
+    assert_eq!(
+        Some(2),
+        provider.resolve_refs::<Lazy<i64>>().map(|l| l.get())
+    );
+}
+
+#[test]
+fn build_with_missing_lazy_dep_fails() {
+    build_with_missing_dependency_fails::<Lazy<String>>("String");
+}
+
+#[test]
+fn factory_resolves_dependencies_on_each_create() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register(|| CREATED.fetch_add(1, Ordering::Relaxed));
+    col.with::<Registered<i32>>()
+        .register_factory_fn(|dep, (a, b): (i32, i32)| dep + a * b);
+    col.with::<Registered<Factory<(i32, i32), i32>>>()
+        .register(|factory| factory.create((2, 3)) as i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let factory = provider.get::<Factory<(i32, i32), i32>>().unwrap();
+    assert_eq!(0, CREATED.load(Ordering::Relaxed));
+    assert_eq!(4, factory.create((2, 2)));
+    assert_eq!(1 + 6, factory.create((2, 3)));
+    assert_eq!(Some(2 + 6), provider.get::<i64>());
+    drop(factory);
+}
+
+#[test]
+fn build_with_missing_factory_fn_dep_fails() {
+    let mut col = ServiceCollection::new();
+    col.with::<Registered<String>>()
+        .register_factory_fn(|s, suffix: &'static str| s + suffix);
+    match col.build() {
+        Err(BuildError::MissingDependency { name, .. }) => assert!(name.contains("String")),
+        _ => panic!("Build with missing dependency should fail"),
+    }
+}
+
+#[test]
+fn build_reports_panicking_registration() {
+    struct Broken;
+    impl TypedKey for Broken {
+        type Key = u8;
+        fn key() -> u8 {
+            panic!("Key is not available")
+        }
+    }
+    let mut col = ServiceCollection::new();
+    col.register_keyed(1u8, || 1i32);
+    col.with::<Keyed<Broken, i32>>().register(|i| i as i64);
+
+    match col.build() {
+        Err(BuildError::FactoryPanicked { name, .. }) => assert_eq!("i64", name),
+        _ => panic!("Build with panicking key should fail"),
+    }
+}
+
+#[test]
+fn get_all_sorted_by_key_keeps_registration_order_for_equal_keys() {
+    let mut col = ServiceCollection::new();
+    col.register(|| (2, 'a'));
+    col.register(|| (1, 'b'));
+    col.register(|| (2, 'c'));
+    col.with::<WeakServiceProvider>()
+        .register(|p| p.get_all_sorted_by_key(|(k, _): &(i32, char)| *k));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let expected = vec![(1, 'b'), (2, 'a'), (2, 'c')];
+    assert_eq!(expected, provider.get_all_sorted_by_key(|(k, _)| *k));
+    assert_eq!(Some(expected), provider.get::<Vec<(i32, char)>>());
+}
+
+#[test]
+fn get_erased_resolves_shared_services_inherited_from_parent() {
+    let mut parent = ServiceCollection::new();
+    parent.register_shared(|| Arc::new(String::from("parent")));
+    let parent = parent.build().expect("Expected to have all dependencies");
+    let factory = ServiceCollection::new()
+        .with_parent(&parent)
+        .build_factory::<()>()
+        .unwrap();
+    let child = factory.build(());
+
+    let erased = child
+        .get_erased(core::any::TypeId::of::<Arc<String>>())
+        .unwrap();
+    let from_child = erased.try_clone_as::<String>().unwrap();
+    assert!(Arc::ptr_eq(
+        &from_child,
+        &parent.get::<Arc<String>>().unwrap()
+    ));
+}
+
+#[test]
+fn register_unique_fails_for_duplicates_only() {
+    let mut col = ServiceCollection::new();
+    col.register_unique(|| 1i32);
+    col.register(|| 1i64);
+    col.register(|| 2i64);
+    assert_eq!(Some(1), col.build().unwrap().get::<i32>());
+
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i32);
+    col.register_unique(|| 2i32);
+    match col.build() {
+        Err(BuildError::DuplicateUnique { name, .. }) => assert_eq!("i32", name),
+        _ => panic!("Expected BuildError::DuplicateUnique"),
+    }
+}
+
+#[test]
+fn register_unique_considers_services_of_parent() {
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1i32);
+    let parent = parent.build().expect("Expected to have all dependencies");
+
+    let mut child = ServiceCollection::new();
+    child.register_unique(|| 2i32);
+    assert!(matches!(
+        child.with_parent(&parent).build_factory::<()>(),
+        Err(BuildError::DuplicateUnique { .. })
+    ));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum MessageKind {
+    Text,
+    Binary,
+    Ping,
+}
+
+#[test]
+fn get_keyed_constructs_only_matching_service() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_keyed(MessageKind::Text, || {
+        CREATED.fetch_add(1, Ordering::Relaxed);
+        1
+    });
+    col.register_keyed(MessageKind::Binary, || {
+        CREATED.fetch_add(10, Ordering::Relaxed);
+        2
+    });
+    col.register_keyed(MessageKind::Text, || {
+        CREATED.fetch_add(100, Ordering::Relaxed);
+        3
+    });
+    col.register(|| 4);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(3), provider.get_keyed::<_, i32>(&MessageKind::Text));
+    assert_eq!(100, CREATED.load(Ordering::Relaxed));
+    assert_eq!(Some(2), provider.get_keyed::<_, i32>(&MessageKind::Binary));
+    assert_eq!(None, provider.get_keyed::<_, i32>(&MessageKind::Ping));
+    assert_eq!(None, provider.get_keyed::<_, i64>(&MessageKind::Text));
+    assert_eq!(vec![4], provider.get_all::<i32>().collect::<Vec<_>>());
+}
+
+#[test]
+fn expect_keys_fails_for_missing_variant() {
+    let mut col = ServiceCollection::new();
+    col.register_keyed(MessageKind::Text, || 1);
+    col.register_keyed(MessageKind::Binary, || 2);
+    col.register_keyed(MessageKind::Ping, || 3i64);
+    col.expect_keys::<_, i32>(&[MessageKind::Text, MessageKind::Binary, MessageKind::Ping]);
+
+    match col.build() {
+        Err(BuildError::MissingKey { name, key, .. }) => {
+            assert_eq!("i32", name);
+            assert_eq!("Ping", key);
+        }
+        Err(e) => panic!("Unexpected error {e:?}"),
+        Ok(_) => panic!("Expected MissingKey"),
+    }
+}
+
+#[test]
+fn reset_shared_recreates_services_and_runs_finalizers() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    static FINALIZED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(CREATED.fetch_add(1, Ordering::Relaxed)))
+        .on_drop(|_| {
+            FINALIZED.fetch_add(1, Ordering::Relaxed);
+        });
+    col.with::<WeakServiceProvider>().register(|p| p);
+    let mut provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(0), provider.get::<Arc<i32>>().map(|x| *x));
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+    assert!(provider.reset_shared().is_err());
+    assert_eq!(0, FINALIZED.load(Ordering::Relaxed));
+    drop(weak);
+
+    provider.reset_shared().expect("No outstanding references");
+    assert_eq!(1, FINALIZED.load(Ordering::Relaxed));
+    assert_eq!(Some(1), provider.get::<Arc<i32>>().map(|x| *x));
+}
+
+#[test]
+fn failed_reset_shared_keeps_referenced_services() {
+    static FINALIZED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(1i32)).on_drop(|_| {
+        FINALIZED.fetch_add(1, Ordering::Relaxed);
+    });
+    col.register_shared(|| Arc::new(2i64));
+    let mut provider = col.build().expect("Expected to have all dependencies");
+
+    let outstanding = provider.get::<Arc<i32>>().unwrap();
+    let unreferenced = provider.get::<Arc<i64>>().map(|x| Arc::as_ptr(&x));
+    assert!(provider.reset_shared().is_err());
+    assert_eq!(0, FINALIZED.load(Ordering::Relaxed));
+    assert!(Arc::ptr_eq(
+        &outstanding,
+        &provider.get::<Arc<i32>>().unwrap()
+    ));
+    assert_eq!(
+        unreferenced,
+        provider.get::<Arc<i64>>().map(|x| Arc::as_ptr(&x))
+    );
+
+    drop(outstanding);
+    provider.reset_shared().expect("No outstanding references");
+    assert_eq!(1, FINALIZED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn reset_shared_releases_memoized_values_holding_shared_services() {
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| Arc::new(1i32));
+    col.with::<Registered<Arc<i32>>>().register(|x| (x,));
+    let mut provider = col.build().expect("Expected to have all dependencies");
+
+    assert!(provider.memoize::<(Arc<i32>,)>().is_some());
+    provider
+        .reset_shared()
+        .expect("Only the memoized value references the service");
+
+    let (memoized,) = provider.memoize::<(Arc<i32>,)>().unwrap();
+    assert!(Arc::ptr_eq(&memoized, &provider.get::<Arc<i32>>().unwrap()));
+}
+
+#[test]
+fn registered_array_fails_build_on_count_mismatch() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1);
+    col.register(|| 2);
+    col.with::<RegisteredArray<i32, 3>>()
+        .register(|x| x.iter().sum::<i32>() as i64);
+
+    match col.build() {
+        Err(BuildError::CountMismatch {
+            name,
+            expected,
+            found,
+            ..
+        }) => {
+            assert_eq!("i32", name);
+            assert_eq!(3, expected);
+            assert_eq!(2, found);
+        }
+        Err(e) => panic!("Unexpected error {e:?}"),
+        Ok(_) => panic!("Expected CountMismatch"),
+    }
+}
+
+#[test]
+fn build_errors_display_the_affected_type() {
+    let mut col = ServiceCollection::new();
+    col.with::<Registered<u8>>().register(|x| x as i64);
+    assert_eq!(
+        "Missing dependency u8",
+        col.build().map(|_| ()).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn warm_up_initializes_shared_services_once() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    col.register_shared(|| {
+        CREATED.fetch_add(1, Ordering::Relaxed);
+        Arc::new(42i64)
+    });
+    col.register(|| CREATED.fetch_add(100, Ordering::Relaxed));
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    provider.warm_up();
+    assert_eq!(1, CREATED.load(Ordering::Relaxed));
+    assert_eq!(Some(42), provider.get::<Arc<i64>>().map(|x| *x));
+    provider.warm_up();
+    assert_eq!(1, CREATED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn get_all_scoped_iterates_like_get_all() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1);
+    col.register(|| 2);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let mut sum = 0;
+    for i in provider.get_all_scoped::<i32>() {
+        sum += i;
+    }
+    assert_eq!(3, sum);
+    assert!(provider.get_scoped::<i64>().is_none());
+}
+
+#[test]
+fn collect_into_custom_container() {
+    struct Sum(i32);
+    impl FromIterator<i32> for Sum {
+        fn from_iter<I: IntoIterator<Item = i32>>(iter: I) -> Self {
+            Sum(iter.into_iter().sum())
+        }
+    }
+    impl FromServices for Sum {
+        type Service = i32;
+    }
+
+    let mut col = ServiceCollection::new();
+    col.register(|| 1);
+    col.register(|| 2);
+    col.with::<CollectInto<Sum>>().register(|s| s.0 as i64);
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(3i64), provider.get::<i64>());
+    assert_eq!(
+        Some(0),
+        provider
+            .resolve_refs::<CollectInto<Vec<u8>>>()
+            .map(|x| x.len())
+    );
+}
+
+#[test]
+fn get_with_source_positions_are_equal_for_providers_of_one_factory() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1i64);
+    col.register(|| 2i64);
+    let factory = col.build_factory::<i32>().expect("Configuration is valid");
+    let first = factory.build(1);
+    let second = factory.build(2);
+
+    let (value, position) = first.get_with_source::<i64>().unwrap();
+    assert_eq!(2, value);
+    assert_eq!(Some((2, position)), second.get_with_source::<i64>());
+    assert_ne!(Some(position), second.get_with_source::<i32>().map(|x| x.1));
+    assert_eq!(None, second.get_with_source::<u8>());
+}
+
+struct TextKey;
+impl TypedKey for TextKey {
+    type Key = MessageKind;
+    fn key() -> MessageKind {
+        MessageKind::Text
+    }
+}
+
+#[test]
+fn keyed_dependency_resolves_last_matching_key_after_parent_services() {
+    let mut parent = ServiceCollection::new();
+    parent.register_keyed(MessageKind::Text, || 1);
+    let parent_provider = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.register_keyed(MessageKind::Text, || 2);
+    child.register_keyed(MessageKind::Binary, || 3);
+    child.register_keyed(MessageKind::Text, || 4);
+    child.with::<Keyed<TextKey, i32>>().register(|i| i as i64);
+    let factory = child
+        .with_parent(&parent_provider)
+        .build_factory::<()>()
+        .unwrap();
+    let child_provider = factory.build(());
+
+    assert_eq!(Some(4i64), child_provider.get::<i64>());
+}
+
+#[test]
+fn keyed_dependency_fails_build_for_missing_key() {
+    let mut col = ServiceCollection::new();
+    col.register_keyed(MessageKind::Binary, || 1);
+    col.with::<Keyed<TextKey, i32>>().register(|i| i as i64);
+
+    match col.build() {
+        Err(BuildError::MissingKey { name, key, .. }) => {
+            assert_eq!("i32", name);
+            assert_eq!("Text", key);
+        }
+        Err(e) => panic!("Unexpected error {e:?}"),
+        Ok(_) => panic!("Expected MissingKey"),
+    }
+}
+
+#[test]
+fn at_least_one_resolves_all_instances() {
+    let mut col = ServiceCollection::new();
+    col.register(|| 1);
+    col.register(|| 2);
+    col.with::<AtLeastOne<i32>>()
+        .register(|i| i.map(|x| x as i64).sum::<i64>());
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    assert_eq!(Some(3i64), provider.get::<i64>());
+    assert_eq!(
+        Some(vec![1, 2]),
+        provider
+            .resolve_refs::<AtLeastOne<i32>>()
+            .map(|i| i.collect::<Vec<_>>())
+    );
+    assert!(provider.resolve_refs::<AtLeastOne<u8>>().is_none());
+}
+
+#[test]
+fn service_iterator_skips_without_constructing_services() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut col = ServiceCollection::new();
+    for _ in 0..3 {
+        col.register(|| CREATED.fetch_add(1, Ordering::Relaxed));
+    }
+    let provider = col.build().expect("Expected to have all dependencies");
+
+    let mut iter = provider.get_all::<i32>();
+    assert_eq!(3, iter.len());
+    assert_eq!(Some(0), iter.nth(2));
+    assert_eq!(0, iter.len());
+    assert_eq!(None, iter.nth(5));
+    assert_eq!(1, CREATED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn shared_blocking_is_created_once_on_first_get() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1i64);
+    collection
+        .with::<Registered<i64>>()
+        .register_shared_blocking(|base| async move {
+            CREATED.fetch_add(1, Ordering::Relaxed);
+            Arc::new(base as i32)
+        });
+    let provider = collection.build().unwrap();
+    assert_eq!(0, CREATED.load(Ordering::Relaxed));
+
+    let first = provider.get::<Arc<i32>>().unwrap();
+    let second = provider.get::<Arc<i32>>().unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(1, CREATED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn missing_dependencies_lists_each_unmet_type_once() {
+    use core::any::TypeId;
+
+    let mut collection = ServiceCollection::new();
+    collection.with::<Registered<i32>>().register(|x| x as i64);
+    collection.with::<Registered<u8>>().register(|x| x as u64);
+    collection.with::<Registered<u8>>().register(|x| x as u16);
+    collection.with::<Shared<u32>>().register(|x| *x as i8);
+    collection.register_shared(|| Arc::new(1u32));
+
+    let mut missing: Vec<_> = collection
+        .missing_dependencies(&[])
+        .into_iter()
+        .map(|e| match e {
+            BuildError::MissingDependency { id, .. } => id,
+            e => panic!("Unexpected error {e:?}"),
+        })
+        .collect();
+    missing.sort();
+    let mut expected = vec![TypeId::of::<i32>(), TypeId::of::<u8>()];
+    expected.sort();
+    assert_eq!(expected, missing);
+    assert!(collection
+        .missing_dependencies(&[TypeId::of::<i32>(), TypeId::of::<u8>()])
+        .is_empty());
+}
+
+#[test]
+fn root_moved_to_other_thread_releases_shared_services_there() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(AtomicI32::new(1)));
+    let provider = collection.build().unwrap();
+    let service = provider.get::<Arc<AtomicI32>>().unwrap();
+    let weak = Arc::downgrade(&service);
+    drop(service);
+
+    std::thread::spawn(move || drop(provider)).join().unwrap();
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn alias_registered_with_handle_after_other_registrations() {
+    let mut collection = ServiceCollection::new();
+    let handle = collection.register(|| 1i8).handle();
+    let shared = collection.register_shared(|| Arc::new(2u8)).handle();
+    collection.register(|| 3i32);
+
+    collection.alias_registered(handle, |a| a as i64 * 10);
+    collection.alias_registered(shared, |a| *a as u64 * 10);
+    let provider = collection.build().unwrap();
+    assert_eq!(Some(10i64), provider.get());
+    assert_eq!(Some(20u64), provider.get());
+}
+
+#[test]
+fn either_prefers_transient_and_falls_back_to_shared() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1i32);
+    collection.register_shared(|| Arc::new(2i32));
+    collection.register_shared(|| Arc::new(3i64));
+    let provider = collection.build().unwrap();
+
+    assert!(matches!(
+        provider.resolve_unchecked::<Either<i32>>(),
+        OwnedOrShared::Owned(1)
+    ));
+    let shared = provider.resolve_unchecked::<Either<i64>>();
+    assert_eq!(3, *shared);
+    let expected = provider.get::<Arc<i64>>().unwrap();
+    assert!(matches!(&shared, OwnedOrShared::Shared(x) if Arc::ptr_eq(x, &expected)));
+
+    let mut collection = ServiceCollection::new();
+    collection.with::<Either<u8>>().register(|x| *x as u16);
+    assert!(matches!(
+        collection.build(),
+        Err(BuildError::MissingDependency { .. })
+    ));
+}
+
+#[test]
+#[should_panic(expected = "was resolved while initializing itself")]
+fn resolving_shared_service_during_its_initialization_panics_instead_of_deadlocking() {
+    struct Reentrant;
+    let mut collection = ServiceCollection::new();
+    collection
+        .with::<WeakServiceProvider>()
+        .register_shared(|p| {
+            p.get::<Arc<Reentrant>>();
+            Arc::new(Reentrant)
+        });
+    let provider = collection.build().unwrap();
+    provider.get::<Arc<Reentrant>>();
+}
+
+#[test]
+fn shared_trait_views_resolve_the_same_instance() {
+    static CREATED: AtomicI32 = AtomicI32::new(0);
+    let mut collection = ServiceCollection::new();
+    let mut shared = collection.register_shared(|| {
+        CREATED.fetch_add(1, Ordering::Relaxed);
+        Arc::new(ServiceImpl(Arc::new(42i32)))
+    });
+    shared.alias(|x| x as Arc<dyn Service + Send + Sync>);
+    shared.alias(|x| x as Arc<dyn core::any::Any + Send + Sync>);
+    let provider = collection.build().unwrap();
+
+    let service = provider.get::<Arc<dyn Service + Send + Sync>>().unwrap();
+    let any = provider
+        .get::<Arc<dyn core::any::Any + Send + Sync>>()
+        .unwrap();
+    let concrete = provider.get::<Arc<ServiceImpl<Arc<i32>>>>().unwrap();
+    assert_eq!(42, service.get_value());
+    assert!(core::ptr::addr_eq(Arc::as_ptr(&service), Arc::as_ptr(&any)));
+    assert!(core::ptr::addr_eq(
+        Arc::as_ptr(&service),
+        Arc::as_ptr(&concrete)
+    ));
+    assert_eq!(1, CREATED.load(Ordering::Relaxed));
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "runtime_checks"))]
+fn zero_sized_constructions_counts_transient_unit_services() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| ());
+    collection.register_shared(|| Arc::new(()));
+    let provider = collection.build().unwrap();
+    provider.get::<()>();
+    provider.get::<Arc<()>>();
+    assert_eq!(1, provider.zero_sized_constructions());
+}
+
+#[test]
+fn uninitialized_shared_lists_services_of_child_until_resolved() {
+    let mut parent = ServiceCollection::new();
+    parent.register_shared(|| Arc::new(1u8));
+    let parent = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.register_shared(|| Arc::new(2u16));
+    child
+        .with::<Registered<i32>>()
+        .register_shared(|i| Arc::new(i as i64));
+    let child = child
+        .with_parent(&parent)
+        .build_factory::<i32>()
+        .unwrap()
+        .build(3);
+
+    let mut names = child.uninitialized_shared();
+    names.sort();
+    assert_eq!(
+        vec!["alloc::sync::Arc<i64>", "alloc::sync::Arc<u16>"],
+        names
+    );
+    child.warm_up();
+    assert!(child.uninitialized_shared().is_empty());
+    assert!(parent.uninitialized_shared().is_empty());
+}
+
+#[test]
+fn resolver_gets_services_of_strong_and_weak_providers() {
+    use minfac::Resolver;
+
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    collection.register_shared(|| Arc::new(2u16));
+    let provider = collection.build().unwrap();
+    let weak = WeakServiceProvider::from(&provider);
+
+    assert_eq!(Some(1u8), Resolver::get(&provider));
+    assert_eq!(Some(2u16), Resolver::get::<Arc<u16>>(&weak).map(|x| *x));
+    assert_eq!(None, Resolver::get::<u32>(&weak));
+}
+
+#[test]
+fn group_members_of_different_types_are_resolved_together() {
+    use minfac::{Group, GroupMember};
+
+    struct Values;
+    impl Group for Values {
+        type Member = dyn Service;
+    }
+    struct Constant;
+    impl Service for Constant {
+        fn get_value(&self) -> i32 {
+            1
+        }
+    }
+
+    let mut collection = ServiceCollection::new();
+    collection
+        .register(|| Constant)
+        .in_group::<Values>(|x| Box::new(x));
+    collection
+        .register_shared(|| Arc::new(ServiceImpl(Arc::new(2i32))))
+        .in_group::<Values>(|x| Box::new(ServiceImpl(x.0.clone())));
+    let provider = collection.build().unwrap();
+
+    let mut values: Vec<_> = provider
+        .get_all::<GroupMember<Values>>()
+        .map(|x| x.get_value())
+        .collect();
+    values.sort();
+    assert_eq!(vec![1, 2], values);
+}
+
+#[test]
+fn resolve_subgraph_initializes_transitive_shared_dependencies_only() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(1u8));
+    collection.register_shared(|| Arc::new(2u8));
+    collection.register_shared(|| Arc::new(3u32));
+    collection
+        .with::<AllRegistered<Arc<u8>>>()
+        .register(|x| x.count() as u16);
+    collection.with::<Registered<u16>>().register(|x| x as u64);
+    let provider = collection.build().unwrap();
+
+    assert_eq!(3, provider.uninitialized_shared().len());
+    assert_eq!(Some(2u64), provider.resolve_subgraph::<u64>());
+    assert_eq!(
+        vec!["alloc::sync::Arc<u32>"],
+        provider.uninitialized_shared()
+    );
+    assert_eq!(None, provider.resolve_subgraph::<i8>());
+}
+
+#[test]
+fn resolver_is_generic_over_provider_kinds() {
+    use minfac::Resolver;
+
+    fn sum(resolver: &impl Resolver) -> i32 {
+        resolver.get_all::<i32>().sum::<i32>()
+            + *resolver.resolve_unchecked::<Registered<Arc<i32>>>()
+    }
+
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1i32);
+    collection.register(|| 2i32);
+    collection.register_shared(|| Arc::new(10i32));
+    let provider = collection.build().unwrap();
+
+    assert_eq!(13, sum(&provider));
+    assert_eq!(13, sum(&WeakServiceProvider::from(&provider)));
+}
+
+#[test]
+fn from_parent_skips_local_services_and_is_none_without_parent() {
+    use minfac::FromParent;
+
+    let mut root = ServiceCollection::new();
+    root.register(|| 1i32);
+    let root = root.build().unwrap();
+    let app = ServiceCollection::new()
+        .with_parent(&root)
+        .build_factory::<u8>()
+        .unwrap()
+        .build(0);
+
+    let mut request = ServiceCollection::new();
+    request.register(|| 3i32);
+    request
+        .with::<FromParent<i32>>()
+        .register(|x| x.map(|x| x as i64));
+    let request = request
+        .with_parent(&app)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+
+    assert_eq!(vec![1, 3], request.get_all::<i32>().collect::<Vec<_>>());
+    assert_eq!(Some(Some(1i64)), request.get::<Option<i64>>());
+    assert_eq!(None, root.resolve_unchecked::<FromParent<i32>>());
+}
+
+#[test]
+fn previous_registered_resolves_the_registration_before_the_dependent_service() {
+    use minfac::PreviousRegistered;
+
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1i32);
+    let parent = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child
+        .with::<PreviousRegistered<i32>>()
+        .register(|previous| previous + 1);
+    let child = child
+        .with_parent(&parent)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+    assert_eq!(Some(2), child.get::<i32>());
+    assert!(child.resolve_refs::<PreviousRegistered<i32>>().is_none());
+
+    let mut first = ServiceCollection::new();
+    first
+        .with::<PreviousRegistered<i32>>()
+        .register(|previous| previous + 1);
+    assert!(matches!(
+        first.build(),
+        Err(BuildError::MissingDependency { name: "i32", .. })
+    ));
+
+    let mut other_type = ServiceCollection::new();
+    other_type.register(|| 1i32);
+    other_type
+        .with::<PreviousRegistered<i32>>()
+        .register(|previous| previous as i64);
+    assert!(matches!(
+        other_type.build(),
+        Err(BuildError::MissingDependency { name: "i32", .. })
+    ));
+}
+
+#[test]
+fn services_registered_with_id_keep_their_id_in_child_providers() {
+    #[repr(transparent)]
+    struct Port(u16);
+
+    let mut parent = ServiceCollection::new();
+    unsafe { parent.register_with_id(core::any::TypeId::of::<Port>(), || 8080u16) };
+    let parent = parent.build().unwrap();
+    let mut child = ServiceCollection::new();
+    child
+        .with::<Registered<Port>>()
+        .register(|port| port.0 as u32);
+    let child = child
+        .with_parent(&parent)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+
+    assert_eq!(Some(8080u32), child.get::<u32>());
+    assert_eq!(Some(8080), child.get::<Port>().map(|p| p.0));
+    assert_eq!(None, child.get::<u16>());
+}
+
+#[test]
+fn get_all_mapped_outlives_provider_in_registration_order() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1i32);
+    collection.register(|| 2i32);
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+
+    let from_weak = weak.get_all_mapped(|x: i32| x * 10);
+    drop(weak);
+    let mapped = provider.get_all_mapped(|x: i32| Box::new(x) as Box<dyn core::fmt::Debug>);
+    drop(provider);
+
+    assert_eq!(vec![10, 20], from_weak);
+    assert_eq!("[1, 2]", format!("{mapped:?}"));
+}
+
+#[test]
+fn for_each_drops_every_service_before_constructing_the_next() {
+    static LIVE: AtomicI32 = AtomicI32::new(0);
+    struct Tracked(i32);
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            LIVE.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    let mut collection = ServiceCollection::new();
+    fn tracked(i: i32) -> Tracked {
+        LIVE.fetch_add(1, Ordering::SeqCst);
+        Tracked(i)
+    }
+    collection.register(|| tracked(1));
+    collection.register(|| tracked(2));
+    collection.register(|| tracked(3));
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+
+    let mut visited = Vec::new();
+    provider.for_each(|t: Tracked| {
+        assert_eq!(1, LIVE.load(Ordering::SeqCst));
+        visited.push(t.0);
+    });
+    weak.for_each(|t: Tracked| visited.push(t.0 * 10));
+    drop(weak);
+    drop(provider);
+
+    assert_eq!(0, LIVE.load(Ordering::SeqCst));
+    assert_eq!(vec![1, 2, 3, 10, 20, 30], visited);
+}
+
+#[test]
+fn get_all_rev_returns_latest_registration_first_and_skips_without_constructing() {
+    static CONSTRUCTED: AtomicI32 = AtomicI32::new(0);
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1i32);
+    collection.register(|| {
+        CONSTRUCTED.fetch_add(1, Ordering::Relaxed);
+        2i32
+    });
+    collection.register(|| 3i32);
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+
+    assert_eq!(
+        vec![3, 2, 1],
+        provider.get_all_rev::<i32>().collect::<Vec<_>>()
+    );
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+    assert_eq!(vec![3, 2, 1], weak.get_all_rev::<i32>().collect::<Vec<_>>());
+    drop(weak);
+
+    let mut iter = provider.get_all::<i32>();
+    assert_eq!(Some(3), iter.next_back());
+    assert_eq!(Some(1), iter.nth_back(1));
+    assert_eq!(None, iter.next());
+    assert_eq!(2, CONSTRUCTED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn get_nth_from_last_counts_from_latest_registration() {
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1i32);
+    let parent = parent.build().unwrap();
+    let mut child = ServiceCollection::new();
+    child.register(|| 2i32);
+    child.register(|| 3i32);
+    child.with::<WeakServiceProvider>().register(|p| p);
+    let child = child
+        .with_parent(&parent)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+
+    assert_eq!(child.get::<i32>(), child.get_nth_from_last::<i32>(0));
+    assert_eq!(Some(2), child.get_nth_from_last::<i32>(1));
+    assert_eq!(Some(1), child.get_nth_from_last::<i32>(2));
+    assert_eq!(None, child.get_nth_from_last::<i32>(3));
+    assert_eq!(None, child.get_nth_from_last::<i32>(usize::MAX));
+    assert_eq!(None, child.get_nth_from_last::<u8>(0));
+    let weak = child.get::<WeakServiceProvider>().unwrap();
+    assert_eq!(Some(2), weak.get_nth_from_last::<i32>(1));
+}
+
+#[test]
+fn set_instance_replaces_local_services_but_keeps_dependents_and_parents() {
+    let mut parent = ServiceCollection::new();
+    parent.register_instance(1u8);
+    let parent = parent.build().unwrap();
+
+    let mut child = ServiceCollection::new();
+    child.with::<Registered<u8>>().register(|x| x as u16);
+    child.register_shared(|| Arc::new(2u8)).alias(|x| *x);
+    child.set_instance(3u8);
+    child.set_instance(4u8);
+    let child = child
+        .with_parent(&parent)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+
+    assert_eq!(vec![1u8, 4], child.get_all::<u8>().collect::<Vec<_>>());
+    assert_eq!(Some(4u16), child.get::<u16>());
+    assert!(child.get::<Arc<u8>>().is_some());
+}
+
+#[test]
+fn unreachable_services_list_registrations_outside_of_the_roots_closure() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    collection.register(|| 2u8);
+    collection.with::<Registered<u8>>().register(|x| x as u16);
+    collection.register(|| 1i8);
+    collection.register(|| 2i8);
+    collection.with::<Registered<i8>>().register(|x| x as i16);
+    collection.register(|| "unused");
+
+    let mut unreachable = collection
+        .unreachable_services(&[core::any::TypeId::of::<u16>()])
+        .unwrap();
+    unreachable.sort();
+    // Registered<u8> only depends on the last registration of u8
+    assert_eq!(vec!["&str", "i16", "i8", "u8"], unreachable);
+
+    let mut unreachable = collection
+        .unreachable_services(&[
+            core::any::TypeId::of::<u8>(),
+            core::any::TypeId::of::<i16>(),
+        ])
+        .unwrap();
+    unreachable.sort();
+    assert_eq!(vec!["&str", "i8", "u16"], unreachable);
+}
+
+#[test]
+fn registering_after_validate_builds_like_without_validating() {
+    fn register_first(col: &mut ServiceCollection) {
+        col.register(|| 1u8);
+        col.with::<Registered<u8>>()
+            .register_shared(|x| Arc::new(x as u16));
+    }
+    fn register_second(col: &mut ServiceCollection) {
+        col.register(|| 2u8);
+        col.with::<AllRegistered<u8>>()
+            .register(|x| x.map(|x| x as u32).sum::<u32>());
+    }
+
+    let mut validated = ServiceCollection::new();
+    register_first(&mut validated);
+    validated.validate().unwrap();
+    validated.validate().unwrap();
+    register_second(&mut validated);
+    validated.validate().unwrap();
+    let validated = validated.build().unwrap();
+
+    let mut unvalidated = ServiceCollection::new();
+    register_first(&mut unvalidated);
+    register_second(&mut unvalidated);
+    let unvalidated = unvalidated.build().unwrap();
+
+    for provider in [&validated, &unvalidated] {
+        assert_eq!(Some(2), provider.get::<Arc<u16>>().map(|x| *x));
+        assert_eq!(Some(3), provider.get::<u32>());
+        assert_eq!(2, provider.get_all::<u8>().count());
+    }
+    assert_eq!(validated.describe(), unvalidated.describe());
+}
+
+#[test]
+fn transitive_dependencies_follow_all_registrations_and_list_each_type_once() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    collection.register(|| 2u8);
+    collection
+        .with::<AllRegistered<u8>>()
+        .register(|x| x.count() as u16);
+    collection.with::<Registered<u8>>().register(|x| x as u32);
+    collection.with::<Registered<u16>>().register(|x| x as u32);
+    collection.register(|| 1i8);
+
+    assert_eq!(
+        vec!["u8", "u16"],
+        collection.transitive_dependencies::<u32>().unwrap()
+    );
+    assert!(collection
+        .transitive_dependencies::<i8>()
+        .unwrap()
+        .is_empty());
+
+    collection.with::<Registered<i64>>().register(|x| x as i8);
+    assert!(matches!(
+        collection.transitive_dependencies::<u32>(),
+        Err(BuildError::MissingDependency { .. })
+    ));
+}
+
+#[test]
+fn memoized_services_are_shared_with_weak_providers_and_released_before_shared_services() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(AtomicI32::new(0)));
+    collection
+        .with::<Registered<Arc<AtomicI32>>>()
+        .register(|x| (x.fetch_add(1, Ordering::Relaxed), x));
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+    let (first, _) = weak.memoize::<(i32, Arc<AtomicI32>)>().unwrap();
+    drop(weak);
+    let (second, _) = provider.memoize::<(i32, Arc<AtomicI32>)>().unwrap();
+    let (fresh, _) = provider.get::<(i32, Arc<AtomicI32>)>().unwrap();
+
+    assert_eq!((0, 0, 1), (first, second, fresh));
+    assert_eq!(None, provider.memoize::<u8>());
+}
+
+#[test]
+fn sections_are_projected_from_the_last_registered_instance() {
+    let mut collection = ServiceCollection::new();
+    collection
+        .register_instance((1u8, 2u16))
+        .section(|x| &x.0)
+        .section(|x| &x.1);
+    collection.register(|| (3u8, 4u16));
+    let provider = collection.build().unwrap();
+
+    assert_eq!(Some(3), provider.get::<u8>());
+    assert_eq!(Some(4), provider.get::<u16>());
+}
+
+#[test]
+#[should_panic(
+    expected = "Resolve unknown service (minfac::Registered<u8>, minfac::Registered<i32>)"
+)]
+fn resolve_unchecked_panics_with_the_resolved_type_name() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    let provider = collection.build().unwrap();
+    provider.resolve_unchecked::<(Registered<u8>, Registered<i32>)>();
+}
+
+#[test]
+fn check_against_accepts_inherited_and_shared_services_of_the_host() {
+    let mut root = ServiceCollection::new();
+    root.register_shared(|| Arc::new(1u8));
+    let root = root.build().unwrap();
+    let mut host = ServiceCollection::new();
+    host.register(|| 2i32);
+    let host = host
+        .with_parent(&root)
+        .build_factory::<()>()
+        .unwrap()
+        .build(());
+    let manifest = host.export_manifest();
+
+    let mut plugin = ServiceCollection::new();
+    plugin
+        .with::<(Shared<u8>, Registered<i32>)>()
+        .register(|(a, b)| *a as i64 + b as i64);
+    assert!(plugin.check_against(&manifest).is_empty());
+    assert!(manifest.contains(&core::any::TypeId::of::<Arc<u8>>()));
+
+    plugin.with::<Registered<u16>>().register(|x| x as u64);
+    assert!(matches!(
+        plugin.check_against(&manifest).as_slice(),
+        [BuildError::MissingDependency { name: "u16", .. }]
+    ));
+}
+
+#[test]
+fn fallbacks_are_only_resolved_if_neither_collection_nor_parent_provide_the_type() {
+    let build_child = |parent: &minfac::ServiceProvider, register: fn(&mut ServiceCollection)| {
+        let mut child = ServiceCollection::new();
+        register(&mut child);
+        child
+            .with_parent(parent)
+            .build_factory::<()>()
+            .unwrap()
+            .build(())
+    };
+    let mut parent = ServiceCollection::new();
+    parent.register(|| 1i32);
+    parent.register_fallback(|| 2u8);
+    let parent = parent.build().unwrap();
+
+    let child = build_child(&parent, |c| {
+        c.register_fallback(|| 3i32);
+        c.register_fallback(|| 4i64);
+    });
+    assert_eq!(Some(1), child.get::<i32>());
+    assert_eq!(Some(4), child.get::<i64>());
+    assert_eq!(vec![3, 1], child.get_all::<i32>().collect::<Vec<_>>());
+    drop(child);
+
+    let child = build_child(&parent, |c| {
+        c.register(|| 5u8);
+    });
+    assert_eq!(Some(5), child.get::<u8>());
+    assert_eq!(Some(2), parent.get::<u8>());
+}
+
+#[test]
+fn one_of_prefers_the_first_registered_type_of_the_tuple() {
+    let mut collection = ServiceCollection::new();
+    collection.register(|| 1u8);
+    collection.register(|| 2u16);
+    collection
+        .with::<OneOf<(u32, u16, u8), u64>>()
+        .register(|x| x as i64);
+    let provider = collection.build().unwrap();
+    assert_eq!(Some(2), provider.get::<i64>());
+    assert_eq!(Some(2), provider.resolve_refs::<OneOf<(u32, u16), u64>>());
+
+    let mut collection = ServiceCollection::new();
+    collection
+        .with::<OneOf<(u32, u16), u64>>()
+        .register(|x| x as i64);
+    assert!(matches!(
+        collection.build(),
+        Err(BuildError::MissingDependency { name: "u32", .. })
+    ));
+}
+
+#[test]
+fn cloned_collections_keep_instances_keys_and_aliases_independently() {
+    let instance = Arc::new(AtomicI32::new(0));
+    let mut base = ServiceCollection::new();
+    base.register_instance(instance.clone());
+    base.register_keyed(MessageKind::Text, || 1i32);
+    base.expect_keys::<_, i32>(&[MessageKind::Text, MessageKind::Binary]);
+    base.register(|| 1u8).alias_all(|x| x as u16);
+    base.register_shared(|| Arc::new(1i64));
+
+    let mut variant = base.clone();
+    variant.register_keyed(MessageKind::Binary, || 2i32);
+    variant.register(|| 2u8);
+    assert_eq!(3, Arc::strong_count(&instance));
+
+    assert!(matches!(base.build(), Err(BuildError::MissingKey { .. })));
+    assert_eq!(2, Arc::strong_count(&instance));
+    let variant = variant.build().unwrap();
+    assert_eq!(vec![1, 2], variant.get_all::<u16>().collect::<Vec<_>>());
+    assert_eq!(Some(2), variant.get_keyed::<_, i32>(&MessageKind::Binary));
+    assert!(Arc::ptr_eq(&instance, &variant.get().unwrap()));
+}
+
+#[test]
+fn coercions_of_several_types_are_listed_after_direct_registrations() {
+    trait Named: Send + Sync {
+        fn name(&self) -> &'static str;
+    }
+    struct A;
+    impl Named for A {
+        fn name(&self) -> &'static str {
+            "a"
+        }
+    }
+    struct B;
+    impl Named for B {
+        fn name(&self) -> &'static str {
+            "b"
+        }
+    }
+
+    let mut collection = ServiceCollection::new();
+    collection.register_coercion(|x: Arc<A>| x as Arc<dyn Named>);
+    collection.register_coercion(|x: Arc<B>| x as Arc<dyn Named>);
+    collection.register_shared(|| Arc::new(B));
+    collection.register_shared(|| Arc::new(A));
+    collection.register(|| Arc::new(A) as Arc<dyn Named>);
+    let provider = collection.build().unwrap();
+
+    let names: Vec<_> = provider
+        .get_all::<Arc<dyn Named>>()
+        .map(|x| x.name())
+        .collect();
+    assert_eq!(vec!["a", "a", "b"], names);
+    assert_eq!("b", provider.get::<Arc<dyn Named>>().unwrap().name());
+    assert!(Arc::ptr_eq(
+        &(provider.get::<Arc<B>>().unwrap() as Arc<dyn Named>),
+        &provider.get::<Arc<dyn Named>>().unwrap()
+    ));
+}
+
+#[test]
+fn scope_locals_are_injectable_and_released_before_shared_services() {
+    let mut collection = ServiceCollection::new();
+    collection.register_shared(|| Arc::new(1i32));
+    collection
+        .with::<(ScopeLocal<Arc<i32>>, Shared<i32>)>()
+        .register(|(slot, shared)| {
+            slot.set(Arc::new(*shared))
+                .map_or(0u8, |previous| *previous as u8)
+        });
+    let factory = collection.build_factory::<()>().unwrap();
+    let scope = factory.build(());
+
+    assert_eq!(Some(0), scope.get::<u8>());
+    assert_eq!(Some(1), scope.get::<u8>());
+    let weak = WeakServiceProvider::from(&scope);
+    assert!(weak
+        .resolve_unchecked::<ScopeLocal<Arc<i32>>>()
+        .take()
+        .is_some());
+    drop(weak);
+    scope
+        .resolve_unchecked::<ScopeLocal<Arc<i32>>>()
+        .set(scope.get::<Arc<i32>>().unwrap());
+}
+
+#[test]
+fn services_of_inactive_profiles_are_neither_built_nor_aliased() {
+    #[derive(PartialEq)]
+    enum Profile {
+        Dev,
+        Prod,
+    }
+    let mut collection = ServiceCollection::new();
+    collection
+        .register_for(Profile::Dev, || 1u8)
+        .alias_all(|x| x as u16);
+    collection.register_for(Profile::Prod, || 2u8);
+    collection.with::<Registered<i64>>().register(|x| x as i32);
+    collection.register_for(Profile::Prod, || 3i64);
+
+    assert!(matches!(
+        collection.clone().build(),
+        Err(BuildError::MissingDependency { name: "i64", .. })
+    ));
+
+    collection.set_profile("Prod");
+    assert!(collection.clone().build().is_err());
+
+    collection.set_profile(Profile::Prod);
+    let provider = collection.build().unwrap();
+    assert_eq!(vec![2], provider.get_all::<u8>().collect::<Vec<_>>());
+    assert_eq!(vec![2], provider.get_all::<u16>().collect::<Vec<_>>());
+    assert_eq!(Some(3), provider.get::<i32>());
+}
+
+#[test]
+fn aliases_of_inactive_profiles_are_not_built() {
+    #[derive(PartialEq)]
+    enum Profile {
+        Dev,
+        Prod,
+    }
+    let mut collection = ServiceCollection::new();
+    collection
+        .register_for(Profile::Dev, || 1u8)
+        .alias(|x| x as u16)
+        .alias(|x| x as u32);
+    collection
+        .register_for(Profile::Prod, || 2u8)
+        .alias(|x| x as u16 * 10);
+    collection.set_profile(Profile::Prod);
+
+    let provider = collection.build().expect("Aliases of Dev are skipped");
+    assert_eq!(vec![20], provider.get_all::<u16>().collect::<Vec<_>>());
+    assert_eq!(None, provider.get::<u32>());
+}
@@ -0,0 +1,46 @@
+// The error handler is global, so tests replacing it live in their own binary to not affect other tests
+#![cfg(any(debug_assertions, feature = "runtime_checks"))]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use minfac::{
+    error_handler, scoped_error_handler, LifetimeError, ServiceCollection, WeakServiceProvider,
+};
+use std::sync::Arc;
+
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C-unwind" fn count_errors(_: &LifetimeError) {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn scoped_handler_captures_lifetime_errors_and_restores_previous_on_drop() {
+    let original = error_handler();
+    let mut collection = ServiceCollection::new();
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+    let outliving = provider.get::<WeakServiceProvider>().unwrap();
+    {
+        let _guard = scoped_error_handler(count_errors);
+        assert!(error_handler() as usize == count_errors as *const () as usize);
+        drop(provider);
+        // Reentrant initializations aren't lifetime errors, so they panic without calling the handler
+        assert!(std::panic::catch_unwind(resolve_during_initialization).is_err());
+    }
+    assert_eq!(1, ERRORS.load(Ordering::Relaxed));
+    assert!(error_handler() as usize == original as usize);
+    drop(outliving);
+}
+
+fn resolve_during_initialization() {
+    struct Reentrant;
+    let mut collection = ServiceCollection::new();
+    collection
+        .with::<WeakServiceProvider>()
+        .register_shared(|p| {
+            p.get::<Arc<Reentrant>>();
+            Arc::new(Reentrant)
+        });
+    let provider = collection.build().unwrap();
+    provider.get::<Arc<Reentrant>>();
+}
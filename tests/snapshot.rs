@@ -0,0 +1,71 @@
+// Snapshots depend on the optional serde feature
+#![cfg(feature = "serde")]
+
+use minfac::{Registered, SerializedInstances, ServiceCollection, SnapshotError};
+
+#[test]
+fn snapshot_contains_serializable_instances_only() {
+    let mut collection = ServiceCollection::new();
+    collection.register_serializable_instance(1u8);
+    collection.register_serializable_instance(2u8);
+    collection.register_serializable_instance(vec![String::from("a"), String::from("b")]);
+    collection.register_instance(3u16);
+    collection.with::<Registered<u8>>().register(|x| x as u32);
+
+    let bytes = collection.snapshot_instances().unwrap().to_bytes();
+    let snapshot = SerializedInstances::from_bytes(&bytes).unwrap();
+    assert!(snapshot.contains::<u8>());
+    assert!(snapshot.contains::<Vec<String>>());
+    assert!(!snapshot.contains::<u16>());
+    assert!(!snapshot.contains::<u32>());
+
+    let mut restored = ServiceCollection::new();
+    restored.load_instance::<u8>(&snapshot).unwrap();
+    restored
+        .load_instance::<Vec<String>>(&snapshot)
+        .unwrap()
+        .alias(|x| x.len());
+    assert!(matches!(
+        restored.load_instance::<u16>(&snapshot),
+        Err(SnapshotError::MissingInstance { name: "u16", .. })
+    ));
+    assert_eq!(snapshot, restored.snapshot_instances().unwrap());
+
+    let provider = restored.build().unwrap();
+    assert_eq!(Some(2u8), provider.get());
+    assert_eq!(Some(2usize), provider.get());
+}
+
+#[test]
+fn snapshots_of_other_format_versions_are_rejected() {
+    assert!(matches!(
+        SerializedInstances::from_bytes(br#"{"format_version":2,"instances":{}}"#),
+        Err(SnapshotError::UnsupportedVersion {
+            found: 2,
+            supported: 1,
+            ..
+        })
+    ));
+    assert!(matches!(
+        SerializedInstances::from_bytes(br#"{"instances":{}}"#),
+        Err(SnapshotError::Format(_))
+    ));
+    assert_eq!(
+        Ok(SerializedInstances::default()),
+        SerializedInstances::from_bytes(br#"{"format_version":1,"instances":{},"unknown":0}"#)
+            .map_err(|_| ())
+    );
+}
+
+#[test]
+fn set_instance_removes_replaced_instances_from_snapshot() {
+    let mut collection = ServiceCollection::new();
+    collection.register_serializable_instance(1u8);
+    collection.register_serializable_instance(2u16);
+    collection.set_instance(3u8);
+
+    let snapshot = collection.snapshot_instances().unwrap();
+    assert!(!snapshot.contains::<u8>());
+    assert!(snapshot.contains::<u16>());
+    assert_eq!(Some(3), collection.build().unwrap().get::<u8>());
+}
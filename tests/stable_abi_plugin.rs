@@ -0,0 +1,67 @@
+// Builds the plugin in tests/fixtures separately, so ids are computed by two compilation units like in real plugins
+#![cfg(feature = "plugin")]
+
+use abi_stable::std_types::RString;
+use minfac::{stable_abi::StableAbiStrategy, GenericServiceCollection, PluginHost};
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn build_plugin() -> PathBuf {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A separate target directory prevents waiting for the lock of the running `cargo test`
+    let target_dir = root.join("target").join("stable_abi_plugin");
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let mut command = Command::new(env!("CARGO"));
+    command
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(root.join("tests/fixtures/stable_abi_plugin/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir);
+    if !cfg!(debug_assertions) {
+        command.arg("--release");
+    }
+    // These features change the layout of types shared across the boundary, so the plugin needs the same
+    let features: Vec<_> = [
+        ("log", cfg!(feature = "log")),
+        (
+            "resolution_depth_limit",
+            cfg!(feature = "resolution_depth_limit"),
+        ),
+        ("runtime_checks", cfg!(feature = "runtime_checks")),
+        ("serde", cfg!(feature = "serde")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| format!("minfac/{name}"))
+    .collect();
+    if !features.is_empty() {
+        command.arg("--features").arg(features.join(","));
+    }
+    let status = command.status().expect("cargo should be executable");
+    assert!(status.success(), "Building the plugin failed");
+    target_dir
+        .join(profile)
+        .join(format!("{DLL_PREFIX}stable_abi_plugin{DLL_SUFFIX}"))
+}
+
+#[test]
+fn services_registered_by_separately_compiled_plugin_are_resolvable() {
+    let mut collection = GenericServiceCollection::<StableAbiStrategy>::new();
+    collection.register(|| 21u32);
+    let mut host = PluginHost::with_collection(collection);
+    unsafe { host.load(build_plugin()) }.expect("Plugin should be loadable");
+    let provider = host
+        .build()
+        .expect("Plugin dependencies should be registered");
+
+    assert_eq!(Some(42u64), provider.get::<u64>());
+    assert_eq!(Some(RString::from("plugin")), provider.get::<RString>());
+}
@@ -0,0 +1,42 @@
+// Replaces the global panic hook, so it lives in its own binary
+use minfac::{Registered, Resolver, ServiceCollection, WeakServiceProvider};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+static LOCATION: Mutex<Option<(String, u32)>> = Mutex::new(None);
+
+fn panic_location(f: impl FnOnce()) -> (String, u32) {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info.location().unwrap();
+        *LOCATION.lock().unwrap() = Some((location.file().to_string(), location.line()));
+    }));
+    assert!(catch_unwind(AssertUnwindSafe(f)).is_err());
+    let _ = std::panic::take_hook();
+    LOCATION.lock().unwrap().take().unwrap()
+}
+
+#[test]
+fn resolve_unchecked_reports_the_caller_of_each_provider_kind() {
+    let mut collection = ServiceCollection::new();
+    collection.with::<WeakServiceProvider>().register(|p| p);
+    let provider = collection.build().unwrap();
+    let weak = provider.get::<WeakServiceProvider>().unwrap();
+
+    let (file, line) = panic_location(|| {
+        provider.resolve_unchecked::<Registered<i32>>();
+    });
+    assert_eq!((file!(), line!() - 2), (file.as_str(), line));
+
+    let (file, line) = panic_location(|| {
+        weak.resolve_unchecked::<Registered<i32>>();
+    });
+    assert_eq!((file!(), line!() - 2), (file.as_str(), line));
+
+    let (file, line) = panic_location(|| {
+        Resolver::resolve_unchecked::<Registered<i32>>(&provider);
+    });
+    assert_eq!((file!(), line!() - 2), (file.as_str(), line));
+    drop(weak);
+}